@@ -0,0 +1,92 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use revault_tx::{
+    miniscript::{
+        bitcoin::{secp256k1, util::bip32, Address, Amount, Network, OutPoint, Txid},
+        DescriptorPublicKey,
+    },
+    scripts::{CpfpDescriptor, DepositDescriptor, EmergencyAddress, UnvaultDescriptor},
+    transactions::{transaction_chain, transaction_chains},
+};
+
+use std::str::FromStr;
+
+const N_DEPOSITS: u32 = 500;
+const CSV: u32 = 144;
+
+fn xpub(secp: &secp256k1::Secp256k1<secp256k1::All>, seed: u8) -> DescriptorPublicKey {
+    let xpriv = bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &[seed; 32]).unwrap();
+    DescriptorPublicKey::from_str(&format!(
+        "{}/*",
+        bip32::ExtendedPubKey::from_private(secp, &xpriv)
+    ))
+    .unwrap()
+}
+
+fn chains_benchmarks(c: &mut Criterion) {
+    let secp = secp256k1::Secp256k1::new();
+    let stakeholders: Vec<_> = (0..4).map(|i| xpub(&secp, i)).collect();
+    let managers: Vec<_> = (4..7).map(|i| xpub(&secp, i)).collect();
+    let cosigners: Vec<_> = (7..11).map(|i| xpub(&secp, i)).collect();
+    let deposit_descriptor = DepositDescriptor::new(stakeholders.clone()).unwrap();
+    let unvault_descriptor =
+        UnvaultDescriptor::new(stakeholders, managers.clone(), 2, cosigners, CSV).unwrap();
+    let cpfp_descriptor = CpfpDescriptor::new(managers).unwrap();
+    let emergency_address = EmergencyAddress::from_network(
+        Address::from_str("bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6")
+            .unwrap(),
+        Network::Bitcoin,
+    )
+    .unwrap();
+    let deposits: Vec<_> = (0..N_DEPOSITS)
+        .map(|i| {
+            (
+                OutPoint::new(Txid::default(), i),
+                Amount::from_sat(10_000_000),
+                bip32::ChildNumber::from(i),
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("500 deposits transaction chains");
+    group.sample_size(10);
+    group.bench_function("transaction_chain loop", |b| {
+        b.iter(|| {
+            black_box(&deposits)
+                .iter()
+                .map(|(outpoint, amount, index)| {
+                    transaction_chain(
+                        *outpoint,
+                        *amount,
+                        &deposit_descriptor,
+                        &unvault_descriptor,
+                        &cpfp_descriptor,
+                        *index,
+                        emergency_address.clone(),
+                        0,
+                        &secp,
+                    )
+                    .unwrap()
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("transaction_chains", |b| {
+        b.iter(|| {
+            transaction_chains(
+                black_box(&deposits),
+                &deposit_descriptor,
+                &unvault_descriptor,
+                &cpfp_descriptor,
+                &emergency_address,
+                0,
+                &secp,
+            )
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, chains_benchmarks);
+criterion_main!(benches);
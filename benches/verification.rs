@@ -0,0 +1,168 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use revault_tx::{
+    miniscript::{
+        bitcoin::{secp256k1, util::bip32, Amount, Network, OutPoint, TxOut, Txid},
+        DescriptorPublicKey, DescriptorTrait,
+    },
+    scripts::{CpfpDescriptor, DepositDescriptor, UnvaultDescriptor},
+    transactions::{
+        transaction_chain_manager, CancelTransaction, RevaultTransaction, SpendTransaction,
+    },
+    txins::UnvaultTxIn,
+    txouts::{SpendTxOut, UnvaultTxOut},
+};
+
+use std::str::FromStr;
+
+const N_TXS: u32 = 100;
+const CSV: u32 = 144;
+
+fn xpriv(seed: u8) -> bip32::ExtendedPrivKey {
+    bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &[seed; 32]).unwrap()
+}
+
+fn xpub(
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+    xpriv: &bip32::ExtendedPrivKey,
+) -> DescriptorPublicKey {
+    DescriptorPublicKey::from_str(&format!(
+        "{}/*",
+        bip32::ExtendedPubKey::from_private(secp, xpriv)
+    ))
+    .unwrap()
+}
+
+// Sign all the inputs of this transaction with these keys, derived at the given index per input
+fn sign(
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+    tx: &mut impl RevaultTransaction,
+    xprivs: &[bip32::ExtendedPrivKey],
+    indexes: &[bip32::ChildNumber],
+) {
+    let sighashes = tx.signature_hashes().unwrap();
+    let mut signatures = Vec::with_capacity(sighashes.len() * xprivs.len());
+    for (input_index, (sighash, index)) in sighashes.iter().zip(indexes.iter()).enumerate() {
+        let msg = secp256k1::Message::from_slice(sighash).unwrap();
+        for xpriv in xprivs {
+            let privkey = xpriv.derive_priv(secp, &[*index]).unwrap().private_key.key;
+            signatures.push((
+                input_index,
+                secp256k1::PublicKey::from_secret_key(secp, &privkey),
+                secp.sign(&msg, &privkey),
+            ));
+        }
+    }
+    tx.add_signatures(&signatures, secp).unwrap();
+}
+
+fn verification_benchmarks(c: &mut Criterion) {
+    let secp = secp256k1::Secp256k1::new();
+    let stakeholders_priv: Vec<_> = (0..4).map(xpriv).collect();
+    let managers_priv: Vec<_> = (4..7).map(xpriv).collect();
+    let cosigners_priv: Vec<_> = (7..11).map(xpriv).collect();
+    let xpubs = |xprivs: &[bip32::ExtendedPrivKey]| -> Vec<_> {
+        xprivs.iter().map(|xpriv| xpub(&secp, xpriv)).collect()
+    };
+    let deposit_descriptor = DepositDescriptor::new(xpubs(&stakeholders_priv)).unwrap();
+    let unvault_descriptor = UnvaultDescriptor::new(
+        xpubs(&stakeholders_priv),
+        xpubs(&managers_priv),
+        2,
+        xpubs(&cosigners_priv),
+        CSV,
+    )
+    .unwrap();
+    let cpfp_descriptor = CpfpDescriptor::new(xpubs(&managers_priv)).unwrap();
+    let indexes: Vec<bip32::ChildNumber> = (0..N_TXS).map(bip32::ChildNumber::from).collect();
+
+    // A Spend transaction batching 100 Unvault outputs
+    let unvault_txins = indexes
+        .iter()
+        .map(|index| {
+            UnvaultTxIn::new(
+                OutPoint::new(Txid::default(), u32::from(*index)),
+                UnvaultTxOut::new(
+                    Amount::from_sat(10_000_000),
+                    &unvault_descriptor.derive(*index, &secp),
+                ),
+                CSV,
+            )
+        })
+        .collect();
+    let spend_txouts = vec![SpendTxOut::Destination(TxOut {
+        value: 990_000_000,
+        script_pubkey: unvault_descriptor
+            .derive(0.into(), &secp)
+            .inner()
+            .script_pubkey(),
+    })];
+    let mut spend_tx = SpendTransaction::new(
+        unvault_txins,
+        spend_txouts,
+        &cpfp_descriptor.derive(0.into(), &secp),
+        0,
+        false,
+    )
+    .unwrap();
+    let spend_signers: Vec<_> = managers_priv[..2]
+        .iter()
+        .chain(cosigners_priv.iter())
+        .copied()
+        .collect();
+    c.bench_function("100 inputs Spend signature_hashes", |b| {
+        b.iter(|| black_box(&spend_tx).signature_hashes().unwrap())
+    });
+    sign(&secp, &mut spend_tx, &spend_signers, &indexes);
+    c.bench_function("100 inputs Spend is_finalizable", |b| {
+        b.iter(|| assert!(black_box(&spend_tx).is_finalizable(&secp)))
+    });
+    spend_tx.finalize(&secp).unwrap();
+    c.bench_function("100 inputs Spend verify_inputs", |b| {
+        b.iter(|| black_box(&spend_tx).verify_inputs().unwrap())
+    });
+
+    // 100 Cancel transactions, as checked by a watchtower
+    let cancel_txs: Vec<CancelTransaction> = indexes
+        .iter()
+        .map(|index| {
+            let (_, mut cancel_tx) = transaction_chain_manager(
+                OutPoint::new(Txid::default(), u32::from(*index)),
+                Amount::from_sat(10_000_000),
+                &deposit_descriptor,
+                &unvault_descriptor,
+                &cpfp_descriptor,
+                *index,
+                0,
+                &secp,
+            )
+            .unwrap();
+            sign(&secp, &mut cancel_tx, &stakeholders_priv, &[*index]);
+            cancel_tx
+        })
+        .collect();
+    c.bench_function("100 Cancel is_finalizable", |b| {
+        b.iter(|| {
+            for cancel_tx in black_box(&cancel_txs) {
+                assert!(cancel_tx.is_finalizable(&secp));
+            }
+        })
+    });
+    let cancel_txs: Vec<_> = cancel_txs
+        .into_iter()
+        .map(|mut cancel_tx| {
+            cancel_tx.finalize(&secp).unwrap();
+            cancel_tx
+        })
+        .collect();
+    c.bench_function("100 Cancel is_valid", |b| {
+        b.iter(|| {
+            for cancel_tx in black_box(&cancel_txs) {
+                assert!(cancel_tx.is_valid(&secp));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, verification_benchmarks);
+criterion_main!(benches);
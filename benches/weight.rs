@@ -0,0 +1,110 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use revault_tx::{
+    miniscript::{
+        bitcoin::{secp256k1, util::bip32, Amount, Network, OutPoint, TxOut, Txid},
+        descriptor::Wsh,
+        DescriptorPublicKey, DescriptorTrait, Miniscript,
+    },
+    scripts::{CpfpDescriptor, UnvaultDescriptor},
+    transactions::{RevaultTransaction, SpendTransaction},
+    txins::{RevaultTxIn, UnvaultTxIn},
+    txouts::{RevaultInternalTxOut, SpendTxOut, UnvaultTxOut},
+};
+
+use std::str::FromStr;
+
+const N_INPUTS: u32 = 100;
+const CSV: u32 = 144;
+
+fn xpub(secp: &secp256k1::Secp256k1<secp256k1::All>, seed: u8) -> DescriptorPublicKey {
+    let xpriv = bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &[seed; 32]).unwrap();
+    DescriptorPublicKey::from_str(&format!(
+        "{}/*",
+        bip32::ExtendedPubKey::from_private(secp, &xpriv)
+    ))
+    .unwrap()
+}
+
+// A Spend transaction batching 100 Unvault outputs
+fn spend_inputs(
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+) -> (Vec<UnvaultTxIn>, Vec<SpendTxOut>, CpfpDescriptor) {
+    let stakeholders: Vec<_> = (0..4).map(|i| xpub(secp, i)).collect();
+    let managers: Vec<_> = (4..7).map(|i| xpub(secp, i)).collect();
+    let cosigners: Vec<_> = (7..11).map(|i| xpub(secp, i)).collect();
+    let unvault_descriptor =
+        UnvaultDescriptor::new(stakeholders, managers.clone(), 2, cosigners, CSV).unwrap();
+    let cpfp_descriptor = CpfpDescriptor::new(managers).unwrap();
+
+    let unvault_txins = (0..N_INPUTS)
+        .map(|i| {
+            let derived = unvault_descriptor.derive(i.into(), secp);
+            UnvaultTxIn::new(
+                OutPoint::new(Txid::default(), i),
+                UnvaultTxOut::new(Amount::from_sat(10_000_000), &derived),
+                CSV,
+            )
+        })
+        .collect();
+    let spend_txouts = vec![SpendTxOut::Destination(TxOut {
+        value: 990_000_000,
+        script_pubkey: unvault_descriptor
+            .derive(0.into(), secp)
+            .inner()
+            .script_pubkey(),
+    })];
+
+    (unvault_txins, spend_txouts, cpfp_descriptor)
+}
+
+fn weight_benchmarks(c: &mut Criterion) {
+    let secp = secp256k1::Secp256k1::new();
+    let (unvault_txins, spend_txouts, cpfp_descriptor) = spend_inputs(&secp);
+    let cpfp_descriptor = cpfp_descriptor.derive(0.into(), &secp);
+    let spend_tx = SpendTransaction::new(
+        unvault_txins.clone(),
+        spend_txouts.clone(),
+        &cpfp_descriptor,
+        0,
+        false,
+    )
+    .unwrap();
+    let raw_psbt = spend_tx.as_psbt_serialized();
+
+    // What every weight query used to cost: reparsing the Miniscript of each input
+    c.bench_function("100 inputs Miniscript analysis", |b| {
+        b.iter(|| {
+            unvault_txins
+                .iter()
+                .map(|txin| {
+                    Wsh::new(Miniscript::parse(txin.txout().witness_script()).unwrap())
+                        .unwrap()
+                        .max_satisfaction_weight()
+                        .unwrap()
+                })
+                .sum::<usize>()
+        })
+    });
+    c.bench_function("100 inputs Spend creation", |b| {
+        b.iter(|| {
+            SpendTransaction::new(
+                black_box(unvault_txins.clone()),
+                black_box(spend_txouts.clone()),
+                &cpfp_descriptor,
+                0,
+                false,
+            )
+            .unwrap()
+        })
+    });
+    c.bench_function("100 inputs Spend max_weight", |b| {
+        b.iter(|| black_box(&spend_tx).max_weight())
+    });
+    c.bench_function("100 inputs Spend parsing", |b| {
+        b.iter(|| SpendTransaction::from_psbt_serialized(black_box(&raw_psbt)).unwrap())
+    });
+}
+
+criterion_group!(benches, weight_benchmarks);
+criterion_main!(benches);
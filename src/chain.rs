@@ -0,0 +1,637 @@
+//! # Revault chain processing
+//!
+//! Drive the state of a set of [vaults](crate::vault::Vault) from the blocks and the mempool
+//! transactions, as a watchtower would. The transactions are matched against the vaults by the
+//! txids of their pre-signed transactions, and by the Unvault outpoint for the Spend
+//! transactions. Reorganisations are supported by disconnecting the tip.
+
+use crate::{
+    error::{ChainError, VaultError},
+    scripts::RevaultDescriptors,
+    transactions::RevaultTransaction,
+    vault::{PresignedTransaction, Vault, VaultEvent, VaultStatus},
+};
+
+use miniscript::bitcoin::{secp256k1, Block, BlockHash, OutPoint, Transaction, Txid};
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// The number of blocks we can disconnect.
+pub const MAX_REORG_DEPTH: usize = 100;
+
+/// Something that happened to a vault, as reported by the [ChainProcessor].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// The deposit of this vault got confirmed
+    DepositConfirmed {
+        deposit_outpoint: OutPoint,
+        height: u32,
+    },
+    /// The Unvault transaction of this vault was broadcast
+    UnvaultBroadcast { deposit_outpoint: OutPoint },
+    /// The Unvault transaction of this vault got confirmed. It can be spent by the managers
+    /// starting from the block at height `csv_maturity`.
+    UnvaultConfirmed {
+        deposit_outpoint: OutPoint,
+        height: u32,
+        csv_maturity: u32,
+    },
+    /// A transaction spending the Unvault output of this vault was broadcast, and it is not a
+    /// revocation transaction.
+    SpendBroadcast {
+        deposit_outpoint: OutPoint,
+        txid: Txid,
+    },
+    /// A Spend transaction for this vault got confirmed
+    SpendConfirmed {
+        deposit_outpoint: OutPoint,
+        txid: Txid,
+        height: u32,
+    },
+    /// A revocation transaction of this vault was broadcast
+    RevocationBroadcast {
+        deposit_outpoint: OutPoint,
+        transaction: PresignedTransaction,
+    },
+    /// A revocation transaction of this vault got confirmed
+    RevocationConfirmed {
+        deposit_outpoint: OutPoint,
+        transaction: PresignedTransaction,
+        height: u32,
+    },
+    /// A block was disconnected, and this vault went back to this state
+    Reorged {
+        deposit_outpoint: OutPoint,
+        status: VaultStatus,
+    },
+    /// A transaction of this vault was observed, but the vault could not transition
+    Unexpected {
+        deposit_outpoint: OutPoint,
+        txid: Txid,
+        error: VaultError,
+    },
+}
+
+/// Tracks a set of vaults and updates them according to the chain and the mempool.
+#[derive(Debug, Clone)]
+pub struct ChainProcessor {
+    unvault_csv: u32,
+    vaults: HashMap<OutPoint, Vault>,
+    // The txids of the vaults' pre-signed transactions, and the deposit outpoint of the vault
+    presigned_txids: HashMap<Txid, (OutPoint, PresignedTransaction)>,
+    // The vaults' Unvault outpoints, and the deposit outpoint of the vault
+    unvault_outpoints: HashMap<OutPoint, OutPoint>,
+    // The mempool transactions we already processed
+    mempool_txids: HashSet<Txid>,
+    tip: Option<(u32, BlockHash)>,
+    // For each connected block, the vaults it modified as they were before it
+    undo: VecDeque<(BlockHash, BTreeMap<OutPoint, Vault>)>,
+}
+
+impl ChainProcessor {
+    /// Create a processor for the vaults of this deployment. The processor does not track any
+    /// vault yet and will accept any block as the first one.
+    pub fn new(descriptors: &RevaultDescriptors) -> ChainProcessor {
+        ChainProcessor {
+            unvault_csv: descriptors.unvault_descriptor().csv_value(),
+            vaults: HashMap::new(),
+            presigned_txids: HashMap::new(),
+            unvault_outpoints: HashMap::new(),
+            mempool_txids: HashSet::new(),
+            tip: None,
+            undo: VecDeque::with_capacity(MAX_REORG_DEPTH),
+        }
+    }
+
+    /// Start tracking this vault, replacing any vault for the same deposit.
+    pub fn add_vault(&mut self, vault: Vault) {
+        let deposit_outpoint = vault.deposit_outpoint();
+        let presigned_txids = [
+            (vault.unvault_tx().txid(), PresignedTransaction::Unvault),
+            (vault.cancel_tx().txid(), PresignedTransaction::Cancel),
+            (vault.emergency_tx().txid(), PresignedTransaction::Emergency),
+            (
+                vault.unvault_emergency_tx().txid(),
+                PresignedTransaction::UnvaultEmergency,
+            ),
+        ];
+        for (txid, transaction) in presigned_txids.iter() {
+            self.presigned_txids
+                .insert(*txid, (deposit_outpoint, *transaction));
+        }
+        self.unvault_outpoints
+            .insert(vault.unvault_outpoint(), deposit_outpoint);
+        self.vaults.insert(deposit_outpoint, vault);
+    }
+
+    /// Get the vault for this deposit, if we track it.
+    pub fn vault(&self, deposit_outpoint: &OutPoint) -> Option<&Vault> {
+        self.vaults.get(deposit_outpoint)
+    }
+
+    /// Get the vault for this deposit, if we track it, for instance to add signatures.
+    pub fn vault_mut(&mut self, deposit_outpoint: &OutPoint) -> Option<&mut Vault> {
+        self.vaults.get_mut(deposit_outpoint)
+    }
+
+    /// Get all the vaults we track.
+    pub fn vaults(&self) -> impl Iterator<Item = &Vault> {
+        self.vaults.values()
+    }
+
+    /// The height and hash of the last connected block, if any.
+    pub fn tip(&self) -> Option<(u32, BlockHash)> {
+        self.tip
+    }
+
+    /// Process the transactions of a new block at this height.
+    ///
+    /// # Errors
+    /// - If the block does not connect to the tip.
+    pub fn connect_block<C: secp256k1::Verification>(
+        &mut self,
+        block: &Block,
+        height: u32,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<ChainEvent>, ChainError> {
+        let block_hash = block.block_hash();
+        if let Some((tip_height, tip_hash)) = self.tip {
+            if block.header.prev_blockhash != tip_hash || Some(height) != tip_height.checked_add(1)
+            {
+                return Err(ChainError::UnconnectedBlock(block_hash));
+            }
+        }
+
+        let mut undo = BTreeMap::new();
+        let mut events = Vec::new();
+        for tx in block.txdata.iter() {
+            self.process_transaction(tx, Some(height), &mut undo, &mut events, secp);
+            self.mempool_txids.remove(&tx.txid());
+        }
+
+        if self.undo.len() == MAX_REORG_DEPTH {
+            self.undo.pop_front();
+        }
+        self.undo.push_back((block_hash, undo));
+        self.tip = Some((height, block_hash));
+
+        Ok(events)
+    }
+
+    /// Disconnect the tip block, reverting the vaults it modified to their previous state.
+    ///
+    /// Note that the vaults are reverted to their state *before* the block, mempool transactions
+    /// processed after it are forgotten and need to be processed again.
+    ///
+    /// # Errors
+    /// - If the block is not the tip.
+    /// - If we disconnected more than [MAX_REORG_DEPTH] blocks.
+    pub fn disconnect_block(&mut self, block: &Block) -> Result<Vec<ChainEvent>, ChainError> {
+        let block_hash = block.block_hash();
+        let tip_height = match self.tip {
+            Some((height, hash)) if hash == block_hash => height,
+            _ => return Err(ChainError::NotTip(block_hash)),
+        };
+        let (undo_hash, undo) = self.undo.pop_back().ok_or(ChainError::ReorgTooDeep)?;
+        assert_eq!(undo_hash, block_hash, "Undo data is always for the tip");
+
+        self.mempool_txids.clear();
+        self.tip = tip_height
+            .checked_sub(1)
+            .map(|height| (height, block.header.prev_blockhash));
+
+        Ok(undo
+            .into_iter()
+            .map(|(deposit_outpoint, vault)| {
+                let status = vault.status();
+                self.vaults.insert(deposit_outpoint, vault);
+                ChainEvent::Reorged {
+                    deposit_outpoint,
+                    status,
+                }
+            })
+            .collect())
+    }
+
+    /// Process a transaction that entered the mempool. A transaction is only processed once.
+    pub fn process_mempool_transaction<C: secp256k1::Verification>(
+        &mut self,
+        tx: &Transaction,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Vec<ChainEvent> {
+        let mut events = Vec::new();
+        if self.mempool_txids.insert(tx.txid()) {
+            // Mempool transactions can't be disconnected
+            let mut undo = BTreeMap::new();
+            self.process_transaction(tx, None, &mut undo, &mut events, secp);
+        }
+
+        events
+    }
+
+    // Update the vaults this transaction, confirmed at `height` or unconfirmed, relates to.
+    fn process_transaction<C: secp256k1::Verification>(
+        &mut self,
+        tx: &Transaction,
+        height: Option<u32>,
+        undo: &mut BTreeMap<OutPoint, Vault>,
+        events: &mut Vec<ChainEvent>,
+        secp: &secp256k1::Secp256k1<C>,
+    ) {
+        let txid = tx.txid();
+
+        if let Some(height) = height {
+            for vout in 0..tx.output.len() {
+                let deposit_outpoint = OutPoint::new(txid, vout as u32);
+                if self.vaults.contains_key(&deposit_outpoint) {
+                    self.apply_event(
+                        deposit_outpoint,
+                        txid,
+                        VaultEvent::DepositConfirmed,
+                        ChainEvent::DepositConfirmed {
+                            deposit_outpoint,
+                            height,
+                        },
+                        undo,
+                        events,
+                        secp,
+                    );
+                }
+            }
+        }
+
+        if let Some((deposit_outpoint, transaction)) = self.presigned_txids.get(&txid).copied() {
+            let (vault_event, chain_event) = match (transaction, height) {
+                (PresignedTransaction::Unvault, None) => (
+                    VaultEvent::UnvaultBroadcast,
+                    ChainEvent::UnvaultBroadcast { deposit_outpoint },
+                ),
+                (PresignedTransaction::Unvault, Some(height)) => (
+                    VaultEvent::UnvaultConfirmed,
+                    ChainEvent::UnvaultConfirmed {
+                        deposit_outpoint,
+                        height,
+                        csv_maturity: height.saturating_add(self.unvault_csv),
+                    },
+                ),
+                (transaction, None) => (
+                    match transaction {
+                        PresignedTransaction::Cancel => VaultEvent::CancelBroadcast,
+                        PresignedTransaction::Emergency => VaultEvent::EmergencyBroadcast,
+                        _ => VaultEvent::UnvaultEmergencyBroadcast,
+                    },
+                    ChainEvent::RevocationBroadcast {
+                        deposit_outpoint,
+                        transaction,
+                    },
+                ),
+                (transaction, Some(height)) => (
+                    match transaction {
+                        PresignedTransaction::Cancel => VaultEvent::CancelConfirmed,
+                        PresignedTransaction::Emergency => VaultEvent::EmergencyConfirmed,
+                        _ => VaultEvent::UnvaultEmergencyConfirmed,
+                    },
+                    ChainEvent::RevocationConfirmed {
+                        deposit_outpoint,
+                        transaction,
+                        height,
+                    },
+                ),
+            };
+            self.apply_event(
+                deposit_outpoint,
+                txid,
+                vault_event,
+                chain_event,
+                undo,
+                events,
+                secp,
+            );
+        } else {
+            // Any other transaction spending an Unvault output is a Spend attempt
+            let spent_vaults: Vec<OutPoint> = tx
+                .input
+                .iter()
+                .filter_map(|txin| self.unvault_outpoints.get(&txin.previous_output))
+                .copied()
+                .collect();
+            for deposit_outpoint in spent_vaults {
+                let (vault_event, chain_event) = match height {
+                    None => (
+                        VaultEvent::SpendBroadcast(txid),
+                        ChainEvent::SpendBroadcast {
+                            deposit_outpoint,
+                            txid,
+                        },
+                    ),
+                    Some(height) => (
+                        VaultEvent::SpendConfirmed(txid),
+                        ChainEvent::SpendConfirmed {
+                            deposit_outpoint,
+                            txid,
+                            height,
+                        },
+                    ),
+                };
+                self.apply_event(
+                    deposit_outpoint,
+                    txid,
+                    vault_event,
+                    chain_event,
+                    undo,
+                    events,
+                    secp,
+                );
+            }
+        }
+    }
+
+    // Apply this event to the vault, recording its previous state on success.
+    #[allow(clippy::too_many_arguments)]
+    fn apply_event<C: secp256k1::Verification>(
+        &mut self,
+        deposit_outpoint: OutPoint,
+        txid: Txid,
+        vault_event: VaultEvent,
+        chain_event: ChainEvent,
+        undo: &mut BTreeMap<OutPoint, Vault>,
+        events: &mut Vec<ChainEvent>,
+        secp: &secp256k1::Secp256k1<C>,
+    ) {
+        let vault = self
+            .vaults
+            .get_mut(&deposit_outpoint)
+            .expect("We only track outpoints of registered vaults");
+        let previous_vault = vault.clone();
+
+        match vault.process_event(vault_event, secp) {
+            Ok(_) => {
+                undo.entry(deposit_outpoint).or_insert(previous_vault);
+                events.push(chain_event);
+            }
+            Err(error) => events.push(ChainEvent::Unexpected {
+                deposit_outpoint,
+                txid,
+                error,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChainEvent, ChainProcessor};
+    use crate::{
+        error::{ChainError, VaultError},
+        scripts::RevaultDescriptors,
+        test_utils::{descriptors, get_random_privkey, sign_vault, xpubs},
+        transactions::RevaultTransaction,
+        vault::{PresignedTransaction, Vault, VaultEvent, VaultStatus},
+    };
+
+    use miniscript::{
+        bitcoin::{
+            blockdata::constants::genesis_block, hashes::Hash, secp256k1, util::bip32, Amount,
+            Block, BlockHeader, Network, OutPoint, Transaction, TxIn, TxMerkleNode, TxOut,
+        },
+        DescriptorTrait,
+    };
+
+    // Get an Active vault for this deposit
+    fn active_vault(
+        deposit_outpoint: OutPoint,
+        descriptors: &RevaultDescriptors,
+        stakeholders: &[bip32::ExtendedPrivKey],
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+    ) -> Vault {
+        let derivation_index = bip32::ChildNumber::from(deposit_outpoint.vout);
+        let mut vault = Vault::new(
+            deposit_outpoint,
+            Amount::from_sat(10_000_000),
+            derivation_index,
+            descriptors,
+            0,
+            secp,
+        )
+        .unwrap();
+
+        for transaction in &[
+            PresignedTransaction::Cancel,
+            PresignedTransaction::Emergency,
+            PresignedTransaction::UnvaultEmergency,
+            PresignedTransaction::Unvault,
+        ] {
+            if *transaction == PresignedTransaction::Unvault {
+                vault
+                    .process_event(VaultEvent::DepositConfirmed, secp)
+                    .unwrap();
+            }
+            sign_vault(&mut vault, *transaction, stakeholders, secp).unwrap();
+        }
+        assert_eq!(vault.status(), VaultStatus::Active);
+
+        vault
+    }
+
+    fn next_block(prev: &Block, txdata: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                version: 2,
+                prev_blockhash: prev.block_hash(),
+                merkle_root: TxMerkleNode::hash(&[txdata.len() as u8]),
+                time: prev.header.time + 600,
+                bits: prev.header.bits,
+                nonce: 0,
+            },
+            txdata,
+        }
+    }
+
+    #[test]
+    fn chain_processor() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let stakeholders: Vec<_> = (0..2).map(|_| get_random_privkey(&mut rng)).collect();
+        let managers: Vec<_> = (0..2).map(|_| get_random_privkey(&mut rng)).collect();
+        let descriptors = descriptors(xpubs(&stakeholders, &secp), xpubs(&managers, &secp), 1, 6);
+        let mut processor = ChainProcessor::new(&descriptors);
+
+        // A deposit transaction creating two vaults
+        let deposit_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: (0..2)
+                .map(|i| TxOut {
+                    value: 10_000_000,
+                    script_pubkey: descriptors
+                        .deposit_descriptor()
+                        .derive(bip32::ChildNumber::from(i), &secp)
+                        .inner()
+                        .script_pubkey(),
+                })
+                .collect(),
+        };
+        let (first_deposit, second_deposit) = (
+            OutPoint::new(deposit_tx.txid(), 0),
+            OutPoint::new(deposit_tx.txid(), 1),
+        );
+        let first_vault = active_vault(first_deposit, &descriptors, &stakeholders, &secp);
+        let second_vault = active_vault(second_deposit, &descriptors, &stakeholders, &secp);
+        let (first_unvault, first_cancel) = (
+            first_vault.unvault_tx().clone().into_tx(),
+            first_vault.cancel_tx().clone().into_tx(),
+        );
+        let second_unvault = second_vault.unvault_tx().clone().into_tx();
+        // They were confirmed already when signing, get them back to unconfirmed
+        processor.add_vault(
+            Vault::new(
+                first_deposit,
+                Amount::from_sat(10_000_000),
+                bip32::ChildNumber::from(0),
+                &descriptors,
+                0,
+                &secp,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            processor.vault(&first_deposit).unwrap().status(),
+            VaultStatus::Unconfirmed
+        );
+        processor.add_vault(first_vault);
+        processor.add_vault(second_vault);
+
+        let genesis = genesis_block(Network::Bitcoin);
+        assert!(processor
+            .connect_block(&genesis, 0, &secp)
+            .unwrap()
+            .is_empty());
+        assert_eq!(processor.tip(), Some((0, genesis.block_hash())));
+
+        // The blocks must connect
+        let block = next_block(&genesis, vec![first_unvault.clone()]);
+        assert_eq!(
+            processor.connect_block(&block, 2, &secp),
+            Err(ChainError::UnconnectedBlock(block.block_hash()))
+        );
+        assert_eq!(
+            processor.connect_block(&next_block(&block, vec![]), 1, &secp),
+            Err(ChainError::UnconnectedBlock(
+                next_block(&block, vec![]).block_hash()
+            ))
+        );
+
+        // First unvault seen in the mempool, only once
+        assert_eq!(
+            processor.process_mempool_transaction(&first_unvault, &secp),
+            vec![ChainEvent::UnvaultBroadcast {
+                deposit_outpoint: first_deposit
+            }]
+        );
+        assert!(processor
+            .process_mempool_transaction(&first_unvault, &secp)
+            .is_empty());
+        assert_eq!(
+            processor.connect_block(&block, 1, &secp).unwrap(),
+            vec![ChainEvent::UnvaultConfirmed {
+                deposit_outpoint: first_deposit,
+                height: 1,
+                csv_maturity: 7
+            }]
+        );
+
+        // A spend attempt for the first vault, and the second vault unvaults
+        let spend_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: processor.vault(&first_deposit).unwrap().unvault_outpoint(),
+                ..TxIn::default()
+            }],
+            output: vec![TxOut::default()],
+        };
+        assert_eq!(
+            processor.process_mempool_transaction(&spend_tx, &secp),
+            vec![ChainEvent::SpendBroadcast {
+                deposit_outpoint: first_deposit,
+                txid: spend_tx.txid()
+            }]
+        );
+        let block_2 = next_block(&block, vec![second_unvault.clone(), first_cancel.clone()]);
+        let events = processor.connect_block(&block_2, 2, &secp).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                ChainEvent::UnvaultConfirmed {
+                    deposit_outpoint: second_deposit,
+                    height: 2,
+                    csv_maturity: 8
+                },
+                ChainEvent::RevocationConfirmed {
+                    deposit_outpoint: first_deposit,
+                    transaction: PresignedTransaction::Cancel,
+                    height: 2
+                }
+            ]
+        );
+        assert_eq!(
+            processor.vault(&first_deposit).unwrap().status(),
+            VaultStatus::Canceled
+        );
+
+        // Now the Spend can't confirm
+        let block_3 = next_block(&block_2, vec![spend_tx.clone()]);
+        assert_eq!(
+            processor.connect_block(&block_3, 3, &secp).unwrap(),
+            vec![ChainEvent::Unexpected {
+                deposit_outpoint: first_deposit,
+                txid: spend_tx.txid(),
+                error: VaultError::InvalidTransition(
+                    VaultStatus::Canceled,
+                    VaultEvent::SpendConfirmed(spend_tx.txid())
+                ),
+            }]
+        );
+
+        // Reorg the last two blocks
+        assert_eq!(
+            processor.disconnect_block(&block),
+            Err(ChainError::NotTip(block.block_hash()))
+        );
+        assert!(processor.disconnect_block(&block_3).unwrap().is_empty());
+        assert_eq!(
+            processor.disconnect_block(&block_2).unwrap(),
+            vec![
+                ChainEvent::Reorged {
+                    deposit_outpoint: first_deposit,
+                    status: VaultStatus::Spending
+                },
+                ChainEvent::Reorged {
+                    deposit_outpoint: second_deposit,
+                    status: VaultStatus::Active
+                }
+            ]
+        );
+        assert_eq!(processor.tip(), Some((1, block.block_hash())));
+
+        // The Spend confirms in the new chain
+        let block_2 = next_block(&block, vec![spend_tx.clone()]);
+        assert_eq!(
+            processor.connect_block(&block_2, 2, &secp).unwrap(),
+            vec![ChainEvent::SpendConfirmed {
+                deposit_outpoint: first_deposit,
+                txid: spend_tx.txid(),
+                height: 2
+            }]
+        );
+        assert_eq!(
+            processor.vault(&first_deposit).unwrap().status(),
+            VaultStatus::Spent
+        );
+        assert_eq!(
+            processor.vault(&first_deposit).unwrap().spend_txid(),
+            Some(spend_tx.txid())
+        );
+    }
+}
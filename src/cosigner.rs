@@ -0,0 +1,377 @@
+//! # Revault cosigning server
+//!
+//! The cosigners of the [Unvault descriptor](crate::scripts::UnvaultDescriptor) make sure an
+//! Unvault output is only ever spent by a single [Spend
+//! transaction](crate::transactions::SpendTransaction). A [Cosigner] checks the Spend
+//! transactions it's asked to sign and refuses to sign for an Unvault output it already signed
+//! in another transaction. Its signing history is kept in a [SigningHistory] backend.
+
+use crate::{
+    error::CosignerError,
+    scripts::UnvaultDescriptor,
+    transactions::{RevaultTransaction, SpendTransaction},
+};
+
+use miniscript::{
+    bitcoin::{secp256k1, util::bip32, OutPoint, Script, Txid},
+    DescriptorTrait,
+};
+
+use std::{collections::HashMap, fmt};
+
+/// The storage of the Spend transactions a cosigner signed.
+pub trait SigningHistory {
+    /// The error returned by the storage backend
+    type Error: fmt::Display;
+
+    /// Get the Spend transaction we signed for this Unvault outpoint, if any.
+    fn signed_for(&self, unvault_outpoint: &OutPoint) -> Result<Option<Txid>, Self::Error>;
+
+    /// Record that we signed the Spend transaction `spend_txid` for these Unvault outpoints.
+    fn record(
+        &mut self,
+        unvault_outpoints: &[OutPoint],
+        spend_txid: Txid,
+    ) -> Result<(), Self::Error>;
+}
+
+/// A [SigningHistory] that is not persisted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InMemorySigningHistory {
+    signed: HashMap<OutPoint, Txid>,
+}
+
+impl InMemorySigningHistory {
+    /// An empty history
+    pub fn new() -> InMemorySigningHistory {
+        InMemorySigningHistory::default()
+    }
+}
+
+impl SigningHistory for InMemorySigningHistory {
+    type Error = std::convert::Infallible;
+
+    fn signed_for(&self, unvault_outpoint: &OutPoint) -> Result<Option<Txid>, Self::Error> {
+        Ok(self.signed.get(unvault_outpoint).copied())
+    }
+
+    fn record(
+        &mut self,
+        unvault_outpoints: &[OutPoint],
+        spend_txid: Txid,
+    ) -> Result<(), Self::Error> {
+        for outpoint in unvault_outpoints {
+            self.signed.insert(*outpoint, spend_txid);
+        }
+        Ok(())
+    }
+}
+
+/// A cosigning server, signing Spend transactions with its static key.
+#[derive(Debug)]
+pub struct Cosigner<H: SigningHistory> {
+    secret_key: secp256k1::SecretKey,
+    public_key: secp256k1::PublicKey,
+    csv_value: u32,
+    // The Unvault scriptPubKeys for all the derivation indexes we accept
+    unvault_script_pubkeys: HashMap<Script, bip32::ChildNumber>,
+    history: H,
+}
+
+impl<H: SigningHistory> Cosigner<H> {
+    /// Create a cosigner signing with `secret_key` the Spend transactions of the Unvault
+    /// outputs for the derivation indexes `0` to `derivation_window` (excluded) of the
+    /// `unvault_descriptor`.
+    ///
+    /// Will error if the public key of `secret_key` is not one of the cosigners keys of the
+    /// `unvault_descriptor`.
+    pub fn new<C: secp256k1::Signing + secp256k1::Verification>(
+        unvault_descriptor: &UnvaultDescriptor,
+        secret_key: secp256k1::SecretKey,
+        derivation_window: u32,
+        history: H,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Cosigner<H>, CosignerError> {
+        let public_key = secp256k1::PublicKey::from_secret_key(secp, &secret_key);
+        // We sign with a static key, so it can't be a wildcard xpub
+        let is_cosigner = unvault_descriptor.cosigners().iter().any(|key| {
+            key.derive_public_key(secp)
+                .map(|pubkey| pubkey.key == public_key)
+                .unwrap_or(false)
+        });
+        if !is_cosigner {
+            return Err(CosignerError::UnknownKey(public_key));
+        }
+
+        let unvault_script_pubkeys = (0..derivation_window.min(1 << 31))
+            .map(|index| {
+                let child_number = bip32::ChildNumber::from(index);
+                (
+                    unvault_descriptor
+                        .derive(child_number, secp)
+                        .inner()
+                        .script_pubkey(),
+                    child_number,
+                )
+            })
+            .collect();
+
+        Ok(Cosigner {
+            secret_key,
+            public_key,
+            csv_value: unvault_descriptor.csv_value(),
+            unvault_script_pubkeys,
+            history,
+        })
+    }
+
+    /// Our public key
+    pub fn public_key(&self) -> secp256k1::PublicKey {
+        self.public_key
+    }
+
+    /// The signing history backend
+    pub fn history(&self) -> &H {
+        &self.history
+    }
+
+    /// Check this Spend transaction and add our signature to all its inputs.
+    ///
+    /// Signing again the same Spend transaction is fine, but we refuse to sign a Spend transaction
+    /// spending an Unvault output we already signed for in another transaction.
+    ///
+    /// # Errors
+    /// - If an input is not an (unfinalized) Unvault output of our descriptor.
+    /// - If an input's nSequence is not the Unvault CSV.
+    /// - If an input was already signed for in another Spend transaction.
+    /// - If the signing history backend failed.
+    pub fn sign<C: secp256k1::Signing + secp256k1::Verification>(
+        &mut self,
+        spend_tx: &mut SpendTransaction,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), CosignerError> {
+        let spend_txid = spend_tx.txid();
+        let psbt = spend_tx.psbt();
+        let mut unvault_outpoints = Vec::with_capacity(psbt.inputs.len());
+
+        for (txin, psbtin) in psbt.global.unsigned_tx.input.iter().zip(psbt.inputs.iter()) {
+            let outpoint = txin.previous_output;
+            let is_unvault = match (&psbtin.witness_script, &psbtin.witness_utxo) {
+                (Some(witness_script), Some(prev_txo)) => {
+                    witness_script.to_v0_p2wsh() == prev_txo.script_pubkey
+                        && self
+                            .unvault_script_pubkeys
+                            .contains_key(&prev_txo.script_pubkey)
+                }
+                _ => false,
+            };
+            if !is_unvault {
+                return Err(CosignerError::NotUnvaultInput(outpoint));
+            }
+
+            if txin.sequence != self.csv_value {
+                return Err(CosignerError::InvalidSequence(outpoint, txin.sequence));
+            }
+
+            match self
+                .history
+                .signed_for(&outpoint)
+                .map_err(|e| CosignerError::History(e.to_string()))?
+            {
+                Some(txid) if txid != spend_txid => {
+                    return Err(CosignerError::AlreadySigned(outpoint, txid))
+                }
+                _ => unvault_outpoints.push(outpoint),
+            }
+        }
+
+        let signatures: Vec<_> = spend_tx
+            .signature_hashes()?
+            .iter()
+            .enumerate()
+            .map(|(input_index, sighash)| {
+                let sighash = secp256k1::Message::from_slice(sighash).expect("sighash is 32 bytes");
+                (
+                    input_index,
+                    self.public_key,
+                    secp.sign(&sighash, &self.secret_key),
+                )
+            })
+            .collect();
+
+        // Record before handing out the signatures, we'd rather fail to sign than sign twice.
+        self.history
+            .record(&unvault_outpoints, spend_txid)
+            .map_err(|e| CosignerError::History(e.to_string()))?;
+        spend_tx.add_signatures(&signatures, secp)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cosigner, InMemorySigningHistory, SigningHistory};
+    use crate::{
+        error::CosignerError,
+        scripts::{CpfpDescriptor, UnvaultDescriptor},
+        test_utils::{cosigner_pubkey, get_random_pubkey, get_random_secret_key},
+        transactions::{RevaultTransaction, SpendTransaction},
+        txins::UnvaultTxIn,
+        txouts::{SpendTxOut, UnvaultTxOut},
+    };
+
+    use miniscript::{
+        bitcoin::{secp256k1, util::bip32, Amount, OutPoint, TxOut, Txid},
+        DescriptorTrait,
+    };
+
+    #[test]
+    fn cosigner() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let secret_keys: Vec<_> = (0..2).map(|_| get_random_secret_key(&mut rng)).collect();
+        let cosigners = secret_keys
+            .iter()
+            .map(|sk| cosigner_pubkey(sk, &secp))
+            .collect();
+        let managers: Vec<_> = (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let unvault_descriptor = UnvaultDescriptor::new_with_thresholds(
+            (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect(),
+            2,
+            managers.clone(),
+            1,
+            cosigners,
+            None,
+            12,
+        )
+        .unwrap();
+        let cpfp_descriptor = CpfpDescriptor::new(managers).unwrap();
+
+        // Only a cosigner of the descriptor can sign
+        let unknown_secret_key = get_random_secret_key(&mut rng);
+        assert_eq!(
+            Cosigner::new(
+                &unvault_descriptor,
+                unknown_secret_key,
+                10,
+                InMemorySigningHistory::new(),
+                &secp,
+            )
+            .unwrap_err(),
+            CosignerError::UnknownKey(secp256k1::PublicKey::from_secret_key(
+                &secp,
+                &unknown_secret_key
+            ))
+        );
+
+        let mut cosigner = Cosigner::new(
+            &unvault_descriptor,
+            secret_keys[0],
+            10,
+            InMemorySigningHistory::new(),
+            &secp,
+        )
+        .unwrap();
+        let unvault_txin = |index: u32, vout: u32, sequence: u32| {
+            UnvaultTxIn::new(
+                OutPoint::new(Txid::default(), vout),
+                UnvaultTxOut::new(
+                    Amount::from_sat(10_000_000),
+                    &unvault_descriptor.derive(bip32::ChildNumber::from(index), &secp),
+                ),
+                sequence,
+            )
+        };
+        let spend = |unvault_inputs: Vec<UnvaultTxIn>, value: u64| {
+            SpendTransaction::new(
+                unvault_inputs,
+                vec![SpendTxOut::Destination(TxOut {
+                    value,
+                    script_pubkey: cpfp_descriptor
+                        .derive(bip32::ChildNumber::from(1), &secp)
+                        .into_inner()
+                        .script_pubkey(),
+                })],
+                &cpfp_descriptor.derive(bip32::ChildNumber::from(0), &secp),
+                0,
+                false,
+            )
+            .unwrap()
+        };
+
+        // Sign a Spend, twice
+        let mut spend_tx = spend(
+            vec![unvault_txin(0, 0, 12), unvault_txin(9, 1, 12)],
+            19_000_000,
+        );
+        cosigner.sign(&mut spend_tx, &secp).unwrap();
+        cosigner.sign(&mut spend_tx, &secp).unwrap();
+        for psbtin in spend_tx.psbt().inputs.iter() {
+            assert_eq!(psbtin.partial_sigs.len(), 1);
+            assert!(psbtin
+                .partial_sigs
+                .keys()
+                .any(|pk| pk.key == cosigner.public_key()));
+        }
+        assert_eq!(
+            cosigner
+                .history()
+                .signed_for(&OutPoint::new(Txid::default(), 1)),
+            Ok(Some(spend_tx.txid()))
+        );
+
+        // Can't sign another Spend for the same Unvault
+        let mut other_spend_tx = spend(
+            vec![unvault_txin(1, 2, 12), unvault_txin(0, 0, 12)],
+            19_100_000,
+        );
+        assert_eq!(
+            cosigner.sign(&mut other_spend_tx, &secp),
+            Err(CosignerError::AlreadySigned(
+                OutPoint::new(Txid::default(), 0),
+                spend_tx.txid()
+            ))
+        );
+        assert_eq!(
+            cosigner
+                .history()
+                .signed_for(&OutPoint::new(Txid::default(), 2)),
+            Ok(None)
+        );
+
+        // Wrong sequence, or out of our derivation window
+        let mut other_spend_tx = spend(vec![unvault_txin(1, 2, 11)], 9_000_000);
+        assert_eq!(
+            cosigner.sign(&mut other_spend_tx, &secp),
+            Err(CosignerError::InvalidSequence(
+                OutPoint::new(Txid::default(), 2),
+                11
+            ))
+        );
+        let mut other_spend_tx = spend(vec![unvault_txin(10, 2, 12)], 9_000_000);
+        assert_eq!(
+            cosigner.sign(&mut other_spend_tx, &secp),
+            Err(CosignerError::NotUnvaultInput(OutPoint::new(
+                Txid::default(),
+                2
+            )))
+        );
+
+        // The other cosigner signs too
+        let mut other_cosigner = Cosigner::new(
+            &unvault_descriptor,
+            secret_keys[1],
+            10,
+            InMemorySigningHistory::new(),
+            &secp,
+        )
+        .unwrap();
+        other_cosigner.sign(&mut spend_tx, &secp).unwrap();
+        assert!(spend_tx
+            .psbt()
+            .inputs
+            .iter()
+            .all(|psbtin| psbtin.partial_sigs.len() == 2));
+    }
+}
@@ -1,19 +1,95 @@
 //! # Errors related to Revault transactions and Scripts management
 
-use crate::transactions::INSANE_FEES;
+use crate::{
+    transactions::INSANE_FEES,
+    vault::{PresignedTransaction, VaultEvent, VaultStatus},
+};
 
 use bitcoinconsensus::Error as LibConsensusError;
 use miniscript::{
     bitcoin::{
         consensus::encode::Error as EncodeError,
+        network::constants::Network,
         secp256k1,
-        util::psbt::{Input as PsbtInput, Output as PsbtOutput},
+        util::{
+            bip32,
+            psbt::{Input as PsbtInput, Output as PsbtOutput},
+        },
+        BlockHash, OutPoint, Script, Txid,
     },
     policy::compiler::CompilerError,
 };
 
 use std::{convert::From, error, fmt};
 
+/// A descriptor that does not follow the Revault template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptorTemplateError {
+    /// Not a P2WSH of a Miniscript
+    NotP2wsh,
+    /// The Deposit policy is not a N-of-N between at least 2 keys, optionally with a timelocked
+    /// k-of-N between N recovery keys
+    DepositPolicy,
+    /// The Deposit recovery relative timelock is disabled, not in blocks, or not minimal
+    DepositTimelock(u32),
+    /// The CPFP policy is not a 1-of-N between keys
+    CpfpPolicy,
+    /// The Unvault policy is not a disjunction between a timelocked and a non-timelocked branch
+    UnvaultBranches,
+    /// The stakeholders branch of the Unvault policy is not a threshold between keys, or not the
+    /// expected one (all the stakeholders unless specified)
+    StakeholdersBranch,
+    /// The managers branch of the Unvault policy is not
+    /// `and(thresh(k, managers), and(thresh(k, cosigners), older(csv)))` nor
+    /// `and(thresh(k, managers), older(csv))`, or the cosigners threshold is not the expected one
+    /// (all the cosigners unless specified)
+    ManagersBranch,
+    /// The Unvault relative timelock is disabled, not in blocks, or not minimal
+    UnvaultTimelock(u32),
+    /// The Emergency policy is not a N-of-N between at least 2 keys along with a relative
+    /// timelock
+    EmergencyPolicy,
+    /// The Emergency relative timelock is disabled, not in blocks, or not minimal
+    EmergencyTimelock(u32),
+}
+
+impl fmt::Display for DescriptorTemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotP2wsh => write!(f, "Not a P2WSH Miniscript descriptor"),
+            Self::DepositPolicy => write!(f, "Deposit policy is not a N-of-N between keys"),
+            Self::DepositTimelock(csv) => {
+                write!(f, "Invalid Deposit recovery relative timelock: '{}'", csv)
+            }
+            Self::CpfpPolicy => write!(f, "CPFP policy is not a 1-of-N between keys"),
+            Self::UnvaultBranches => write!(
+                f,
+                "Unvault policy is not a disjunction between a timelocked and a non-timelocked branch"
+            ),
+            Self::StakeholdersBranch => write!(
+                f,
+                "Unvault stakeholders branch is not the expected threshold between keys"
+            ),
+            Self::ManagersBranch => write!(
+                f,
+                "Unvault managers branch is not a threshold of managers and a threshold of cosigners and a relative timelock"
+            ),
+            Self::UnvaultTimelock(csv) => {
+                write!(f, "Invalid Unvault relative timelock: '{}'", csv)
+            }
+            Self::EmergencyPolicy => write!(
+                f,
+                "Emergency policy is not a N-of-N between keys and a relative timelock"
+            ),
+            Self::EmergencyTimelock(csv) => {
+                write!(f, "Invalid Emergency relative timelock: '{}'", csv)
+            }
+        }
+    }
+}
+
+impl error::Error for DescriptorTemplateError {}
+
 /// Error when creating a Revault Miniscript Descriptor
 #[derive(Debug)]
 pub enum ScriptCreationError {
@@ -26,6 +102,26 @@ pub enum ScriptCreationError {
     /// Miniscript general error, currently only for sanity checks in descriptor
     /// constructors
     MiniscriptError(miniscript::Error),
+    /// The descriptor does not follow the Revault template
+    InvalidTemplate(DescriptorTemplateError),
+    /// The Deposit and Unvault descriptors don't have the same stakeholders
+    StakeholdersMismatch,
+    /// The CPFP and Unvault descriptors don't have the same managers
+    ManagersMismatch,
+    /// A recovery key of the Deposit descriptor is also a manager's or a cosigner's key
+    RecoveryKeysReuse,
+    /// The Unvault descriptor has cosigners, but not as many as stakeholders
+    CosignersMismatch,
+    /// The address is not for the configured network
+    NetworkMismatch(Network),
+    /// A key has a hardened derivation step after the xpub, or a child number is hardened
+    HardenedDerivation,
+    /// The origin of a key is inconsistent with its xpub
+    InvalidKeyOrigin(String),
+    /// Invalid multipath (`<a;b>`) derivation steps
+    InvalidMultipath,
+    /// Spending from the descriptor would not be standard
+    NonStandard(StandardnessError),
 }
 
 impl fmt::Display for ScriptCreationError {
@@ -35,16 +131,53 @@ impl fmt::Display for ScriptCreationError {
             Self::PolicyCompilation(e) => write!(f, "Policy compilation error: '{}'", e),
             Self::MiniscriptError(e) => write!(f, "Miniscript error: '{}'", e),
             Self::NonWildcardKeys => write!(f, "Not all xpubs were wildcard"),
+            Self::InvalidTemplate(e) => write!(f, "Invalid descriptor template: '{}'", e),
+            Self::StakeholdersMismatch => write!(
+                f,
+                "Deposit and Unvault descriptors' stakeholders keys differ"
+            ),
+            Self::ManagersMismatch => {
+                write!(f, "CPFP and Unvault descriptors' managers keys differ")
+            }
+            Self::RecoveryKeysReuse => write!(
+                f,
+                "Deposit recovery keys are reused for managers or cosigning servers"
+            ),
+            Self::CosignersMismatch => write!(
+                f,
+                "The number of cosigners is not the number of stakeholders"
+            ),
+            Self::NetworkMismatch(network) => {
+                write!(f, "Address is not for network '{}'", network)
+            }
+            Self::HardenedDerivation => write!(f, "Hardened derivation from an xpub"),
+            Self::InvalidKeyOrigin(key) => {
+                write!(f, "Key origin does not match the xpub: '{}'", key)
+            }
+            Self::InvalidMultipath => write!(f, "Invalid multipath derivation steps"),
+            Self::NonStandard(e) => write!(f, "Non-standard descriptor: '{}'", e),
         }
     }
 }
 
+impl From<StandardnessError> for ScriptCreationError {
+    fn from(e: StandardnessError) -> Self {
+        Self::NonStandard(e)
+    }
+}
+
 impl From<CompilerError> for ScriptCreationError {
     fn from(e: CompilerError) -> Self {
         Self::PolicyCompilation(e)
     }
 }
 
+impl From<DescriptorTemplateError> for ScriptCreationError {
+    fn from(e: DescriptorTemplateError) -> Self {
+        Self::InvalidTemplate(e)
+    }
+}
+
 impl From<miniscript::Error> for ScriptCreationError {
     fn from(e: miniscript::Error) -> Self {
         Self::MiniscriptError(e)
@@ -69,6 +202,50 @@ impl fmt::Display for TxoutCreationError {
 
 impl error::Error for TxoutCreationError {}
 
+/// A transaction, or a Script it would use, is not standard and would not be relayed by Bitcoin
+/// Core
+#[derive(PartialEq, Eq, Debug)]
+pub enum StandardnessError {
+    /// The witness Script is larger than 3600 bytes
+    WitnessScriptTooLarge(usize),
+    /// The witness has more than 100 items, not counting the witness Script
+    TooManyStackItems(usize),
+    /// An item of the witness, other than the witness Script, is larger than 80 bytes
+    StackItemTooLarge(usize),
+    /// The transaction's sigops cost is higher than 16000
+    TooManySigops(usize),
+    /// This output Script is not of a standard type
+    NonStandardOutput(Script),
+    /// The transaction has more than one OP_RETURN output
+    MultipleDataCarriers,
+    /// The witness Script can't be satisfied, a transaction spending it would never be relayed
+    Unsatisfiable,
+}
+
+impl fmt::Display for StandardnessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WitnessScriptTooLarge(size) => {
+                write!(f, "Witness Script too large: '{}' bytes", size)
+            }
+            Self::TooManyStackItems(count) => {
+                write!(f, "Too many witness stack items: '{}'", count)
+            }
+            Self::StackItemTooLarge(size) => {
+                write!(f, "Witness stack item too large: '{}' bytes", size)
+            }
+            Self::TooManySigops(cost) => write!(f, "Sigops cost too high: '{}'", cost),
+            Self::NonStandardOutput(script) => {
+                write!(f, "Non-standard output Script: '{}'", script)
+            }
+            Self::MultipleDataCarriers => write!(f, "More than one OP_RETURN output"),
+            Self::Unsatisfiable => write!(f, "Witness Script can't be satisfied"),
+        }
+    }
+}
+
+impl error::Error for StandardnessError {}
+
 /// Error when creating a Revault Bitcoin transaction
 #[derive(PartialEq, Eq, Debug)]
 pub enum TransactionCreationError {
@@ -82,6 +259,10 @@ pub enum TransactionCreationError {
     NegativeFees,
     /// Transaction weight more than 400k weight units.
     TooLarge,
+    /// This input's nSequence doesn't satisfy the relative timelock of the output it spends
+    InvalidSequence(OutPoint, u32),
+    /// Transaction would not be relayed by Bitcoin Core
+    NonStandard(StandardnessError),
 }
 
 impl fmt::Display for TransactionCreationError {
@@ -100,12 +281,24 @@ impl fmt::Display for TransactionCreationError {
                 f,
                 "Transaction too large: satisfied it could be >400k weight units"
             ),
+            Self::InvalidSequence(outpoint, sequence) => write!(
+                f,
+                "Input '{}' has an immature nSequence '{}'",
+                outpoint, sequence
+            ),
+            Self::NonStandard(e) => write!(f, "Non-standard transaction: '{}'", e),
         }
     }
 }
 
 impl error::Error for TransactionCreationError {}
 
+impl From<StandardnessError> for TransactionCreationError {
+    fn from(e: StandardnessError) -> Self {
+        Self::NonStandard(e)
+    }
+}
+
 /// Error when satisfying a Revault Bitcoin transaction input
 #[derive(PartialEq, Eq, Debug)]
 pub enum InputSatisfactionError {
@@ -163,9 +356,11 @@ pub enum PsbtValidationError {
     InvalidInputField(PsbtInput),
     InvalidOutputField(PsbtOutput),
     InvalidPrevoutType(PsbtInput),
+    InvalidSequence(OutPoint, u32),
     PartiallyFinalized,
     InsaneAmounts,
     TransactionTooLarge,
+    NonStandard(StandardnessError),
 }
 
 impl fmt::Display for PsbtValidationError {
@@ -212,6 +407,11 @@ impl fmt::Display for PsbtValidationError {
                 "This input refers to an output of invalid type: '{:#?}'",
                 i
             ),
+            Self::InvalidSequence(outpoint, sequence) => write!(
+                f,
+                "Input '{}' has an immature nSequence '{}'",
+                outpoint, sequence
+            ),
             Self::PartiallyFinalized => write!(f, "PSBT contains both final and non-final inputs"),
             Self::InsaneAmounts => write!(
                 f,
@@ -221,12 +421,19 @@ impl fmt::Display for PsbtValidationError {
                 f,
                 "Transaction too large: satisfied it could be >400k weight units"
             ),
+            Self::NonStandard(e) => write!(f, "Non-standard transaction: '{}'", e),
         }
     }
 }
 
 impl error::Error for PsbtValidationError {}
 
+impl From<StandardnessError> for PsbtValidationError {
+    fn from(e: StandardnessError) -> Self {
+        Self::NonStandard(e)
+    }
+}
+
 /// Error when working with serialized Revault transactions
 #[derive(PartialEq, Debug)]
 pub enum TransactionSerialisationError {
@@ -269,6 +476,296 @@ impl From<PsbtValidationError> for TransactionSerialisationError {
 
 impl error::Error for TransactionSerialisationError {}
 
+/// Error when updating the state of a vault
+#[derive(PartialEq, Eq, Debug)]
+pub enum VaultError {
+    /// This event can't happen to a vault in this state
+    InvalidTransition(VaultStatus, VaultEvent),
+    /// This transaction can't be signed for a vault in this state
+    UnexpectedSignature(VaultStatus, PresignedTransaction),
+    /// The signature could not be added to the transaction
+    InputSatisfaction(InputSatisfactionError),
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidTransition(status, event) => {
+                write!(f, "Invalid event '{}' for a '{}' vault", event, status)
+            }
+            Self::UnexpectedSignature(status, tx) => {
+                write!(
+                    f,
+                    "Can't sign the {:?} transaction of a '{}' vault",
+                    tx, status
+                )
+            }
+            Self::InputSatisfaction(e) => write!(f, "Input satisfaction error: '{}'", e),
+        }
+    }
+}
+
+impl From<InputSatisfactionError> for VaultError {
+    fn from(e: InputSatisfactionError) -> Self {
+        Self::InputSatisfaction(e)
+    }
+}
+
+impl error::Error for VaultError {}
+
+/// Error when processing the chain
+#[derive(PartialEq, Eq, Debug)]
+pub enum ChainError {
+    /// This block does not connect to the tip
+    UnconnectedBlock(BlockHash),
+    /// This block is not the tip, it can't be disconnected
+    NotTip(BlockHash),
+    /// We don't have the data to disconnect blocks this deep
+    ReorgTooDeep,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnconnectedBlock(hash) => {
+                write!(f, "Block '{}' does not connect to the tip", hash)
+            }
+            Self::NotTip(hash) => write!(f, "Block '{}' is not the tip", hash),
+            Self::ReorgTooDeep => write!(f, "Reorganisation deeper than the undo data"),
+        }
+    }
+}
+
+impl error::Error for ChainError {}
+
+/// Error related to the spending policies
+#[derive(PartialEq, Eq, Debug)]
+pub enum PolicyError {
+    /// The Unvault inputs are not the inputs of the Spend transaction
+    UnvaultInputsMismatch,
+    /// Invalid configuration at this line
+    InvalidConfig(usize, String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnvaultInputsMismatch => write!(
+                f,
+                "The Unvault inputs are not the inputs of the Spend transaction"
+            ),
+            Self::InvalidConfig(line, msg) => {
+                write!(
+                    f,
+                    "Invalid policy configuration at line {}: '{}'",
+                    line, msg
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for PolicyError {}
+
+/// Error when cosigning a Spend transaction
+#[derive(PartialEq, Eq, Debug)]
+pub enum CosignerError {
+    /// Our key is not one of the cosigners keys of the Unvault descriptor
+    UnknownKey(secp256k1::PublicKey),
+    /// This input does not spend an Unvault output of our descriptor
+    NotUnvaultInput(OutPoint),
+    /// This input's nSequence is not the Unvault CSV
+    InvalidSequence(OutPoint, u32),
+    /// We already signed this Spend transaction for this Unvault output
+    AlreadySigned(OutPoint, Txid),
+    /// The signing history backend failed
+    History(String),
+    /// Our signature could not be added to the transaction
+    InputSatisfaction(InputSatisfactionError),
+}
+
+impl fmt::Display for CosignerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownKey(pubkey) => write!(
+                f,
+                "Key '{}' is not a cosigner of the Unvault descriptor",
+                pubkey
+            ),
+            Self::NotUnvaultInput(outpoint) => {
+                write!(f, "Input '{}' is not one of our Unvault outputs", outpoint)
+            }
+            Self::InvalidSequence(outpoint, sequence) => write!(
+                f,
+                "Input '{}' has an invalid nSequence '{}'",
+                outpoint, sequence
+            ),
+            Self::AlreadySigned(outpoint, txid) => write!(
+                f,
+                "Input '{}' was already signed for in transaction '{}'",
+                outpoint, txid
+            ),
+            Self::History(e) => write!(f, "Signing history error: '{}'", e),
+            Self::InputSatisfaction(e) => write!(f, "Input satisfaction error: '{}'", e),
+        }
+    }
+}
+
+impl From<InputSatisfactionError> for CosignerError {
+    fn from(e: InputSatisfactionError) -> Self {
+        Self::InputSatisfaction(e)
+    }
+}
+
+impl error::Error for CosignerError {}
+
+/// Error when managing a Spend proposal
+#[derive(PartialEq, Eq, Debug)]
+pub enum SpendProposalError {
+    /// No Unvault transaction for the Unvault output spent by this input
+    MissingUnvault(OutPoint),
+    /// This Unvault transaction is not spent by the Spend transaction
+    UnknownUnvault(Txid),
+    /// This input does not spend the Unvault output of our descriptor, or not with the CSV
+    InvalidUnvaultInput(OutPoint),
+    /// This is a different Spend transaction
+    SpendMismatch(Txid),
+    /// This key can't sign the Spend transaction input
+    UnknownKey(secp256k1::PublicKey),
+    /// The signature for this key could not be parsed
+    InvalidSignature(secp256k1::PublicKey),
+    /// The thresholds are not met for all the inputs
+    MissingSignatures,
+    /// A transaction could not be finalized
+    Finalisation(String),
+    /// The signature could not be added to the Spend transaction
+    InputSatisfaction(InputSatisfactionError),
+}
+
+impl fmt::Display for SpendProposalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingUnvault(outpoint) => {
+                write!(f, "No Unvault transaction for input '{}'", outpoint)
+            }
+            Self::UnknownUnvault(txid) => write!(
+                f,
+                "Unvault transaction '{}' is not spent by the Spend transaction",
+                txid
+            ),
+            Self::InvalidUnvaultInput(outpoint) => {
+                write!(f, "Input '{}' is not a valid Unvault spend", outpoint)
+            }
+            Self::SpendMismatch(txid) => {
+                write!(f, "Transaction '{}' is not the proposed Spend", txid)
+            }
+            Self::UnknownKey(pubkey) => {
+                write!(f, "Key '{}' can't sign for this Spend input", pubkey)
+            }
+            Self::InvalidSignature(pubkey) => {
+                write!(f, "Invalid signature encoding for key '{}'", pubkey)
+            }
+            Self::MissingSignatures => write!(f, "Not all inputs are signed"),
+            Self::Finalisation(e) => write!(f, "Finalisation error: '{}'", e),
+            Self::InputSatisfaction(e) => write!(f, "Input satisfaction error: '{}'", e),
+        }
+    }
+}
+
+impl From<InputSatisfactionError> for SpendProposalError {
+    fn from(e: InputSatisfactionError) -> Self {
+        Self::InputSatisfaction(e)
+    }
+}
+
+impl error::Error for SpendProposalError {}
+
+/// Error when handling a signatures exchange message
+#[derive(PartialEq, Eq, Debug)]
+pub enum SignatureMessageError {
+    /// We don't know this version of the format
+    UnknownVersion(u8),
+    /// The message is not correctly encoded
+    InvalidEncoding(String),
+    /// A signature could not be added to the transaction
+    InputSatisfaction(InputSatisfactionError),
+}
+
+impl fmt::Display for SignatureMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownVersion(v) => write!(f, "Unknown signature message version '{}'", v),
+            Self::InvalidEncoding(e) => write!(f, "Invalid signature message encoding: '{}'", e),
+            Self::InputSatisfaction(e) => write!(f, "Input satisfaction error: '{}'", e),
+        }
+    }
+}
+
+impl From<InputSatisfactionError> for SignatureMessageError {
+    fn from(e: InputSatisfactionError) -> Self {
+        Self::InputSatisfaction(e)
+    }
+}
+
+impl error::Error for SignatureMessageError {}
+
+/// Error when collecting or applying the signatures of a signing session
+#[derive(PartialEq, Eq, Debug)]
+pub enum SigningSessionError {
+    /// There must be one signature per sighash (expected, got)
+    SignaturesCount(usize, usize),
+    /// The signature of this transaction of this deposit is invalid
+    InvalidSignature(OutPoint, PresignedTransaction),
+    /// This deposit is not part of the session
+    UnknownDeposit(OutPoint),
+    /// This key is not the signing stakeholder's key for this deposit
+    UnknownKey(OutPoint, secp256k1::PublicKey),
+    /// The xpriv is not one of the stakeholders' of the Deposit descriptor
+    NotStakeholder,
+    /// The signing keys could not be derived from the xpriv
+    KeyDerivation(bip32::Error),
+    /// The signatures could not be applied to the transactions
+    SignatureMessage(SignatureMessageError),
+}
+
+impl fmt::Display for SigningSessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SignaturesCount(expected, got) => {
+                write!(f, "Expected '{}' signatures but got '{}'", expected, got)
+            }
+            Self::InvalidSignature(outpoint, tx) => write!(
+                f,
+                "Invalid signature for the {} transaction of deposit '{}'",
+                tx, outpoint
+            ),
+            Self::UnknownDeposit(outpoint) => write!(f, "Unknown deposit '{}'", outpoint),
+            Self::UnknownKey(outpoint, pubkey) => write!(
+                f,
+                "Key '{}' is not the signing stakeholder's key for deposit '{}'",
+                pubkey, outpoint
+            ),
+            Self::NotStakeholder => write!(f, "Not a stakeholder's xpriv"),
+            Self::KeyDerivation(e) => write!(f, "Key derivation error: '{}'", e),
+            Self::SignatureMessage(e) => write!(f, "Signature message error: '{}'", e),
+        }
+    }
+}
+
+impl From<SignatureMessageError> for SigningSessionError {
+    fn from(e: SignatureMessageError) -> Self {
+        Self::SignatureMessage(e)
+    }
+}
+
+impl From<bip32::Error> for SigningSessionError {
+    fn from(e: bip32::Error) -> Self {
+        Self::KeyDerivation(e)
+    }
+}
+
+impl error::Error for SigningSessionError {}
+
 /// An error specific to the management of Revault transactions and scripts.
 #[derive(Debug)]
 pub enum Error {
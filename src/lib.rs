@@ -16,4 +16,27 @@ pub mod txins;
 
 pub mod txouts;
 
+pub mod standardness;
+
 pub mod transactions;
+
+pub mod scanner;
+
+pub mod vault;
+
+pub mod chain;
+
+pub mod policy;
+
+pub mod cosigner;
+
+pub mod proposal;
+
+pub mod signatures;
+
+pub mod session;
+
+pub mod limits;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,588 @@
+//! # Revault configuration limits
+//!
+//! The number of participants a Revault deployment can have is bounded by the size of the
+//! Scripts and by the weight of the transactions spending them. This reports the limits of a
+//! configuration before any key is exchanged, so setup tools can reject the infeasible ones.
+//!
+//! The Unvault descriptor considered is the one of the default constructor, compiled from the
+//! Revault policies (see [UnvaultDescriptor::new](crate::scripts::UnvaultDescriptor::new)), if
+//! all the stakeholders and all the cosigning servers are required. Otherwise it's the one of
+//! [UnvaultDescriptor::new_with_thresholds](crate::scripts::UnvaultDescriptor::new_with_thresholds).
+//! The weights are the worst-case ones, for a satisfaction with maximum-size signatures and
+//! without fee-bumping inputs.
+
+use crate::{
+    error::Error,
+    scripts::{
+        DerivedCpfpDescriptor, DerivedDepositDescriptor, DerivedUnvaultDescriptor, EmergencyAddress,
+    },
+    standardness,
+    transactions::{
+        transaction_chain_derived, RevaultTransaction, SpendTransaction, MAX_STANDARD_TX_WEIGHT,
+    },
+    txins::UnvaultTxIn,
+    txouts::{DepositTxOut, RevaultInternalTxOut, SpendTxOut, UnvaultTxOut},
+};
+
+use miniscript::{
+    bitcoin::{
+        secp256k1, util::psbt::PartiallySignedTransaction as Psbt, Address, Amount, Network,
+        OutPoint, PublicKey, Script, TxOut, Txid,
+    },
+    DescriptorTrait,
+};
+
+use std::convert::TryInto;
+
+// The value of the dummy deposit used to create the transactions. It doesn't affect their size.
+const DUMMY_DEPOSIT_VALUE: u64 = 100_000_000;
+
+// An upper bound on the number of stakeholders, as the Deposit Script has a key for each of them.
+const MAX_STAKEHOLDERS_BOUND: usize = 128;
+
+/// The limits of a Revault configuration. Sizes are in bytes and weights in weight units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigurationLimits {
+    /// The size of the Deposit witness Script
+    pub deposit_script_size: usize,
+    /// The size of the Unvault witness Script
+    pub unvault_script_size: usize,
+    /// The size of the CPFP witness Script
+    pub cpfp_script_size: usize,
+    /// The worst-case weight of the Unvault transaction
+    pub unvault_tx_weight: u64,
+    /// The worst-case weight of the Cancel transaction
+    pub cancel_tx_weight: u64,
+    /// The worst-case weight of the Emergency transaction
+    pub emergency_tx_weight: u64,
+    /// The worst-case weight of the Unvault Emergency transaction
+    pub unvault_emergency_tx_weight: u64,
+    /// The worst-case weight each Unvault input adds to a Spend transaction
+    pub spend_input_weight: u64,
+    /// The maximum number of Unvault outputs a standard Spend transaction can batch
+    pub max_spend_inputs: usize,
+    /// Whether the pre-signed Cancel transaction would be relayed
+    pub cancel_standard: bool,
+    /// Whether the pre-signed Emergency transaction would be relayed
+    pub emergency_standard: bool,
+    /// Whether the pre-signed Unvault Emergency transaction would be relayed
+    pub unvault_emergency_standard: bool,
+}
+
+impl ConfigurationLimits {
+    /// Whether all the pre-signed revocation transactions would be relayed, and a Spend
+    /// transaction could spend at least one Unvault output.
+    pub fn is_feasible(&self) -> bool {
+        self.cancel_standard
+            && self.emergency_standard
+            && self.unvault_emergency_standard
+            && self.max_spend_inputs > 0
+    }
+}
+
+// The i-th dummy key used to create the descriptors. Only their size matters, not their value.
+fn dummy_key<C: secp256k1::Signing>(secp: &secp256k1::Secp256k1<C>, i: usize) -> PublicKey {
+    let mut secret = [0; 32];
+    secret[24..].copy_from_slice(&(i as u64 + 1).to_be_bytes());
+    let secret = secp256k1::SecretKey::from_slice(&secret).expect("Non-zero and below the order");
+
+    PublicKey {
+        compressed: true,
+        key: secp256k1::PublicKey::from_secret_key(secp, &secret),
+    }
+}
+
+// The worst-case weight of this transaction once its P2WSH input is satisfied.
+fn max_weight(psbt: &Psbt, sat_weight: usize) -> u64 {
+    psbt.global
+        .unsigned_tx
+        .get_weight()
+        .checked_add(sat_weight)
+        .expect("Weight computation bug: cannot overflow")
+        .try_into()
+        .expect("usize in u64")
+}
+
+// Whether this pre-signed transaction would be relayed once satisfied. Round-tripping it through
+// the PSBT sanity checks also catches a Script that can't be parsed back.
+fn is_standard<T: RevaultTransaction>(tx: &T, sat_weight: usize) -> bool {
+    max_weight(tx.psbt(), sat_weight) <= MAX_STANDARD_TX_WEIGHT as u64
+        && T::from_psbt_serialized(&tx.as_psbt_serialized()).is_ok()
+}
+
+// A Spend transaction batching this many Unvault outputs, paying to a single P2WSH output.
+fn dummy_spend(
+    der_unvault_descriptor: &DerivedUnvaultDescriptor,
+    der_cpfp_descriptor: &DerivedCpfpDescriptor,
+    destination: &Script,
+    n_inputs: u32,
+) -> Result<SpendTransaction, Error> {
+    let unvault_txins = (0..n_inputs)
+        .map(|i| {
+            UnvaultTxIn::new(
+                OutPoint::new(Txid::default(), i),
+                UnvaultTxOut::new(
+                    Amount::from_sat(DUMMY_DEPOSIT_VALUE),
+                    der_unvault_descriptor,
+                ),
+                der_unvault_descriptor.csv_value(),
+            )
+        })
+        .collect();
+    let spend_txouts = vec![SpendTxOut::Destination(TxOut {
+        value: DUMMY_DEPOSIT_VALUE / 2,
+        script_pubkey: destination.clone(),
+    })];
+
+    Ok(SpendTransaction::new(
+        unvault_txins,
+        spend_txouts,
+        der_cpfp_descriptor,
+        0,
+        false,
+    )?)
+}
+
+/// Get the limits of a configuration with `n_stakeholders` stakeholders of which
+/// `stakeholders_threshold` are needed to revoke, `n_managers` managers of which
+/// `managers_threshold` are needed to spend, `n_cosigners` cosigning servers (either none or one
+/// per stakeholder) of which `cosigners_threshold` (all of them if `None`) are needed along with
+/// the managers, and an Unvault timelock of `csv_value` blocks.
+///
+/// # Errors
+/// - If the parameters are invalid, as for
+///   [UnvaultDescriptor::new_with_thresholds](crate::scripts::UnvaultDescriptor::new_with_thresholds).
+/// - If a Script would exceed the resource or standardness limits, or the policy compilation
+///   failed.
+/// - If the Unvault transaction could not be created.
+#[allow(clippy::too_many_arguments)]
+pub fn configuration_limits<C: secp256k1::Signing>(
+    n_stakeholders: usize,
+    stakeholders_threshold: usize,
+    n_managers: usize,
+    managers_threshold: usize,
+    n_cosigners: usize,
+    cosigners_threshold: Option<usize>,
+    csv_value: u32,
+    secp: &secp256k1::Secp256k1<C>,
+) -> Result<ConfigurationLimits, Error> {
+    let stakeholders: Vec<_> = (0..n_stakeholders).map(|i| dummy_key(secp, i)).collect();
+    let managers: Vec<_> = (n_stakeholders..n_stakeholders + n_managers)
+        .map(|i| dummy_key(secp, i))
+        .collect();
+    let cosigners: Vec<_> = (n_stakeholders + n_managers
+        ..n_stakeholders + n_managers + n_cosigners)
+        .map(|i| dummy_key(secp, i))
+        .collect();
+
+    let der_deposit_descriptor = DerivedDepositDescriptor::new(stakeholders.clone())?;
+    let der_unvault_descriptor = if stakeholders_threshold == n_stakeholders
+        && cosigners_threshold.unwrap_or(n_cosigners) == n_cosigners
+    {
+        DerivedUnvaultDescriptor::new(
+            stakeholders,
+            managers.clone(),
+            managers_threshold,
+            cosigners,
+            csv_value,
+        )?
+    } else {
+        DerivedUnvaultDescriptor::new_with_thresholds(
+            stakeholders,
+            stakeholders_threshold,
+            managers.clone(),
+            managers_threshold,
+            cosigners,
+            cosigners_threshold,
+            csv_value,
+        )?
+    };
+    let der_cpfp_descriptor = DerivedCpfpDescriptor::new(managers)?;
+    // The Emergency Script is unknown, but its size doesn't affect the pre-signed transactions.
+    let emer_address = EmergencyAddress::from_network(
+        Address::p2wsh(&Script::new(), Network::Bitcoin),
+        Network::Bitcoin,
+    )?;
+
+    let (unvault_tx, cancel_tx, emergency_tx, unvault_emergency_tx) = transaction_chain_derived(
+        OutPoint::default(),
+        Amount::from_sat(DUMMY_DEPOSIT_VALUE),
+        &der_deposit_descriptor,
+        &der_unvault_descriptor,
+        &der_cpfp_descriptor,
+        emer_address,
+        0,
+    )?;
+    let deposit_sat_weight = DepositTxOut::new(
+        Amount::from_sat(DUMMY_DEPOSIT_VALUE),
+        &der_deposit_descriptor,
+    )
+    .max_sat_weight();
+    let unvault_sat_weight = UnvaultTxOut::new(
+        Amount::from_sat(DUMMY_DEPOSIT_VALUE),
+        &der_unvault_descriptor,
+    )
+    .max_sat_weight();
+
+    // The weight an Unvault input adds to a Spend, and what's left for the others once we
+    // account for the input count growing to 3 bytes.
+    let destination = der_deposit_descriptor.inner().script_pubkey();
+    let dummy_spend_weight = |n_inputs: u32| -> Result<u64, Error> {
+        let spend_tx = dummy_spend(
+            &der_unvault_descriptor,
+            &der_cpfp_descriptor,
+            &destination,
+            n_inputs,
+        )?;
+        Ok(max_weight(
+            spend_tx.psbt(),
+            n_inputs as usize * unvault_sat_weight,
+        ))
+    };
+    let spend_weight = dummy_spend_weight(1)?;
+    let spend_input_weight = dummy_spend_weight(2)? - spend_weight;
+    let spend_base_weight = spend_weight - spend_input_weight + 2 * 4;
+    let max_spend_inputs_weight = (MAX_STANDARD_TX_WEIGHT as u64)
+        .saturating_sub(spend_base_weight)
+        .checked_div(spend_input_weight)
+        .expect("Input weight is never 0")
+        .try_into()
+        .expect("Less than MAX_STANDARD_TX_WEIGHT");
+    let unvault_script = der_unvault_descriptor.inner().explicit_script();
+    let max_spend_inputs_sigops = standardness::MAX_STANDARD_TX_SIGOPS_COST
+        .checked_div(standardness::script_sigops(&unvault_script, true))
+        .unwrap_or(usize::MAX);
+
+    Ok(ConfigurationLimits {
+        deposit_script_size: der_deposit_descriptor.inner().explicit_script().len(),
+        unvault_script_size: unvault_script.len(),
+        cpfp_script_size: der_cpfp_descriptor.inner().explicit_script().len(),
+        unvault_tx_weight: max_weight(unvault_tx.psbt(), deposit_sat_weight),
+        cancel_tx_weight: max_weight(cancel_tx.psbt(), unvault_sat_weight),
+        emergency_tx_weight: max_weight(emergency_tx.psbt(), deposit_sat_weight),
+        unvault_emergency_tx_weight: max_weight(unvault_emergency_tx.psbt(), unvault_sat_weight),
+        spend_input_weight,
+        max_spend_inputs: std::cmp::min(max_spend_inputs_weight, max_spend_inputs_sigops),
+        cancel_standard: is_standard(&cancel_tx, unvault_sat_weight),
+        emergency_standard: is_standard(&emergency_tx, deposit_sat_weight),
+        unvault_emergency_standard: is_standard(&unvault_emergency_tx, unvault_sat_weight),
+    })
+}
+
+/// Get the maximum number of stakeholders of a feasible configuration with `n_managers` managers
+/// of which `managers_threshold` are needed to spend, and an Unvault timelock of `csv_value`
+/// blocks. All the stakeholders are needed to revoke, and there is one cosigning server per
+/// stakeholder. See [configuration_limits].
+///
+/// The Scripts and the transactions only grow with the number of stakeholders, so a
+/// configuration with fewer stakeholders than a feasible one is assumed to be feasible too. The
+/// search never goes beyond 128 stakeholders, for which the Deposit Script alone exceeds the
+/// limits.
+///
+/// Returns `None` if no configuration with these managers is feasible.
+pub fn max_stakeholders<C: secp256k1::Signing>(
+    n_managers: usize,
+    managers_threshold: usize,
+    csv_value: u32,
+    secp: &secp256k1::Secp256k1<C>,
+) -> Option<usize> {
+    let is_feasible = |n_stakeholders| {
+        configuration_limits(
+            n_stakeholders,
+            n_stakeholders,
+            n_managers,
+            managers_threshold,
+            n_stakeholders,
+            None,
+            csv_value,
+            secp,
+        )
+        .map(|limits| limits.is_feasible())
+        .unwrap_or(false)
+    };
+
+    // There must be at least 2 stakeholders.
+    if !is_feasible(2) {
+        return None;
+    }
+    let (mut feasible, mut infeasible) = (2, MAX_STAKEHOLDERS_BOUND);
+    while infeasible - feasible > 1 {
+        let middle = feasible + (infeasible - feasible) / 2;
+        if is_feasible(middle) {
+            feasible = middle;
+        } else {
+            infeasible = middle;
+        }
+    }
+
+    Some(feasible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        configuration_limits, max_stakeholders, ConfigurationLimits, MAX_STAKEHOLDERS_BOUND,
+    };
+    use crate::{
+        error::*, standardness::MAX_STANDARD_P2WSH_SCRIPT_SIZE,
+        transactions::MAX_STANDARD_TX_WEIGHT,
+    };
+
+    use miniscript::{bitcoin::secp256k1, policy::compiler::CompilerError};
+
+    // The limits of a configuration in which all the stakeholders are needed to revoke, with a
+    // cosigning server each.
+    fn default_limits(
+        n_stakeholders: usize,
+        n_managers: usize,
+        managers_threshold: usize,
+        csv_value: u32,
+        secp: &secp256k1::Secp256k1<secp256k1::SignOnly>,
+    ) -> Result<ConfigurationLimits, Error> {
+        configuration_limits(
+            n_stakeholders,
+            n_stakeholders,
+            n_managers,
+            managers_threshold,
+            n_stakeholders,
+            None,
+            csv_value,
+            secp,
+        )
+    }
+
+    fn limits_exceeded() -> String {
+        Error::ScriptCreation(ScriptCreationError::PolicyCompilation(
+            CompilerError::LimitsExceeded,
+        ))
+        .to_string()
+    }
+
+    #[test]
+    fn limits() {
+        let secp = secp256k1::Secp256k1::signing_only();
+
+        let limits = default_limits(4, 3, 2, 144, &secp).unwrap();
+        assert!(limits.is_feasible());
+        assert!(limits.unvault_script_size > limits.deposit_script_size);
+        assert!(limits.deposit_script_size > limits.cpfp_script_size);
+        assert_eq!(limits.cancel_tx_weight, limits.unvault_emergency_tx_weight);
+        assert!(limits.unvault_tx_weight > limits.emergency_tx_weight);
+        assert!(limits.max_spend_inputs > 100);
+        assert!(
+            limits.max_spend_inputs as u64 * limits.spend_input_weight
+                < MAX_STANDARD_TX_WEIGHT as u64
+        );
+        // The same configuration always has the same limits
+        assert_eq!(default_limits(4, 3, 2, 144, &secp).unwrap(), limits);
+
+        // Invalid parameters
+        assert_eq!(
+            default_limits(1, 3, 2, 144, &secp).unwrap_err().to_string(),
+            Error::ScriptCreation(ScriptCreationError::BadParameters).to_string()
+        );
+        assert_eq!(
+            default_limits(4, 3, 4, 144, &secp).unwrap_err().to_string(),
+            Error::ScriptCreation(ScriptCreationError::BadParameters).to_string()
+        );
+
+        // The maximum number of stakeholders is the last feasible configuration
+        let max = max_stakeholders(2, 2, 144, &secp).unwrap();
+        let limits = default_limits(max, 2, 2, 144, &secp).unwrap();
+        assert!(limits.is_feasible());
+        assert!(limits.unvault_script_size <= MAX_STANDARD_P2WSH_SCRIPT_SIZE);
+        default_limits(max + 1, 2, 2, 144, &secp).unwrap_err();
+
+        // The managers share the Unvault Script with the stakeholders
+        assert!(max_stakeholders(20, 20, 144, &secp).unwrap() < max);
+    }
+
+    #[test]
+    fn max_stakeholders_search() {
+        let secp = secp256k1::Secp256k1::signing_only();
+
+        // The search assumes that feasibility only decreases with the number of stakeholders.
+        // Compiling the policies takes time, so just check some of them.
+        let max = max_stakeholders(1, 1, 144, &secp).unwrap();
+        for n_stakeholders in &[2, 3, 10, 20, 30, max - 1, max] {
+            assert!(default_limits(*n_stakeholders, 1, 1, 144, &secp)
+                .unwrap()
+                .is_feasible());
+        }
+        for n_stakeholders in &[max + 1, max + 2, MAX_STAKEHOLDERS_BOUND - 1] {
+            default_limits(*n_stakeholders, 1, 1, 144, &secp).unwrap_err();
+        }
+
+        // The upper bound of the search is never feasible, whatever the managers
+        for (n_managers, managers_threshold) in &[(1, 1), (2, 1), (20, 20)] {
+            assert_eq!(
+                default_limits(
+                    MAX_STAKEHOLDERS_BOUND,
+                    *n_managers,
+                    *managers_threshold,
+                    144,
+                    &secp
+                )
+                .unwrap_err()
+                .to_string(),
+                limits_exceeded()
+            );
+        }
+    }
+
+    #[test]
+    fn possible_default_configurations() {
+        // Policy compilation takes time, so just test some remarkable ones
+        let configurations = [
+            // Single-manager configurations
+            ((1, 1), 2),
+            ((1, 1), 5),
+            // Multiple-manager configurations (with threshold)
+            ((2, 2), 3),
+            ((3, 4), 2),
+            ((7, 7), 2),
+            ((2, 3), 8),
+            // Huge configurations
+            ((15, 15), 5),
+            ((20, 20), 5),
+            ((7, 7), 13),
+            ((8, 8), 12),
+            ((3, 3), 18),
+        ];
+        let secp = secp256k1::Secp256k1::signing_only();
+
+        for ((thresh, n_managers), n_stakeholders) in configurations.iter() {
+            let limits = default_limits(*n_stakeholders, *n_managers, *thresh, 18, &secp)
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Configuration with ({}, {}) errored: '{}'",
+                        n_managers, n_stakeholders, e
+                    )
+                });
+            assert!(limits.is_feasible());
+        }
+    }
+
+    #[test]
+    fn default_configurations_bounds() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let bad_parameters = Error::ScriptCreation(ScriptCreationError::BadParameters).to_string();
+
+        // A single stakeholder, no manager, an invalid managers threshold or CSV
+        for (n_stakeholders, n_managers, managers_threshold, csv) in &[
+            (1, 1, 1, 6),
+            (2, 0, 0, 6),
+            (2, 1, 0, 6),
+            (2, 1, 2, 6),
+            (2, 1, 1, 4194305),
+        ] {
+            assert_eq!(
+                default_limits(
+                    *n_stakeholders,
+                    *n_managers,
+                    *managers_threshold,
+                    *csv,
+                    &secp
+                )
+                .unwrap_err()
+                .to_string(),
+                bad_parameters
+            );
+        }
+
+        // Maximum 1-of-N managers
+        assert!(default_limits(2, 20, 1, 145, &secp).unwrap().is_feasible());
+        // Hit the limit
+        assert_eq!(
+            default_limits(2, 21, 1, 145, &secp)
+                .unwrap_err()
+                .to_string(),
+            limits_exceeded()
+        );
+
+        // Maximum stakeholders for 2 managers
+        let limits = default_limits(38, 2, 2, 145, &secp).unwrap();
+        assert!(limits.is_feasible());
+        assert!(limits.unvault_script_size <= MAX_STANDARD_P2WSH_SCRIPT_SIZE);
+        // Now hit the limit
+        assert_eq!(
+            default_limits(39, 2, 2, 32, &secp).unwrap_err().to_string(),
+            limits_exceeded()
+        );
+    }
+
+    #[test]
+    fn thresholds_and_no_cosigners() {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let bad_parameters = Error::ScriptCreation(ScriptCreationError::BadParameters).to_string();
+
+        // An invalid stakeholders threshold, cosigners count or cosigners threshold
+        for (stakeholders_threshold, n_cosigners, cosigners_threshold) in &[
+            (0, 4, None),
+            (5, 4, None),
+            (4, 3, None),
+            (4, 5, None),
+            (3, 4, Some(0)),
+            (3, 4, Some(5)),
+            (3, 0, Some(1)),
+        ] {
+            assert_eq!(
+                configuration_limits(
+                    4,
+                    *stakeholders_threshold,
+                    3,
+                    2,
+                    *n_cosigners,
+                    *cosigners_threshold,
+                    144,
+                    &secp
+                )
+                .unwrap_err()
+                .to_string(),
+                bad_parameters
+            );
+        }
+
+        let default = default_limits(4, 3, 2, 144, &secp).unwrap();
+
+        // Without cosigning servers, the Unvault Script is smaller
+        let no_cosigners = configuration_limits(4, 4, 3, 2, 0, None, 144, &secp).unwrap();
+        assert!(no_cosigners.is_feasible());
+        assert!(no_cosigners.unvault_script_size < default.unvault_script_size);
+        assert_eq!(
+            no_cosigners.deposit_script_size,
+            default.deposit_script_size
+        );
+        assert!(no_cosigners.spend_input_weight < default.spend_input_weight);
+        assert!(no_cosigners.max_spend_inputs > default.max_spend_inputs);
+
+        // With thresholds, the Unvault Script follows the template instead of being compiled
+        for (stakeholders_threshold, n_cosigners, cosigners_threshold) in
+            &[(3, 4, None), (4, 4, Some(2)), (3, 4, Some(2)), (2, 0, None)]
+        {
+            let limits = configuration_limits(
+                4,
+                *stakeholders_threshold,
+                3,
+                2,
+                *n_cosigners,
+                *cosigners_threshold,
+                144,
+                &secp,
+            )
+            .unwrap();
+            assert!(limits.is_feasible());
+            assert_ne!(limits.unvault_script_size, default.unvault_script_size);
+            assert_eq!(limits.deposit_script_size, default.deposit_script_size);
+            assert_eq!(limits.cpfp_script_size, default.cpfp_script_size);
+        }
+
+        // Without cosigning servers, a configuration can have more stakeholders
+        let max = max_stakeholders(2, 2, 145, &secp).unwrap();
+        assert!(
+            configuration_limits(max + 1, max + 1, 2, 2, 0, None, 145, &secp)
+                .unwrap()
+                .is_feasible()
+        );
+    }
+}
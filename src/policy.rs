@@ -0,0 +1,693 @@
+//! # Revault spending policies
+//!
+//! Decide whether a [Spend transaction](crate::transactions::SpendTransaction) observed by a
+//! watchtower is authorized, or whether the Unvault should be revoked by broadcasting the
+//! [Cancel transaction](crate::transactions::CancelTransaction).
+//!
+//! A [SpendPolicy] evaluates a [SpendCandidate], which sorts the Spend outputs between the CPFP
+//! output, the change outputs (paying back to the Deposit descriptor) and the destinations.
+//! Policies are usually combined in a [PolicySet], which can be parsed from a small
+//! line-based configuration:
+//! ```text
+//! # Only pay to these addresses
+//! allow_destination bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6
+//! # At most 1 BTC per Spend transaction
+//! max_amount 1 BTC
+//! # At most 5 BTC over any 144 blocks
+//! window_limit 144 5 BTC
+//! # At least 20% of the unvaulted value must be sent back to a deposit
+//! min_change_ratio 20
+//! ```
+
+use crate::{
+    error::PolicyError,
+    scanner::DepositScanner,
+    scripts::DerivedCpfpDescriptor,
+    transactions::{RevaultTransaction, SpendTransaction},
+    txins::{RevaultTxIn, UnvaultTxIn},
+    txouts::RevaultTxOut,
+};
+
+use miniscript::{
+    bitcoin::{Address, Amount, Denomination, Script, TxOut, Txid},
+    DescriptorTrait,
+};
+
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    str::FromStr,
+};
+
+/// Why a Spend transaction must be revoked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevocationReason {
+    /// This destination is not allowed
+    UnknownDestination(Script),
+    /// The Spend transaction pays more than the maximum to its destinations
+    AmountTooHigh { amount: Amount, max: Amount },
+    /// The Spend transaction would exceed the value allowed over the window
+    WindowLimitExceeded { amount: Amount, limit: Amount },
+    /// The Spend transaction does not send enough of the unvaulted value back to a deposit
+    InsufficientChange { change: Amount, required: Amount },
+    /// A reason given by a custom policy
+    Other(String),
+}
+
+impl fmt::Display for RevocationReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownDestination(script) => write!(f, "Unknown destination '{}'", script),
+            Self::AmountTooHigh { amount, max } => {
+                write!(
+                    f,
+                    "Spent amount '{}' is above the maximum '{}'",
+                    amount, max
+                )
+            }
+            Self::WindowLimitExceeded { amount, limit } => write!(
+                f,
+                "Amount spent over the window '{}' would be above the limit '{}'",
+                amount, limit
+            ),
+            Self::InsufficientChange { change, required } => write!(
+                f,
+                "Change amount '{}' is below the required '{}'",
+                change, required
+            ),
+            Self::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// The decision of a policy about a Spend transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    /// The Spend transaction is authorized
+    Allow,
+    /// The Unvault must be canceled
+    Revoke(RevocationReason),
+}
+
+/// A Spend transaction along with its Unvault inputs, as evaluated by the policies.
+#[derive(Debug, Clone)]
+pub struct SpendCandidate<'a> {
+    spend_tx: &'a SpendTransaction,
+    unvault_inputs: &'a [UnvaultTxIn],
+    height: u32,
+    destinations: Vec<&'a TxOut>,
+    change_value: Amount,
+}
+
+impl<'a> SpendCandidate<'a> {
+    /// Sort the outputs of a Spend transaction observed at `height`. Outputs paying to the
+    /// `cpfp_descriptor` are ignored, outputs to a deposit address known by the
+    /// `deposit_scanner` are change outputs and all the others are destinations.
+    ///
+    /// # Errors
+    /// - If the `unvault_inputs` are not the inputs of the `spend_tx`, in the same order.
+    pub fn new(
+        spend_tx: &'a SpendTransaction,
+        unvault_inputs: &'a [UnvaultTxIn],
+        cpfp_descriptor: &DerivedCpfpDescriptor,
+        deposit_scanner: &DepositScanner,
+        height: u32,
+    ) -> Result<SpendCandidate<'a>, PolicyError> {
+        let tx = spend_tx.tx();
+        if tx.input.len() != unvault_inputs.len()
+            || tx
+                .input
+                .iter()
+                .zip(unvault_inputs.iter())
+                .any(|(txin, unvault_txin)| txin.previous_output != unvault_txin.outpoint())
+        {
+            return Err(PolicyError::UnvaultInputsMismatch);
+        }
+
+        let cpfp_script_pubkey = cpfp_descriptor.inner().script_pubkey();
+        let mut destinations = Vec::with_capacity(tx.output.len());
+        let mut change_value = 0;
+        for txo in tx.output.iter() {
+            if txo.script_pubkey == cpfp_script_pubkey {
+                continue;
+            }
+            if deposit_scanner
+                .derivation_index(&txo.script_pubkey)
+                .is_some()
+            {
+                change_value += txo.value;
+            } else {
+                destinations.push(txo);
+            }
+        }
+
+        Ok(SpendCandidate {
+            spend_tx,
+            unvault_inputs,
+            height,
+            destinations,
+            change_value: Amount::from_sat(change_value),
+        })
+    }
+
+    /// The Spend transaction
+    pub fn spend_tx(&self) -> &SpendTransaction {
+        self.spend_tx
+    }
+
+    /// The Unvault outputs spent by the Spend transaction
+    pub fn unvault_inputs(&self) -> &[UnvaultTxIn] {
+        self.unvault_inputs
+    }
+
+    /// The height at which the Spend transaction was observed
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The outputs paying neither to the CPFP descriptor nor to a deposit
+    pub fn destinations(&self) -> &[&TxOut] {
+        &self.destinations
+    }
+
+    /// The total value of the Unvault inputs
+    pub fn unvaulted_value(&self) -> Amount {
+        Amount::from_sat(
+            self.unvault_inputs
+                .iter()
+                .map(|txin| txin.txout().txout().value)
+                .sum(),
+        )
+    }
+
+    /// The total value sent to the destinations
+    pub fn destinations_value(&self) -> Amount {
+        Amount::from_sat(self.destinations.iter().map(|txo| txo.value).sum())
+    }
+
+    /// The total value sent back to a deposit
+    pub fn change_value(&self) -> Amount {
+        self.change_value
+    }
+}
+
+/// A rule a Spend transaction must abide by.
+pub trait SpendPolicy: fmt::Debug {
+    /// Decide whether this Spend transaction is authorized.
+    fn evaluate(&self, spend: &SpendCandidate) -> PolicyDecision;
+
+    /// Called once the Spend transaction was allowed by all the policies it was evaluated
+    /// against, for policies that keep track of the past Spend transactions.
+    fn record(&mut self, _spend: &SpendCandidate) {}
+
+    /// Called when a recorded Spend transaction is no longer in the chain, for instance after
+    /// a reorg or a disconnection.
+    fn forget(&mut self, _spend_txid: &Txid) {}
+}
+
+/// Only allow paying to these scriptPubKeys.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DestinationAllowlist {
+    script_pubkeys: HashSet<Script>,
+}
+
+impl DestinationAllowlist {
+    /// Only allow paying to these addresses
+    pub fn new(addresses: impl IntoIterator<Item = Address>) -> DestinationAllowlist {
+        DestinationAllowlist {
+            script_pubkeys: addresses
+                .into_iter()
+                .map(|addr| addr.script_pubkey())
+                .collect(),
+        }
+    }
+
+    /// Allow paying to this address as well
+    pub fn add(&mut self, address: Address) {
+        self.script_pubkeys.insert(address.script_pubkey());
+    }
+}
+
+impl SpendPolicy for DestinationAllowlist {
+    fn evaluate(&self, spend: &SpendCandidate) -> PolicyDecision {
+        match spend
+            .destinations()
+            .iter()
+            .find(|txo| !self.script_pubkeys.contains(&txo.script_pubkey))
+        {
+            Some(txo) => PolicyDecision::Revoke(RevocationReason::UnknownDestination(
+                txo.script_pubkey.clone(),
+            )),
+            None => PolicyDecision::Allow,
+        }
+    }
+}
+
+/// Limit the value sent to the destinations by a single Spend transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxAmount(pub Amount);
+
+impl SpendPolicy for MaxAmount {
+    fn evaluate(&self, spend: &SpendCandidate) -> PolicyDecision {
+        let amount = spend.destinations_value();
+        if amount > self.0 {
+            PolicyDecision::Revoke(RevocationReason::AmountTooHigh {
+                amount,
+                max: self.0,
+            })
+        } else {
+            PolicyDecision::Allow
+        }
+    }
+}
+
+/// Limit the value sent to the destinations by all the Spend transactions observed over a
+/// rolling window of blocks. Each Spend transaction is only counted once, until it's
+/// [forgotten](SpendPolicy::forget).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowLimit {
+    window: u32,
+    limit: Amount,
+    // The txid, height and destinations value of the recorded Spend transactions
+    history: VecDeque<(Txid, u32, Amount)>,
+}
+
+impl WindowLimit {
+    /// Allow at most `limit` to be spent over any `window` consecutive blocks.
+    pub fn new(window: u32, limit: Amount) -> WindowLimit {
+        WindowLimit {
+            window,
+            limit,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// The value spent over the window ending at `height`
+    pub fn spent_at(&self, height: u32) -> Amount {
+        Amount::from_sat(
+            self.history
+                .iter()
+                .filter(|(_, spend_height, _)| spend_height.saturating_add(self.window) > height)
+                .map(|(_, _, amount)| amount.as_sat())
+                .sum(),
+        )
+    }
+
+    // Whether this Spend transaction was already recorded
+    fn is_recorded(&self, spend_txid: &Txid) -> bool {
+        self.history.iter().any(|(txid, _, _)| txid == spend_txid)
+    }
+}
+
+impl SpendPolicy for WindowLimit {
+    fn evaluate(&self, spend: &SpendCandidate) -> PolicyDecision {
+        // Don't count twice a Spend transaction we already recorded.
+        let mut amount = self.spent_at(spend.height());
+        if !self.is_recorded(&spend.spend_tx().txid()) {
+            amount += spend.destinations_value();
+        }
+        if amount > self.limit {
+            PolicyDecision::Revoke(RevocationReason::WindowLimitExceeded {
+                amount,
+                limit: self.limit,
+            })
+        } else {
+            PolicyDecision::Allow
+        }
+    }
+
+    fn record(&mut self, spend: &SpendCandidate) {
+        let (height, window) = (spend.height(), self.window);
+        self.history
+            .retain(|(_, spend_height, _)| spend_height.saturating_add(window) > height);
+
+        let txid = spend.spend_tx().txid();
+        if !self.is_recorded(&txid) {
+            self.history
+                .push_back((txid, spend.height(), spend.destinations_value()));
+        }
+    }
+
+    fn forget(&mut self, spend_txid: &Txid) {
+        self.history.retain(|(txid, _, _)| txid != spend_txid);
+    }
+}
+
+/// Require a minimum percentage of the unvaulted value to be sent back to a deposit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinChangeRatio(pub u8);
+
+impl SpendPolicy for MinChangeRatio {
+    fn evaluate(&self, spend: &SpendCandidate) -> PolicyDecision {
+        let change = spend.change_value();
+        let required = Amount::from_sat(
+            (spend.unvaulted_value().as_sat() as u128 * self.0 as u128 / 100) as u64,
+        );
+        if change < required {
+            PolicyDecision::Revoke(RevocationReason::InsufficientChange { change, required })
+        } else {
+            PolicyDecision::Allow
+        }
+    }
+}
+
+/// A set of policies a Spend transaction must all abide by.
+#[derive(Debug, Default)]
+pub struct PolicySet {
+    policies: Vec<Box<dyn SpendPolicy>>,
+}
+
+impl PolicySet {
+    /// An empty set, which allows any Spend transaction
+    pub fn new() -> PolicySet {
+        PolicySet::default()
+    }
+
+    /// Add a policy to the set
+    pub fn push(&mut self, policy: impl SpendPolicy + 'static) {
+        self.policies.push(Box::new(policy));
+    }
+
+    /// The policies in this set
+    pub fn policies(&self) -> &[Box<dyn SpendPolicy>] {
+        &self.policies
+    }
+
+    /// Evaluate the Spend against all the policies, and revoke it for the first that does not
+    /// allow it. If all policies allow it, it's recorded by each of them.
+    pub fn evaluate(&mut self, spend: &SpendCandidate) -> PolicyDecision {
+        for policy in self.policies.iter() {
+            if let PolicyDecision::Revoke(reason) = policy.evaluate(spend) {
+                return PolicyDecision::Revoke(reason);
+            }
+        }
+
+        for policy in self.policies.iter_mut() {
+            policy.record(spend);
+        }
+        PolicyDecision::Allow
+    }
+
+    /// Forget a previously allowed Spend transaction, as it's no longer in the chain (eg after
+    /// a reorg or a disconnection).
+    pub fn forget(&mut self, spend_txid: &Txid) {
+        for policy in self.policies.iter_mut() {
+            policy.forget(spend_txid);
+        }
+    }
+}
+
+impl FromStr for PolicySet {
+    type Err = PolicyError;
+
+    /// Parse a set of policies from the configuration format described in the
+    /// [module documentation](crate::policy). All `allow_destination` entries are
+    /// gathered in a single [DestinationAllowlist].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = PolicySet::new();
+        let mut allowlist: Option<DestinationAllowlist> = None;
+
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let invalid = |msg: String| PolicyError::InvalidConfig(line_number, msg);
+            let parse_amount = |s: &str| {
+                Amount::from_str_with_denomination(s)
+                    .or_else(|_| Amount::from_str_in(s, Denomination::Satoshi))
+                    .map_err(|e| invalid(format!("Invalid amount '{}': {}", s, e)))
+            };
+
+            let line = line.split('#').next().expect("Split always yields").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find(char::is_whitespace) {
+                Some(i) => (&line[..i], line[i..].trim()),
+                None => return Err(invalid(format!("Missing value for '{}'", line))),
+            };
+
+            match key {
+                "allow_destination" => {
+                    let address = Address::from_str(value)
+                        .map_err(|e| invalid(format!("Invalid address '{}': {}", value, e)))?;
+                    allowlist
+                        .get_or_insert_with(DestinationAllowlist::default)
+                        .add(address);
+                }
+                "max_amount" => set.push(MaxAmount(parse_amount(value)?)),
+                "window_limit" => {
+                    let (window, limit) = value
+                        .find(char::is_whitespace)
+                        .map(|i| (&value[..i], value[i..].trim()))
+                        .ok_or_else(|| invalid("Missing window limit".to_string()))?;
+                    let window = u32::from_str(window)
+                        .map_err(|e| invalid(format!("Invalid window '{}': {}", window, e)))?;
+                    set.push(WindowLimit::new(window, parse_amount(limit)?));
+                }
+                "min_change_ratio" => {
+                    let ratio = u8::from_str(value)
+                        .ok()
+                        .filter(|r| *r <= 100)
+                        .ok_or_else(|| invalid(format!("Invalid percentage '{}'", value)))?;
+                    set.push(MinChangeRatio(ratio));
+                }
+                _ => return Err(invalid(format!("Unknown policy '{}'", key))),
+            }
+        }
+
+        if let Some(allowlist) = allowlist {
+            set.push(allowlist);
+        }
+
+        Ok(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PolicyDecision, PolicySet, RevocationReason, SpendCandidate, SpendPolicy, WindowLimit,
+    };
+    use crate::{
+        error::PolicyError,
+        scanner::DepositScanner,
+        scripts::{CpfpDescriptor, DepositDescriptor, UnvaultDescriptor},
+        test_utils::{emergency_address, get_random_pubkey},
+        transactions::{RevaultTransaction, SpendTransaction},
+        txins::UnvaultTxIn,
+        txouts::{DepositTxOut, SpendTxOut, UnvaultTxOut},
+    };
+
+    use miniscript::bitcoin::{secp256k1, util::bip32, Address, Amount, OutPoint, TxOut, Txid};
+
+    use std::str::FromStr;
+
+    #[test]
+    fn spend_policies() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let stakeholders: Vec<_> = (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let managers: Vec<_> = (0..2).map(|_| get_random_pubkey(&mut rng, &secp)).collect();
+        let deposit_descriptor = DepositDescriptor::new(stakeholders.clone()).unwrap();
+        let unvault_descriptor = UnvaultDescriptor::new_with_thresholds(
+            stakeholders,
+            2,
+            managers.clone(),
+            1,
+            vec![],
+            Some(0),
+            6,
+        )
+        .unwrap();
+        let cpfp_descriptor = CpfpDescriptor::new(managers).unwrap();
+        let child_number = bip32::ChildNumber::from(3);
+        let der_cpfp_descriptor = cpfp_descriptor.derive(child_number, &secp);
+        let scanner = DepositScanner::new(deposit_descriptor.clone(), 20, &secp).unwrap();
+
+        let unvault_txin = |vout: u32| {
+            UnvaultTxIn::new(
+                OutPoint::new(Txid::default(), vout),
+                UnvaultTxOut::new(
+                    Amount::from_sat(100_000_000),
+                    &unvault_descriptor.derive(child_number, &secp),
+                ),
+                6,
+            )
+        };
+        let unvault_inputs = vec![unvault_txin(0), unvault_txin(1)];
+        let (allowed, other) = (
+            emergency_address().address().clone(),
+            Address::from_str("bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3")
+                .unwrap(),
+        );
+        let spend = |destinations: Vec<(&Address, u64)>, change: u64| {
+            let mut txouts: Vec<SpendTxOut> = destinations
+                .into_iter()
+                .map(|(addr, value)| {
+                    SpendTxOut::Destination(TxOut {
+                        value,
+                        script_pubkey: addr.script_pubkey(),
+                    })
+                })
+                .collect();
+            txouts.push(SpendTxOut::Change(DepositTxOut::new(
+                Amount::from_sat(change),
+                &deposit_descriptor.derive(bip32::ChildNumber::from(7), &secp),
+            )));
+            SpendTransaction::new(
+                unvault_inputs.clone(),
+                txouts,
+                &der_cpfp_descriptor,
+                0,
+                false,
+            )
+            .unwrap()
+        };
+
+        // The outputs are sorted
+        let spend_tx = spend(
+            vec![(&allowed, 90_000_000), (&other, 10_000_000)],
+            99_000_000,
+        );
+        let candidate = SpendCandidate::new(
+            &spend_tx,
+            &unvault_inputs,
+            &der_cpfp_descriptor,
+            &scanner,
+            10,
+        )
+        .unwrap();
+        assert_eq!(candidate.unvaulted_value(), Amount::from_sat(200_000_000));
+        assert_eq!(candidate.destinations().len(), 2);
+        assert_eq!(
+            candidate.destinations_value(),
+            Amount::from_sat(100_000_000)
+        );
+        assert_eq!(candidate.change_value(), Amount::from_sat(99_000_000));
+        assert_eq!(
+            SpendCandidate::new(
+                &spend_tx,
+                &unvault_inputs[..1],
+                &der_cpfp_descriptor,
+                &scanner,
+                10
+            )
+            .unwrap_err(),
+            PolicyError::UnvaultInputsMismatch
+        );
+
+        let config = format!(
+            "# Our policy\n\
+             allow_destination {}\n\
+             max_amount 1 BTC\n\n\
+             window_limit 144 150000000 # in sats\n\
+             min_change_ratio 40",
+            allowed
+        );
+        let mut policies = PolicySet::from_str(&config).unwrap();
+        assert_eq!(policies.policies().len(), 4);
+
+        // Unknown destination
+        assert_eq!(
+            policies.evaluate(&candidate),
+            PolicyDecision::Revoke(RevocationReason::UnknownDestination(other.script_pubkey()))
+        );
+
+        // Above the maximum amount
+        let spend_tx = spend(vec![(&allowed, 110_000_000)], 89_000_000);
+        let candidate = SpendCandidate::new(
+            &spend_tx,
+            &unvault_inputs,
+            &der_cpfp_descriptor,
+            &scanner,
+            10,
+        )
+        .unwrap();
+        assert_eq!(
+            policies.evaluate(&candidate),
+            PolicyDecision::Revoke(RevocationReason::AmountTooHigh {
+                amount: Amount::from_sat(110_000_000),
+                max: Amount::from_sat(100_000_000),
+            })
+        );
+
+        // Not enough change
+        let spend_tx = spend(vec![(&allowed, 90_000_000)], 79_000_000);
+        let candidate = SpendCandidate::new(
+            &spend_tx,
+            &unvault_inputs,
+            &der_cpfp_descriptor,
+            &scanner,
+            10,
+        )
+        .unwrap();
+        assert_eq!(
+            policies.evaluate(&candidate),
+            PolicyDecision::Revoke(RevocationReason::InsufficientChange {
+                change: Amount::from_sat(79_000_000),
+                required: Amount::from_sat(80_000_000),
+            })
+        );
+
+        // Allowed, then the window limit kicks in until the window passed
+        let spend_txs: Vec<_> = (0..3)
+            .map(|i| spend(vec![(&allowed, 90_000_000)], 109_000_000 - i))
+            .collect();
+        let spend_at = |i: usize, height| {
+            SpendCandidate::new(
+                &spend_txs[i],
+                &unvault_inputs,
+                &der_cpfp_descriptor,
+                &scanner,
+                height,
+            )
+            .unwrap()
+        };
+        assert_eq!(policies.evaluate(&spend_at(0, 10)), PolicyDecision::Allow);
+        // Seeing the same Spend again doesn't count it twice
+        assert_eq!(policies.evaluate(&spend_at(0, 11)), PolicyDecision::Allow);
+        assert_eq!(
+            policies.evaluate(&spend_at(1, 153)),
+            PolicyDecision::Revoke(RevocationReason::WindowLimitExceeded {
+                amount: Amount::from_sat(180_000_000),
+                limit: Amount::from_sat(150_000_000),
+            })
+        );
+        assert_eq!(policies.evaluate(&spend_at(1, 154)), PolicyDecision::Allow);
+        assert_eq!(
+            policies.evaluate(&spend_at(2, 155)),
+            PolicyDecision::Revoke(RevocationReason::WindowLimitExceeded {
+                amount: Amount::from_sat(180_000_000),
+                limit: Amount::from_sat(150_000_000),
+            })
+        );
+        // Unless the previous one was reorged out
+        policies.forget(&spend_txs[1].txid());
+        assert_eq!(policies.evaluate(&spend_at(2, 155)), PolicyDecision::Allow);
+
+        // The history is keyed by txid
+        let mut window_limit = WindowLimit::new(144, Amount::from_sat(150_000_000));
+        window_limit.record(&spend_at(0, 10));
+        window_limit.record(&spend_at(0, 12));
+        assert_eq!(window_limit.spent_at(12), Amount::from_sat(90_000_000));
+        window_limit.record(&spend_at(1, 12));
+        assert_eq!(window_limit.spent_at(12), Amount::from_sat(180_000_000));
+        window_limit.forget(&spend_txs[0].txid());
+        assert_eq!(window_limit.spent_at(12), Amount::from_sat(90_000_000));
+
+        // Invalid configurations
+        for (config, line) in &[
+            ("max_amount", 1),
+            ("\nmax_amount 1 BTD", 2),
+            ("window_limit 144", 1),
+            ("min_change_ratio 101", 1),
+            ("allow_destination bc1qqq", 1),
+            ("# ok\n\nmin_amount 1 BTC", 3),
+        ] {
+            match PolicySet::from_str(config) {
+                Err(PolicyError::InvalidConfig(l, _)) => assert_eq!(l, *line, "{}", config),
+                e => panic!("Unexpected result for '{}': {:?}", config, e),
+            }
+        }
+        assert!(PolicySet::from_str("").unwrap().policies().is_empty());
+    }
+}
@@ -0,0 +1,506 @@
+//! # Revault Spend proposals
+//!
+//! Managers coordinate a Spend by passing the [Spend
+//! transaction](crate::transactions::SpendTransaction) PSBT around. A [SpendProposal] tracks
+//! the Spend transaction along with the [Unvault
+//! transactions](crate::transactions::UnvaultTransaction) it depends on, collects the managers'
+//! and the cosigners' signatures for each of its inputs and eventually produces the
+//! [SpendBundle] to broadcast.
+
+use crate::{
+    error::SpendProposalError,
+    scripts::UnvaultDescriptor,
+    transactions::{RevaultTransaction, SpendTransaction, UnvaultTransaction},
+};
+
+use miniscript::{
+    bitcoin::{secp256k1, util::bip32, SigHashType, Transaction, Txid},
+    DescriptorTrait,
+};
+
+use std::collections::{HashMap, HashSet};
+
+// The keys which can sign for a Spend transaction input
+#[derive(Debug, Clone, PartialEq)]
+struct InputSigners {
+    managers: HashSet<secp256k1::PublicKey>,
+    cosigners: HashSet<secp256k1::PublicKey>,
+}
+
+/// The transactions to broadcast for a Spend, in dependency order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpendBundle {
+    /// The finalized Unvault transactions, to be broadcast first
+    pub unvault_txs: Vec<Transaction>,
+    /// The finalized Spend transaction, to be broadcast once all the Unvaults matured
+    pub spend_tx: Transaction,
+    /// The height of the first block the Spend transaction can be included in, if the
+    /// confirmation heights of all the Unvault transactions are known
+    pub csv_maturity: Option<u32>,
+}
+
+/// A Spend transaction being signed by the managers and the cosigners.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpendProposal {
+    spend_tx: SpendTransaction,
+    // One per Spend input, in the same order
+    unvault_txs: Vec<UnvaultTransaction>,
+    signers: Vec<InputSigners>,
+    unvault_heights: Vec<Option<u32>>,
+    managers_threshold: usize,
+    cosigners_threshold: usize,
+    csv_value: u32,
+}
+
+impl SpendProposal {
+    /// Create a proposal for this Spend transaction, spending the Unvault outputs of these
+    /// Unvault transactions. Each Unvault transaction comes with the derivation index of the
+    /// `unvault_descriptor` it pays to.
+    ///
+    /// # Errors
+    /// - If a Spend input does not spend the Unvault output of one of the Unvault transactions.
+    /// - If an Unvault transaction is not spent by the Spend transaction.
+    /// - If a Spend input does not pay to the `unvault_descriptor` at this derivation index, or
+    ///   its nSequence is not the Unvault CSV.
+    pub fn new<C: secp256k1::Verification>(
+        spend_tx: SpendTransaction,
+        unvault_txs: Vec<(UnvaultTransaction, bip32::ChildNumber)>,
+        unvault_descriptor: &UnvaultDescriptor,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<SpendProposal, SpendProposalError> {
+        let csv_value = unvault_descriptor.csv_value();
+        let mut unvault_txs: HashMap<Txid, (UnvaultTransaction, bip32::ChildNumber)> = unvault_txs
+            .into_iter()
+            .map(|(unvault_tx, index)| (unvault_tx.txid(), (unvault_tx, index)))
+            .collect();
+
+        let derive_keys = |keys: Vec<miniscript::DescriptorPublicKey>, index: u32| {
+            keys.into_iter()
+                .map(|key| {
+                    key.derive(index)
+                        .derive_public_key(secp)
+                        .map(|pk| pk.key)
+                        .expect("Unhardened derivation, checked at creation")
+                })
+                .collect::<HashSet<secp256k1::PublicKey>>()
+        };
+
+        let psbt = spend_tx.psbt();
+        let mut ordered_unvault_txs = Vec::with_capacity(psbt.inputs.len());
+        let mut signers = Vec::with_capacity(psbt.inputs.len());
+        for (txin, psbtin) in psbt.global.unsigned_tx.input.iter().zip(psbt.inputs.iter()) {
+            let outpoint = txin.previous_output;
+            let (unvault_tx, index) = unvault_txs
+                .remove(&outpoint.txid)
+                .ok_or(SpendProposalError::MissingUnvault(outpoint))?;
+
+            let script_pubkey = unvault_descriptor
+                .derive(index, secp)
+                .inner()
+                .script_pubkey();
+            let pays_to_unvault = unvault_tx
+                .tx()
+                .output
+                .get(outpoint.vout as usize)
+                .map(|txo| txo.script_pubkey == script_pubkey)
+                == Some(true)
+                && psbtin.witness_utxo.as_ref().map(|txo| &txo.script_pubkey)
+                    == Some(&script_pubkey);
+            if !pays_to_unvault || txin.sequence != csv_value {
+                return Err(SpendProposalError::InvalidUnvaultInput(outpoint));
+            }
+
+            let index: u32 = index.into();
+            signers.push(InputSigners {
+                managers: derive_keys(unvault_descriptor.managers(), index),
+                cosigners: derive_keys(unvault_descriptor.cosigners(), index),
+            });
+            ordered_unvault_txs.push(unvault_tx);
+        }
+        if let Some(txid) = unvault_txs.keys().next() {
+            return Err(SpendProposalError::UnknownUnvault(*txid));
+        }
+
+        Ok(SpendProposal {
+            unvault_heights: vec![None; ordered_unvault_txs.len()],
+            spend_tx,
+            unvault_txs: ordered_unvault_txs,
+            signers,
+            managers_threshold: unvault_descriptor.managers_threshold(),
+            cosigners_threshold: unvault_descriptor.cosigners_threshold(),
+            csv_value,
+        })
+    }
+
+    /// The proposed Spend transaction
+    pub fn spend_tx(&self) -> &SpendTransaction {
+        &self.spend_tx
+    }
+
+    /// The Unvault transactions, in the order of the Spend inputs spending them
+    pub fn unvault_txs(&self) -> &[UnvaultTransaction] {
+        &self.unvault_txs
+    }
+
+    /// Add a manager's or a cosigner's signature for this input of the Spend transaction.
+    ///
+    /// # Errors
+    /// - If the key is neither a manager's nor a cosigner's for this input.
+    /// - If the signature is invalid.
+    pub fn add_signature<C: secp256k1::Verification>(
+        &mut self,
+        input_index: usize,
+        pubkey: secp256k1::PublicKey,
+        signature: secp256k1::Signature,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), SpendProposalError> {
+        let signers = self
+            .signers
+            .get(input_index)
+            .ok_or(SpendProposalError::UnknownKey(pubkey))?;
+        if !signers.managers.contains(&pubkey) && !signers.cosigners.contains(&pubkey) {
+            return Err(SpendProposalError::UnknownKey(pubkey));
+        }
+
+        self.spend_tx
+            .add_signature(input_index, pubkey, signature, secp)?;
+        Ok(())
+    }
+
+    /// Add the managers' and cosigners' signatures of another copy of the Spend transaction,
+    /// as passed around by the participants.
+    ///
+    /// # Errors
+    /// - If this is not the same Spend transaction.
+    /// - If a signature is for an unknown key or is invalid.
+    pub fn merge_signatures<C: secp256k1::Verification>(
+        &mut self,
+        spend_tx: &SpendTransaction,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), SpendProposalError> {
+        if spend_tx.txid() != self.spend_tx.txid() {
+            return Err(SpendProposalError::SpendMismatch(spend_tx.txid()));
+        }
+
+        for (input_index, psbtin) in spend_tx.psbt().inputs.iter().enumerate() {
+            for (pubkey, rawsig) in psbtin.partial_sigs.iter() {
+                let signature = rawsig
+                    .split_last()
+                    .filter(|(sighash_type, _)| **sighash_type == SigHashType::All.as_u32() as u8)
+                    .and_then(|(_, der_sig)| secp256k1::Signature::from_der(der_sig).ok())
+                    .ok_or(SpendProposalError::InvalidSignature(pubkey.key))?;
+                self.add_signature(input_index, pubkey.key, signature, secp)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The number of managers and cosigners signatures for this input
+    fn signatures_count(&self, input_index: usize) -> (usize, usize) {
+        let signers = &self.signers[input_index];
+        self.spend_tx.psbt().inputs[input_index]
+            .partial_sigs
+            .keys()
+            .fold((0, 0), |(managers, cosigners), pubkey| {
+                (
+                    managers + signers.managers.contains(&pubkey.key) as usize,
+                    cosigners + signers.cosigners.contains(&pubkey.key) as usize,
+                )
+            })
+    }
+
+    /// Get the number of managers and cosigners signatures still needed for each Spend input.
+    pub fn missing_signatures(&self) -> Vec<(usize, usize)> {
+        (0..self.signers.len())
+            .map(|input_index| {
+                let (managers, cosigners) = self.signatures_count(input_index);
+                (
+                    self.managers_threshold.saturating_sub(managers),
+                    self.cosigners_threshold.saturating_sub(cosigners),
+                )
+            })
+            .collect()
+    }
+
+    /// Whether the managers' and cosigners' thresholds are met for all the Spend inputs.
+    pub fn is_complete(&self) -> bool {
+        self.missing_signatures()
+            .iter()
+            .all(|missing| *missing == (0, 0))
+    }
+
+    /// Record the confirmation height of this Unvault transaction.
+    ///
+    /// # Errors
+    /// - If this transaction is not one of the proposal's Unvault transactions.
+    pub fn set_unvault_height(
+        &mut self,
+        unvault_txid: Txid,
+        height: u32,
+    ) -> Result<(), SpendProposalError> {
+        let position = self
+            .unvault_txs
+            .iter()
+            .position(|unvault_tx| unvault_tx.txid() == unvault_txid)
+            .ok_or(SpendProposalError::UnknownUnvault(unvault_txid))?;
+        self.unvault_heights[position] = Some(height);
+        Ok(())
+    }
+
+    /// The height of the first block the Spend transaction can be included in, if the
+    /// confirmation heights of all the Unvault transactions are known.
+    pub fn csv_maturity(&self) -> Option<u32> {
+        self.unvault_heights
+            .iter()
+            .try_fold(0, |max_height, height| height.map(|h| max_height.max(h)))
+            .map(|height| height.saturating_add(self.csv_value))
+    }
+
+    /// Finalize the Unvault transactions and the Spend transaction.
+    ///
+    /// # Errors
+    /// - If the thresholds are not met for all the Spend inputs.
+    /// - If a transaction could not be finalized (e.g. an Unvault transaction lacks
+    ///   stakeholders' signatures).
+    pub fn into_bundle<C: secp256k1::Verification>(
+        self,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<SpendBundle, SpendProposalError> {
+        if !self.is_complete() {
+            return Err(SpendProposalError::MissingSignatures);
+        }
+        let csv_maturity = self.csv_maturity();
+
+        let mut unvault_txs = Vec::with_capacity(self.unvault_txs.len());
+        for mut unvault_tx in self.unvault_txs {
+            if !unvault_tx.is_finalized() {
+                unvault_tx
+                    .finalize(secp)
+                    .map_err(|e| SpendProposalError::Finalisation(e.to_string()))?;
+            }
+            unvault_txs.push(unvault_tx.into_tx());
+        }
+
+        let mut spend_tx = self.spend_tx;
+        spend_tx
+            .finalize(secp)
+            .map_err(|e| SpendProposalError::Finalisation(e.to_string()))?;
+
+        Ok(SpendBundle {
+            unvault_txs,
+            spend_tx: spend_tx.into_tx(),
+            csv_maturity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpendProposal;
+    use crate::{
+        cosigner::{Cosigner, InMemorySigningHistory},
+        error::SpendProposalError,
+        scripts::{CpfpDescriptor, DepositDescriptor, UnvaultDescriptor},
+        test_utils::{cosigner_pubkey, get_random_privkey, get_random_secret_key, sign_at, xpubs},
+        transactions::{RevaultTransaction, SpendTransaction, UnvaultTransaction},
+        txins::DepositTxIn,
+        txouts::{DepositTxOut, SpendTxOut},
+    };
+
+    use miniscript::{
+        bitcoin::{secp256k1, util::bip32, Amount, OutPoint, SigHashType, TxOut, Txid},
+        DescriptorTrait,
+    };
+
+    // Sign the input of this transaction with the keys derived at this index
+    fn sign(
+        tx: &impl RevaultTransaction,
+        input_index: usize,
+        xpriv: &bip32::ExtendedPrivKey,
+        index: bip32::ChildNumber,
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+    ) -> (secp256k1::PublicKey, secp256k1::Signature) {
+        let sighash = tx.signature_hash(input_index, SigHashType::All).unwrap();
+        sign_at(&sighash, xpriv, index, secp)
+    }
+
+    #[test]
+    fn spend_proposal() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let stakeholders: Vec<_> = (0..2).map(|_| get_random_privkey(&mut rng)).collect();
+        let managers: Vec<_> = (0..3).map(|_| get_random_privkey(&mut rng)).collect();
+        let cosigners_keys: Vec<_> = (0..2).map(|_| get_random_secret_key(&mut rng)).collect();
+        let cosigners = cosigners_keys
+            .iter()
+            .map(|sk| cosigner_pubkey(sk, &secp))
+            .collect();
+        let deposit_descriptor = DepositDescriptor::new(xpubs(&stakeholders, &secp)).unwrap();
+        let unvault_descriptor = UnvaultDescriptor::new_with_thresholds(
+            xpubs(&stakeholders, &secp),
+            2,
+            xpubs(&managers, &secp),
+            2,
+            cosigners,
+            None,
+            6,
+        )
+        .unwrap();
+        let cpfp_descriptor = CpfpDescriptor::new(xpubs(&managers, &secp)).unwrap();
+
+        // Two signed Unvault transactions
+        let unvault_txs: Vec<_> = [3, 12]
+            .iter()
+            .map(|index| {
+                let index = bip32::ChildNumber::from(*index);
+                let deposit_txin = DepositTxIn::new(
+                    OutPoint::new(Txid::default(), index.into()),
+                    DepositTxOut::new(
+                        Amount::from_sat(50_000_000),
+                        &deposit_descriptor.derive(index, &secp),
+                    ),
+                );
+                let mut unvault_tx = UnvaultTransaction::new(
+                    deposit_txin,
+                    &unvault_descriptor.derive(index, &secp),
+                    &cpfp_descriptor.derive(index, &secp),
+                    0,
+                )
+                .unwrap();
+                for xpriv in stakeholders.iter() {
+                    let (pubkey, sig) = sign(&unvault_tx, 0, xpriv, index, &secp);
+                    unvault_tx.add_sig(pubkey, sig, &secp).unwrap();
+                }
+                (unvault_tx, index)
+            })
+            .collect();
+        let spend_tx = SpendTransaction::new(
+            unvault_txs
+                .iter()
+                .map(|(unvault_tx, index)| {
+                    unvault_tx.spend_unvault_txin(&unvault_descriptor.derive(*index, &secp))
+                })
+                .collect(),
+            vec![SpendTxOut::Destination(TxOut {
+                value: 99_000_000,
+                script_pubkey: deposit_descriptor
+                    .derive(bip32::ChildNumber::from(42), &secp)
+                    .inner()
+                    .script_pubkey(),
+            })],
+            &cpfp_descriptor.derive(bip32::ChildNumber::from(12), &secp),
+            0,
+            false,
+        )
+        .unwrap();
+
+        // The Unvault transactions must match the inputs
+        assert_eq!(
+            SpendProposal::new(
+                spend_tx.clone(),
+                unvault_txs[..1].to_vec(),
+                &unvault_descriptor,
+                &secp
+            )
+            .unwrap_err(),
+            SpendProposalError::MissingUnvault(spend_tx.tx().input[1].previous_output)
+        );
+        assert_eq!(
+            SpendProposal::new(
+                spend_tx.clone(),
+                vec![
+                    (unvault_txs[0].0.clone(), bip32::ChildNumber::from(4)),
+                    unvault_txs[1].clone()
+                ],
+                &unvault_descriptor,
+                &secp
+            )
+            .unwrap_err(),
+            SpendProposalError::InvalidUnvaultInput(spend_tx.tx().input[0].previous_output)
+        );
+        let mut proposal = SpendProposal::new(
+            spend_tx.clone(),
+            unvault_txs.clone(),
+            &unvault_descriptor,
+            &secp,
+        )
+        .unwrap();
+        assert_eq!(proposal.missing_signatures(), vec![(2, 2), (2, 2)]);
+
+        // The stakeholders can't sign the Spend
+        let (pubkey, sig) = sign(&spend_tx, 0, &stakeholders[0], unvault_txs[0].1, &secp);
+        assert_eq!(
+            proposal.add_signature(0, pubkey, sig, &secp),
+            Err(SpendProposalError::UnknownKey(pubkey))
+        );
+
+        // Two managers sign, one of them in a copy of the PSBT
+        let mut spend_copy = spend_tx.clone();
+        for (input_index, (_, index)) in unvault_txs.iter().enumerate() {
+            let (pubkey, sig) = sign(&spend_tx, input_index, &managers[0], *index, &secp);
+            proposal
+                .add_signature(input_index, pubkey, sig, &secp)
+                .unwrap();
+            let (pubkey, sig) = sign(&spend_tx, input_index, &managers[2], *index, &secp);
+            spend_copy
+                .add_signature(input_index, pubkey, sig, &secp)
+                .unwrap();
+        }
+        proposal.merge_signatures(&spend_copy, &secp).unwrap();
+        assert_eq!(proposal.missing_signatures(), vec![(0, 2), (0, 2)]);
+        assert_eq!(
+            proposal.clone().into_bundle(&secp).unwrap_err(),
+            SpendProposalError::MissingSignatures
+        );
+
+        // Then the cosigners
+        for secret_key in cosigners_keys {
+            let mut cosigner = Cosigner::new(
+                &unvault_descriptor,
+                secret_key,
+                20,
+                InMemorySigningHistory::new(),
+                &secp,
+            )
+            .unwrap();
+            let mut spend_copy = proposal.spend_tx().clone();
+            cosigner.sign(&mut spend_copy, &secp).unwrap();
+            proposal.merge_signatures(&spend_copy, &secp).unwrap();
+        }
+        assert!(proposal.is_complete());
+
+        // The bundle is in dependency order, and we know when to broadcast the Spend once we
+        // know when all the Unvaults confirmed
+        assert_eq!(proposal.csv_maturity(), None);
+        proposal
+            .set_unvault_height(unvault_txs[1].0.txid(), 110)
+            .unwrap();
+        assert_eq!(proposal.csv_maturity(), None);
+        proposal
+            .set_unvault_height(unvault_txs[0].0.txid(), 100)
+            .unwrap();
+        assert_eq!(
+            proposal.set_unvault_height(spend_tx.txid(), 100),
+            Err(SpendProposalError::UnknownUnvault(spend_tx.txid()))
+        );
+        let bundle = proposal.into_bundle(&secp).unwrap();
+        assert_eq!(bundle.csv_maturity, Some(116));
+        assert_eq!(
+            bundle
+                .unvault_txs
+                .iter()
+                .map(|tx| tx.txid())
+                .collect::<Vec<_>>(),
+            unvault_txs
+                .iter()
+                .map(|(tx, _)| tx.txid())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(bundle.spend_tx.txid(), spend_tx.txid());
+        for (txin, unvault_tx) in bundle.spend_tx.input.iter().zip(bundle.unvault_txs.iter()) {
+            assert_eq!(txin.previous_output.txid, unvault_tx.txid());
+            assert!(!txin.witness.is_empty());
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! # Revault deposits detection
+//!
+//! Detect the outputs paying to the [Deposit descriptor](crate::scripts::DepositDescriptor) in
+//! transactions and blocks. The scriptPubKeys are precomputed for a window of derivation indexes
+//! which is extended as deposits are found, so that there are always `gap_limit` unused indexes
+//! watched after the last used one.
+
+use crate::{error::ScriptCreationError, scripts::DepositDescriptor};
+
+use miniscript::{
+    bitcoin::{secp256k1, util::bip32, Amount, Block, OutPoint, Script, Transaction},
+    DescriptorTrait,
+};
+
+use std::collections::HashMap;
+
+/// The first index we can't derive, as we only use unhardened derivation.
+const HARDENED_INDEX: u32 = 1 << 31;
+
+/// An output paying to the Deposit descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedDeposit {
+    /// The deposit outpoint
+    pub outpoint: OutPoint,
+    /// The deposit value
+    pub amount: Amount,
+    /// The derivation index of the Deposit descriptor this output pays to
+    pub child_number: bip32::ChildNumber,
+}
+
+/// Detects deposits to a Deposit descriptor within a derivation window.
+#[derive(Debug, Clone)]
+pub struct DepositScanner {
+    deposit_descriptor: DepositDescriptor,
+    gap_limit: u32,
+    // The scriptPubKeys of all the derived indexes
+    script_pubkeys: HashMap<Script, bip32::ChildNumber>,
+    // The first index we did not derive yet
+    next_index: u32,
+}
+
+impl DepositScanner {
+    /// Create a scanner for this `deposit_descriptor`, watching the `gap_limit` first
+    /// derivation indexes.
+    ///
+    /// # Errors
+    /// - If `gap_limit` is 0, as we must watch at least one index.
+    pub fn new<C: secp256k1::Verification>(
+        deposit_descriptor: DepositDescriptor,
+        gap_limit: u32,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<DepositScanner, ScriptCreationError> {
+        if gap_limit == 0 {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        let mut scanner = DepositScanner {
+            deposit_descriptor,
+            gap_limit,
+            script_pubkeys: HashMap::with_capacity(gap_limit as usize),
+            next_index: 0,
+        };
+        scanner.derive_up_to(gap_limit, secp);
+
+        Ok(scanner)
+    }
+
+    // Derive the scriptPubKeys up to (but excluding) this index
+    fn derive_up_to<C: secp256k1::Verification>(
+        &mut self,
+        index: u32,
+        secp: &secp256k1::Secp256k1<C>,
+    ) {
+        let index = index.min(HARDENED_INDEX);
+        while self.next_index < index {
+            let child_number = bip32::ChildNumber::from(self.next_index);
+            let script_pubkey = self
+                .deposit_descriptor
+                .derive(child_number, secp)
+                .inner()
+                .script_pubkey();
+            self.script_pubkeys.insert(script_pubkey, child_number);
+            self.next_index += 1;
+        }
+    }
+
+    /// Get the derivation index this scriptPubKey corresponds to, if it's one of our deposit
+    /// scriptPubKeys in the current window.
+    pub fn derivation_index(&self, script_pubkey: &Script) -> Option<bip32::ChildNumber> {
+        self.script_pubkeys.get(script_pubkey).copied()
+    }
+
+    /// The first derivation index that is not watched yet.
+    pub fn next_index(&self) -> u32 {
+        self.next_index
+    }
+
+    /// Get all the outputs of this transaction paying to one of our deposit scriptPubKeys.
+    /// The window is extended so that `gap_limit` indexes are watched after the highest one
+    /// used.
+    pub fn scan_transaction<C: secp256k1::Verification>(
+        &mut self,
+        tx: &Transaction,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Vec<DetectedDeposit> {
+        let txid = tx.txid();
+        let mut deposits = Vec::new();
+
+        for (vout, txo) in tx.output.iter().enumerate() {
+            if let Some(child_number) = self.derivation_index(&txo.script_pubkey) {
+                deposits.push(DetectedDeposit {
+                    outpoint: OutPoint {
+                        txid,
+                        vout: vout as u32,
+                    },
+                    amount: Amount::from_sat(txo.value),
+                    child_number,
+                });
+
+                let index: u32 = child_number.into();
+                self.derive_up_to(index.saturating_add(1).saturating_add(self.gap_limit), secp);
+            }
+        }
+
+        deposits
+    }
+
+    /// Get all the outputs of the transactions of this block paying to one of our deposit
+    /// scriptPubKeys. See [DepositScanner::scan_transaction].
+    pub fn scan_block<C: secp256k1::Verification>(
+        &mut self,
+        block: &Block,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Vec<DetectedDeposit> {
+        block
+            .txdata
+            .iter()
+            .flat_map(|tx| self.scan_transaction(tx, secp))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DepositScanner, DetectedDeposit};
+    use crate::{
+        error::ScriptCreationError, scripts::DepositDescriptor, test_utils::get_random_pubkey,
+    };
+
+    use miniscript::{
+        bitcoin::{
+            blockdata::constants::genesis_block, secp256k1, util::bip32, Amount, Block, Network,
+            OutPoint, Transaction, TxIn, TxOut,
+        },
+        DescriptorTrait,
+    };
+
+    fn dummy_tx(outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default()],
+            output: outputs,
+        }
+    }
+
+    #[test]
+    fn deposit_scanner() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let deposit_descriptor =
+            DepositDescriptor::new((0..3).map(|_| get_random_pubkey(&mut rng, &secp)).collect())
+                .unwrap();
+        let deposit_txo = |index: u32, value: u64| TxOut {
+            value,
+            script_pubkey: deposit_descriptor
+                .derive(bip32::ChildNumber::from(index), &secp)
+                .inner()
+                .script_pubkey(),
+        };
+        assert_eq!(
+            DepositScanner::new(deposit_descriptor.clone(), 0, &secp)
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::BadParameters.to_string()
+        );
+        let mut scanner = DepositScanner::new(deposit_descriptor.clone(), 20, &secp).unwrap();
+        assert_eq!(scanner.next_index(), 20);
+
+        // Not ours, or out of the window
+        let tx = dummy_tx(vec![
+            TxOut {
+                value: 1_000_000,
+                script_pubkey: genesis_block(Network::Bitcoin).txdata[0].output[0]
+                    .script_pubkey
+                    .clone(),
+            },
+            deposit_txo(20, 1_000_000),
+        ]);
+        assert!(scanner.scan_transaction(&tx, &secp).is_empty());
+
+        // A deposit at the end of the window extends it
+        let tx = dummy_tx(vec![
+            TxOut::default(),
+            deposit_txo(19, 1_000_000),
+            deposit_txo(3, 500_000),
+        ]);
+        assert_eq!(
+            scanner.scan_transaction(&tx, &secp),
+            vec![
+                DetectedDeposit {
+                    outpoint: OutPoint {
+                        txid: tx.txid(),
+                        vout: 1
+                    },
+                    amount: Amount::from_sat(1_000_000),
+                    child_number: bip32::ChildNumber::from(19),
+                },
+                DetectedDeposit {
+                    outpoint: OutPoint {
+                        txid: tx.txid(),
+                        vout: 2
+                    },
+                    amount: Amount::from_sat(500_000),
+                    child_number: bip32::ChildNumber::from(3),
+                }
+            ]
+        );
+        assert_eq!(scanner.next_index(), 40);
+
+        // Now the index 20 is watched, and we can scan blocks
+        let mut block: Block = genesis_block(Network::Bitcoin);
+        let tx = dummy_tx(vec![deposit_txo(20, 1_000_000), deposit_txo(39, 42_000)]);
+        block.txdata.push(tx.clone());
+        let deposits = scanner.scan_block(&block, &secp);
+        assert_eq!(deposits.len(), 2);
+        assert_eq!(deposits[0].child_number, bip32::ChildNumber::from(20));
+        assert_eq!(deposits[1].child_number, bip32::ChildNumber::from(39));
+        assert_eq!(deposits[1].outpoint, OutPoint::new(tx.txid(), 1));
+        assert_eq!(scanner.next_index(), 60);
+        assert_eq!(
+            scanner.derivation_index(&deposit_txo(59, 0).script_pubkey),
+            Some(bip32::ChildNumber::from(59))
+        );
+        assert_eq!(
+            scanner.derivation_index(&deposit_txo(60, 0).script_pubkey),
+            None
+        );
+    }
+}
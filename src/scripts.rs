@@ -6,28 +6,51 @@
 //! We use [miniscript](http://bitcoin.sipa.be/miniscript/) in order to "safely" compile,
 //! derive, and satisfy Scripts depending on the setup configuration (ie the number of
 //! stakeholders, the number of fund managers, and the relative timelock) for all script
-//! but the (unknown) Emergency one.
+//! but the Emergency one. The Emergency Script is usually unknown, but an
+//! [EmergencyDescriptor] can optionally be used to create the Emergency address.
 //!
 //! **NOTE**: the compilation functions are not safe to reuse after initial set up, as the
 //! returned descriptors are non-deterministically compiled from an abstract policy.
 //! Backup the output Miniscript descriptors instead.
+//!
+//! The `new_deterministic` constructors don't use the policy compiler but a fixed template,
+//! and always return the same descriptor for the same set of keys (whatever their order). They
+//! can be used by each participant to re-create the descriptors and cross-check a setup.
+//! Keys are sorted by their string representation, and a k-of-n between keys is:
+//! - `multi(k,K1,...,Kn)` if there are at most 20 keys,
+//! - `thresh(k,pk(K1),s:pk(K2),...,s:pk(Kn))` otherwise.
+//!
+//! The templates are:
+//! - Deposit: `wsh(<N-of-N stakeholders>)`
+//! - Deposit with recovery: `wsh(or_d(<N-of-N stakeholders>,and_v(v:<k-of-N recovery keys>,older(csv))))`
+//! - Unvault: `wsh(or_d(<N-of-N stakeholders>,and_v(v:<k-of-M managers>,and_v(v:pk(C1),...,and_v(v:pk(Cn),older(csv))))))`
+//! - Unvault with thresholds: `wsh(or_d(<k-of-N stakeholders>,and_v(v:<k-of-M managers>,and_v(v:<k-of-N cosigners>,older(csv)))))`,
+//!   or the cosigners' `and_v()` chain above if they are all required.
+//! - Unvault without cosigners: `wsh(or_d(<k-of-N stakeholders>,and_v(v:<k-of-M managers>,older(csv))))`
+//! - CPFP: `wsh(<1-of-M managers>)`
+//! - Emergency: `wsh(and_v(v:<N-of-N stakeholders>,older(csv)))`
+//!
+//! All the descriptors are v0 P2WSH ones. Taproot descriptors are not supported yet, as they
+//! are blocked on our Miniscript and rust-bitcoin versions.
 
-use crate::error::*;
+use crate::{error::*, standardness};
 
 use miniscript::{
-    bitcoin::{secp256k1, util::bip32, Address, PublicKey},
+    bitcoin::{secp256k1, util::bip32, Address, Network, PublicKey, Script},
     descriptor::{DescriptorPublicKey, Wildcard, WshInner},
     miniscript::{
         iter::PkPkh,
         limits::{SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_TYPE_FLAG},
     },
-    policy::concrete::Policy,
-    Descriptor, ForEachKey, MiniscriptKey, Segwitv0, Terminal, TranslatePk2,
+    policy::{concrete::Policy, semantic::Policy as SemanticPolicy},
+    Descriptor, DescriptorTrait, ForEachKey, Miniscript, MiniscriptKey, Segwitv0, Terminal,
+    TranslatePk2,
 };
 
 use std::{
     fmt::{self, Display},
     str::FromStr,
+    sync::Arc,
 };
 
 #[cfg(feature = "use-serde")]
@@ -38,6 +61,19 @@ use serde::de;
 /// <https://github.com/bitcoin/bitcoin/blob/4a540683ec40393d6369da1a9e02e45614db936d/src/primitives/transaction.h#L87-L89>
 pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x00_00_ff_ff;
 
+// Maximum number of keys in a CHECKMULTISIG
+const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+// We require the locktime to:
+//  - not be disabled
+//  - be in number of blocks
+//  - be 'clean' / minimal, ie all bits without consensus meaning should be 0
+fn is_valid_csv(csv_value: u32) -> bool {
+    (csv_value & SEQUENCE_LOCKTIME_DISABLE_FLAG) == 0
+        && (csv_value & SEQUENCE_LOCKTIME_TYPE_FLAG) == 0
+        && (csv_value & SEQUENCE_LOCKTIME_MASK) == csv_value
+}
+
 // These are useful to create TxOuts out of the right Script descriptor
 
 macro_rules! impl_descriptor_newtype {
@@ -59,18 +95,47 @@ macro_rules! impl_descriptor_newtype {
                 self.0
             }
 
-            /// Derives all wildcard keys in the descriptor using the supplied `child_number`
+            /// Derives all wildcard keys in the descriptor using the supplied `child_number`.
+            ///
+            /// # Panics
+            /// - If the `child_number` is hardened, use [Self::try_derive] to handle this case.
             pub fn derive<C: secp256k1::Verification>(
                 &self,
                 child_number: bip32::ChildNumber,
                 secp: &secp256k1::Secp256k1<C>,
             ) -> $derived_struct_name {
-                $derived_struct_name(
+                self.try_derive(child_number, secp)
+                    .expect("All pubkeys are unhardened wildcards, checked at creation.")
+            }
+
+            /// Derives all wildcard keys in the descriptor using the supplied `child_number`.
+            ///
+            /// # Errors
+            /// - If the `child_number` is hardened, as we can't derive hardened children from xpubs.
+            pub fn try_derive<C: secp256k1::Verification>(
+                &self,
+                child_number: bip32::ChildNumber,
+                secp: &secp256k1::Secp256k1<C>,
+            ) -> Result<$derived_struct_name, ScriptCreationError> {
+                if child_number.is_hardened() {
+                    return Err(ScriptCreationError::HardenedDerivation);
+                }
+
+                Ok($derived_struct_name(
                     self.0
                         .derive(child_number.into())
                         .translate_pk2(|xpk| xpk.derive_public_key(secp))
-                        .expect("All pubkeys are derived, no wildcard."),
-                )
+                        .map_err(|_| ScriptCreationError::HardenedDerivation)?,
+                ))
+            }
+
+            /// Parse a descriptor containing multipath keys (eg `xpub/<0;1>/*`) into one
+            /// descriptor per path, in order. See [expand_multipath] for the accepted format.
+            pub fn from_multipath_str(s: &str) -> Result<Vec<$struct_name>, ScriptCreationError> {
+                expand_multipath(s)?
+                    .iter()
+                    .map(|desc_str| $struct_name::from_str(desc_str))
+                    .collect()
             }
         }
 
@@ -82,6 +147,11 @@ macro_rules! impl_descriptor_newtype {
             pub fn into_inner(self) -> Descriptor<PublicKey> {
                 self.0
             }
+
+            /// Get the P2WSH address of this descriptor on the given network
+            pub fn address(&self, network: Network) -> Address {
+                self.0.address(network).expect("It's a P2WSH")
+            }
         }
     };
 }
@@ -115,6 +185,26 @@ macro_rules! deposit_desc_checks {
     };
 }
 
+macro_rules! deposit_recovery_desc_checks {
+    ($stakeholders:ident, $recovery_keys:ident, $recovery_threshold:ident, $recovery_csv:ident) => {
+        deposit_desc_checks!($stakeholders);
+
+        if $recovery_keys.len() != $stakeholders.len()
+            || $recovery_keys.iter().any(|k| $stakeholders.contains(k))
+        {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        if $recovery_threshold == 0 || $recovery_threshold > $recovery_keys.len() {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        if !is_valid_csv($recovery_csv) {
+            return Err(ScriptCreationError::BadParameters);
+        }
+    };
+}
+
 macro_rules! deposit_desc {
     ($stakeholders:ident) => {{
         let pubkeys = $stakeholders
@@ -126,30 +216,44 @@ macro_rules! deposit_desc {
 
         // This handles the non-safe or malleable cases.
         let ms = policy.compile::<Segwitv0>()?;
-        Descriptor::new_wsh(ms)?
+        let desc = wsh_descriptor(ms)?;
+
+        // We rely on it to recover the stakeholders from the descriptor.
+        deposit_template_check(&desc)?;
+
+        desc
     }};
 }
 
 macro_rules! unvault_desc_checks {
     ($stakeholders:ident,$managers:ident, $managers_threshold:ident, $cosigners:ident, $csv_value:ident) => {
+        // There may be no cosigning server at all, otherwise one per stakeholder.
         if $stakeholders.is_empty()
             || $managers.is_empty()
-            || $cosigners.len() != $stakeholders.len()
+            || (!$cosigners.is_empty() && $cosigners.len() != $stakeholders.len())
         {
             return Err(ScriptCreationError::BadParameters);
         }
 
-        if $managers_threshold > $managers.len() {
+        if $managers_threshold == 0 || $managers_threshold > $managers.len() {
+            return Err(ScriptCreationError::BadParameters);
+        }
+
+        if !is_valid_csv($csv_value) {
+            return Err(ScriptCreationError::BadParameters);
+        }
+    };
+}
+
+macro_rules! unvault_thresholds_checks {
+    ($stakeholders:ident, $stakeholders_threshold:ident, $cosigners:ident, $cosigners_threshold:ident) => {
+        if $stakeholders_threshold == 0 || $stakeholders_threshold > $stakeholders.len() {
             return Err(ScriptCreationError::BadParameters);
         }
 
-        // We require the locktime to:
-        //  - not be disabled
-        //  - be in number of blocks
-        //  - be 'clean' / minimal, ie all bits without consensus meaning should be 0
-        if ($csv_value & SEQUENCE_LOCKTIME_DISABLE_FLAG) != 0
-            || ($csv_value & SEQUENCE_LOCKTIME_TYPE_FLAG) != 0
-            || ($csv_value & SEQUENCE_LOCKTIME_MASK) != $csv_value
+        // A threshold of 0 is only valid without cosigners at all.
+        if ($cosigners_threshold == 0 && !$cosigners.is_empty())
+            || $cosigners_threshold > $cosigners.len()
         {
             return Err(ScriptCreationError::BadParameters);
         }
@@ -174,9 +278,12 @@ macro_rules! unvault_desc {
             .into_iter()
             .map(Policy::Key)
             .collect::<Vec<Policy<_>>>();
-        let cosigners_thres = Policy::Threshold(pubkeys.len(), pubkeys);
-
-        let cosigners_and_csv = Policy::And(vec![cosigners_thres, Policy::Older($csv_value)]);
+        let cosigners_and_csv = if pubkeys.is_empty() {
+            Policy::Older($csv_value)
+        } else {
+            let cosigners_thres = Policy::Threshold(pubkeys.len(), pubkeys);
+            Policy::And(vec![cosigners_thres, Policy::Older($csv_value)])
+        };
 
         let managers_and_cosigners_and_csv = Policy::And(vec![spenders_thres, cosigners_and_csv]);
 
@@ -187,31 +294,283 @@ macro_rules! unvault_desc {
 
         // This handles the non-safe or malleable cases.
         let ms = policy.compile::<Segwitv0>()?;
+        let desc = wsh_descriptor(ms)?;
 
-        Descriptor::new_wsh(ms)?
+        // We rely on it to recover the participants from the descriptor.
+        unvault_participants(&desc)?;
+
+        desc
     }};
 }
 
-// Check all xpubs contain a wildcard
+// Check all xpubs contain an unhardened wildcard, can be derived from without private keys, and
+// that their origin (if any) is consistent with the xpub.
 fn check_deriveable<'a>(
     keys: impl Iterator<Item = &'a DescriptorPublicKey>,
 ) -> Result<(), ScriptCreationError> {
     for key in keys {
-        match key {
-            DescriptorPublicKey::XPub(xpub) => {
-                if matches!(xpub.wildcard, Wildcard::None) {
-                    return Err(ScriptCreationError::NonWildcardKeys);
-                }
-            }
+        let xpub = match key {
+            DescriptorPublicKey::XPub(xpub) => xpub,
             DescriptorPublicKey::SinglePub(_) => {
                 return Err(ScriptCreationError::NonWildcardKeys);
             }
+        };
+
+        match xpub.wildcard {
+            Wildcard::None => return Err(ScriptCreationError::NonWildcardKeys),
+            Wildcard::Hardened => return Err(ScriptCreationError::HardenedDerivation),
+            Wildcard::Unhardened => {}
+        }
+        if xpub.derivation_path.into_iter().any(|c| c.is_hardened()) {
+            return Err(ScriptCreationError::HardenedDerivation);
+        }
+
+        // The origin path (hardened or not) must lead from the origin fingerprint to the xpub
+        if let Some((ref fingerprint, ref origin_path)) = xpub.origin {
+            let origin_path = origin_path.as_ref();
+            let is_consistent = match origin_path.last() {
+                None => xpub.xkey.depth == 0 && fingerprint == &xpub.xkey.fingerprint(),
+                Some(child_number) => {
+                    origin_path.len() == xpub.xkey.depth as usize
+                        && child_number == &xpub.xkey.child_number
+                        && (origin_path.len() > 1 || fingerprint == &xpub.xkey.parent_fingerprint)
+                }
+            };
+            if !is_consistent {
+                return Err(ScriptCreationError::InvalidKeyOrigin(key.to_string()));
+            }
         }
     }
 
     Ok(())
 }
 
+// Check all the keys of a parsed descriptor, see [check_deriveable].
+fn check_descriptor_keys(
+    desc: &Descriptor<DescriptorPublicKey>,
+) -> Result<(), ScriptCreationError> {
+    let mut keys_check = Ok(());
+    desc.for_each_key(|k| {
+        keys_check = check_deriveable(std::iter::once(k.as_key()));
+        keys_check.is_ok()
+    });
+    keys_check
+}
+
+/// Expand the multipath steps of a descriptor string into one descriptor string per path.
+///
+/// A multipath step is a list of derivation steps between angle brackets separated by
+/// semicolons, for instance `xpub/<0;1>/*` expands to `xpub/0/*` and `xpub/1/*`. All the
+/// multipath steps of a descriptor must have the same number of paths, which are expanded in
+/// lockstep. A descriptor without multipath step expands to itself.
+///
+/// # Errors
+/// - If a multipath step is malformed, or has less than 2 paths.
+/// - If the multipath steps don't all have the same number of paths.
+/// - If a multipath descriptor has a checksum, as it can't be checked once expanded.
+pub fn expand_multipath(s: &str) -> Result<Vec<String>, ScriptCreationError> {
+    let mut chunks = Vec::new();
+    let mut alternatives: Vec<Vec<&str>> = Vec::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..]
+            .find('>')
+            .map(|end| start + end)
+            .ok_or(ScriptCreationError::InvalidMultipath)?;
+        let paths: Vec<&str> = rest[start + 1..end].split(';').collect();
+        if paths.len() < 2 || paths.iter().any(|p| p.is_empty() || p.contains('<')) {
+            return Err(ScriptCreationError::InvalidMultipath);
+        }
+        if let Some(first) = alternatives.first() {
+            if first.len() != paths.len() {
+                return Err(ScriptCreationError::InvalidMultipath);
+            }
+        }
+
+        chunks.push(&rest[..start]);
+        alternatives.push(paths);
+        rest = &rest[end + 1..];
+    }
+
+    if rest.contains('>') {
+        return Err(ScriptCreationError::InvalidMultipath);
+    }
+    let n_paths = match alternatives.first() {
+        Some(paths) => paths.len(),
+        None => return Ok(vec![s.to_string()]),
+    };
+    if s.contains('#') {
+        return Err(ScriptCreationError::InvalidMultipath);
+    }
+
+    Ok((0..n_paths)
+        .map(|i| {
+            let mut desc_str = String::with_capacity(s.len());
+            for (chunk, paths) in chunks.iter().zip(alternatives.iter()) {
+                desc_str.push_str(chunk);
+                desc_str.push_str(paths[i]);
+            }
+            desc_str.push_str(rest);
+            desc_str
+        })
+        .collect())
+}
+
+// A k-of-n between the sorted keys, see the module documentation.
+fn template_thresh<Pk: MiniscriptKey>(
+    k: usize,
+    mut keys: Vec<Pk>,
+) -> Result<Miniscript<Pk, Segwitv0>, ScriptCreationError> {
+    keys.sort_by_cached_key(|k| k.to_string());
+
+    let node = if keys.len() <= MAX_PUBKEYS_PER_MULTISIG {
+        Terminal::Multi(k, keys)
+    } else {
+        let subs = keys
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let pk = Miniscript::from_ast(Terminal::Check(Arc::new(Miniscript::from_ast(
+                    Terminal::PkK(key),
+                )?)))?;
+                if i == 0 {
+                    Ok(Arc::new(pk))
+                } else {
+                    Ok(Arc::new(Miniscript::from_ast(Terminal::Swap(Arc::new(
+                        pk,
+                    )))?))
+                }
+            })
+            .collect::<Result<Vec<_>, ScriptCreationError>>()?;
+        Terminal::Thresh(k, subs)
+    };
+
+    Ok(Miniscript::from_ast(node)?)
+}
+
+// Create a P2WSH descriptor for this Miniscript, if spending from it would be standard.
+fn wsh_descriptor<Pk: MiniscriptKey>(
+    ms: Miniscript<Pk, Segwitv0>,
+) -> Result<Descriptor<Pk>, ScriptCreationError> {
+    // Eg if a key is repeated, or if the Miniscript could not be parsed back from the Script
+    // because its satisfaction exceeds the resource limits.
+    ms.sanity_check().map_err(miniscript::Error::from)?;
+    standardness::check_miniscript(&ms)?;
+    Ok(Descriptor::new_wsh(ms)?)
+}
+
+// The Deposit template, see the module documentation.
+fn deposit_template<Pk: MiniscriptKey>(
+    stakeholders: Vec<Pk>,
+) -> Result<Descriptor<Pk>, ScriptCreationError> {
+    let ms = template_thresh(stakeholders.len(), stakeholders)?;
+    let desc = wsh_descriptor(ms)?;
+
+    // We rely on it to recover the stakeholders from the descriptor.
+    deposit_template_check(&desc)?;
+
+    Ok(desc)
+}
+
+// The Deposit with recovery template, see the module documentation.
+fn deposit_recovery_template<Pk: MiniscriptKey>(
+    stakeholders: Vec<Pk>,
+    recovery_keys: Vec<Pk>,
+    recovery_threshold: usize,
+    recovery_csv: u32,
+) -> Result<Descriptor<Pk>, ScriptCreationError> {
+    let recovery_thresh = template_thresh(recovery_threshold, recovery_keys)?;
+    let v_recovery_thresh = Miniscript::from_ast(Terminal::Verify(Arc::new(recovery_thresh)))?;
+    let csv = Miniscript::from_ast(Terminal::Older(recovery_csv))?;
+    let recovery_branch =
+        Miniscript::from_ast(Terminal::AndV(Arc::new(v_recovery_thresh), Arc::new(csv)))?;
+
+    let stakeholders_branch = template_thresh(stakeholders.len(), stakeholders)?;
+    let ms = Miniscript::from_ast(Terminal::OrD(
+        Arc::new(stakeholders_branch),
+        Arc::new(recovery_branch),
+    ))?;
+    let desc = wsh_descriptor(ms)?;
+
+    // We rely on it to recover the stakeholders and the recovery path from the descriptor.
+    deposit_template_check(&desc)?;
+
+    Ok(desc)
+}
+
+// The Unvault template, see the module documentation.
+fn unvault_template<Pk: MiniscriptKey>(
+    stakeholders: Vec<Pk>,
+    stakeholders_threshold: usize,
+    managers: Vec<Pk>,
+    managers_threshold: usize,
+    mut cosigners: Vec<Pk>,
+    cosigners_threshold: usize,
+    csv_value: u32,
+) -> Result<Descriptor<Pk>, ScriptCreationError> {
+    let mut cosigners_and_csv = Miniscript::from_ast(Terminal::Older(csv_value))?;
+    if cosigners_threshold == cosigners.len() {
+        // Build the and_v() chain from the timelock up to the first cosigner.
+        cosigners.sort_by_cached_key(|k| k.to_string());
+        for cosigner in cosigners.into_iter().rev() {
+            let pk = Miniscript::from_ast(Terminal::Check(Arc::new(Miniscript::from_ast(
+                Terminal::PkK(cosigner),
+            )?)))?;
+            let v_pk = Miniscript::from_ast(Terminal::Verify(Arc::new(pk)))?;
+            cosigners_and_csv =
+                Miniscript::from_ast(Terminal::AndV(Arc::new(v_pk), Arc::new(cosigners_and_csv)))?;
+        }
+    } else {
+        let cosigners_thresh = template_thresh(cosigners_threshold, cosigners)?;
+        let v_cosigners = Miniscript::from_ast(Terminal::Verify(Arc::new(cosigners_thresh)))?;
+        cosigners_and_csv = Miniscript::from_ast(Terminal::AndV(
+            Arc::new(v_cosigners),
+            Arc::new(cosigners_and_csv),
+        ))?;
+    }
+
+    let managers_thresh = template_thresh(managers_threshold, managers)?;
+    let v_managers = Miniscript::from_ast(Terminal::Verify(Arc::new(managers_thresh)))?;
+    let managers_branch = Miniscript::from_ast(Terminal::AndV(
+        Arc::new(v_managers),
+        Arc::new(cosigners_and_csv),
+    ))?;
+
+    let stakeholders_branch = template_thresh(stakeholders_threshold, stakeholders)?;
+    let ms = Miniscript::from_ast(Terminal::OrD(
+        Arc::new(stakeholders_branch),
+        Arc::new(managers_branch),
+    ))?;
+    let desc = wsh_descriptor(ms)?;
+
+    // We rely on it to recover the participants from the descriptor.
+    unvault_participants(&desc)?;
+
+    Ok(desc)
+}
+
+// The CPFP template, see the module documentation.
+fn cpfp_template<Pk: MiniscriptKey>(
+    managers: Vec<Pk>,
+) -> Result<Descriptor<Pk>, ScriptCreationError> {
+    let ms = template_thresh(1, managers)?;
+    wsh_descriptor(ms)
+}
+
+// The Emergency template, see the module documentation.
+fn emergency_template<Pk: MiniscriptKey>(
+    stakeholders: Vec<Pk>,
+    csv_value: u32,
+) -> Result<Descriptor<Pk>, ScriptCreationError> {
+    let stakeholders_thresh = template_thresh(stakeholders.len(), stakeholders)?;
+    let v_stakeholders = Miniscript::from_ast(Terminal::Verify(Arc::new(stakeholders_thresh)))?;
+    let csv = Miniscript::from_ast(Terminal::Older(csv_value))?;
+    let ms = Miniscript::from_ast(Terminal::AndV(Arc::new(v_stakeholders), Arc::new(csv)))?;
+
+    wsh_descriptor(ms)
+}
+
 impl DepositDescriptor {
     /// Get the xpub miniscript descriptor for deposit outputs.
     ///
@@ -250,6 +609,65 @@ impl DepositDescriptor {
         Ok(DepositDescriptor(deposit_desc!(stakeholders)))
     }
 
+    /// Get the xpub miniscript descriptor for deposit outputs, without using the policy
+    /// compiler. The returned descriptor does not depend on the order of the given keys, see the
+    /// [module documentation](self) for the template.
+    ///
+    /// # Errors
+    /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If the given vector contains less than 2 public keys.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_deterministic(
+        stakeholders: Vec<DescriptorPublicKey>,
+    ) -> Result<DepositDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+        check_deriveable(stakeholders.iter())?;
+
+        Ok(DepositDescriptor(deposit_template(stakeholders)?))
+    }
+
+    /// Get the xpub miniscript descriptor for deposit outputs with a recovery path: either all
+    /// the stakeholders may spend, or `recovery_threshold` of their recovery keys after
+    /// `recovery_csv` blocks. This avoids freezing all the vaults if a single stakeholder loses
+    /// their key.
+    ///
+    /// There must be one recovery key per stakeholder, distinct from the stakeholders' keys (a
+    /// Script may not contain the same key twice). It may be another derivation of the same xpub,
+    /// eg `xpub/1/*` for `xpub/*`.
+    ///
+    /// The presigned transactions always spend through the N-of-N, as they don't set the
+    /// deposit input's relative timelock. Like [DepositDescriptor::new_deterministic], this
+    /// doesn't use the policy compiler, see the [module documentation](self) for the template.
+    ///
+    /// # Errors
+    /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If the given vector contains less than 2 public keys.
+    /// - If there is not one recovery key per stakeholder, or if they are not distinct.
+    /// - If the recovery threshold is 0 or larger than the number of recovery keys.
+    /// - If `recovery_csv` is not a valid relative timelock in blocks.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_with_recovery(
+        stakeholders: Vec<DescriptorPublicKey>,
+        recovery_keys: Vec<DescriptorPublicKey>,
+        recovery_threshold: usize,
+        recovery_csv: u32,
+    ) -> Result<DepositDescriptor, ScriptCreationError> {
+        deposit_recovery_desc_checks!(
+            stakeholders,
+            recovery_keys,
+            recovery_threshold,
+            recovery_csv
+        );
+        check_deriveable(stakeholders.iter().chain(recovery_keys.iter()))?;
+
+        Ok(DepositDescriptor(deposit_recovery_template(
+            stakeholders,
+            recovery_keys,
+            recovery_threshold,
+            recovery_csv,
+        )?))
+    }
+
     /// Get the stakeholders xpubs used in this deposit descriptor.
     pub fn xpubs(&self) -> Vec<DescriptorPublicKey> {
         let ms = match self.0 {
@@ -262,14 +680,25 @@ impl DepositDescriptor {
             _ => unreachable!("Deposit descriptor is always a P2WSH"),
         };
 
+        // Leave out the recovery keys, if any.
+        let (stakeholders, _) = deposit_template_check(&self.0).expect("Checked at creation");
+
         // For DescriptorPublicKey, Pk::Hash == Self.
         ms.iter_pk_pkh()
             .map(|pkpkh| match pkpkh {
                 PkPkh::PlainPubkey(xpub) => xpub,
                 PkPkh::HashedPubkey(xpub) => xpub,
             })
+            .filter(|xpub| stakeholders.contains(xpub))
             .collect()
     }
+
+    /// Get the recovery path of this deposit descriptor, if it has one. See
+    /// [DepositDescriptor::new_with_recovery].
+    pub fn recovery(&self) -> Option<DepositRecovery<DescriptorPublicKey>> {
+        let (_, recovery) = deposit_template_check(&self.0).expect("Checked at creation");
+        recovery
+    }
 }
 
 impl Display for DepositDescriptor {
@@ -284,9 +713,9 @@ impl FromStr for DepositDescriptor {
     fn from_str(s: &str) -> Result<DepositDescriptor, Self::Err> {
         let desc: Descriptor<DescriptorPublicKey> = FromStr::from_str(s)?;
 
-        if !desc.for_each_key(|k| k.as_key().is_deriveable()) {
-            return Err(ScriptCreationError::NonWildcardKeys);
-        }
+        check_descriptor_keys(&desc)?;
+        deposit_template_check(&desc)?;
+        standardness::check_miniscript(wsh_miniscript(&desc)?)?;
 
         Ok(DepositDescriptor(desc))
     }
@@ -324,6 +753,50 @@ impl DerivedDepositDescriptor {
 
         Ok(DerivedDepositDescriptor(deposit_desc!(stakeholders)))
     }
+
+    /// Get the derived miniscript descriptor for deposit outputs, without using the policy
+    /// compiler. See [DepositDescriptor::new_deterministic].
+    ///
+    /// # Errors
+    /// - If the given vector contains less than 2 public keys.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_deterministic(
+        stakeholders: Vec<PublicKey>,
+    ) -> Result<DerivedDepositDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+
+        Ok(DerivedDepositDescriptor(deposit_template(stakeholders)?))
+    }
+
+    /// Get the derived miniscript descriptor for deposit outputs with a recovery path. See
+    /// [DepositDescriptor::new_with_recovery].
+    ///
+    /// # Errors
+    /// - If the given vector contains less than 2 public keys.
+    /// - If there is not one recovery key per stakeholder, or if they are not distinct.
+    /// - If the recovery threshold is 0 or larger than the number of recovery keys.
+    /// - If `recovery_csv` is not a valid relative timelock in blocks.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_with_recovery(
+        stakeholders: Vec<PublicKey>,
+        recovery_keys: Vec<PublicKey>,
+        recovery_threshold: usize,
+        recovery_csv: u32,
+    ) -> Result<DerivedDepositDescriptor, ScriptCreationError> {
+        deposit_recovery_desc_checks!(
+            stakeholders,
+            recovery_keys,
+            recovery_threshold,
+            recovery_csv
+        );
+
+        Ok(DerivedDepositDescriptor(deposit_recovery_template(
+            stakeholders,
+            recovery_keys,
+            recovery_threshold,
+            recovery_csv,
+        )?))
+    }
 }
 
 impl Display for DerivedDepositDescriptor {
@@ -337,6 +810,8 @@ impl FromStr for DerivedDepositDescriptor {
 
     fn from_str(s: &str) -> Result<DerivedDepositDescriptor, Self::Err> {
         let desc: Descriptor<PublicKey> = FromStr::from_str(s)?;
+        deposit_template_check(&desc)?;
+        standardness::check_miniscript(wsh_miniscript(&desc)?)?;
 
         Ok(DerivedDepositDescriptor(desc))
     }
@@ -361,6 +836,350 @@ fn unvault_descriptor_csv<Pk: MiniscriptKey>(desc: &Descriptor<Pk>) -> u32 {
     }
 }
 
+// Lift a Miniscript to its abstract policy, *without* normalizing it.
+//
+// We don't use Miniscript's Liftable implementation: it normalizes the policy at each step,
+// which would merge the sets of keys we want to tell apart (eg the managers and the cosigners
+// in the Unvault). It also lifts `andor(X,Y,Z)` as `or(and(X,Z),Y)` instead of `or(and(X,Y),Z)`.
+fn lift_ms<Pk: MiniscriptKey>(ms: &Miniscript<Pk, Segwitv0>) -> SemanticPolicy<Pk> {
+    match ms.node {
+        Terminal::PkK(ref pk) => SemanticPolicy::KeyHash(pk.to_pubkeyhash()),
+        Terminal::PkH(ref pkh) => SemanticPolicy::KeyHash(pkh.clone()),
+        Terminal::After(t) => SemanticPolicy::After(t),
+        Terminal::Older(t) => SemanticPolicy::Older(t),
+        Terminal::Sha256(h) => SemanticPolicy::Sha256(h),
+        Terminal::Hash256(h) => SemanticPolicy::Hash256(h),
+        Terminal::Ripemd160(h) => SemanticPolicy::Ripemd160(h),
+        Terminal::Hash160(h) => SemanticPolicy::Hash160(h),
+        Terminal::True => SemanticPolicy::Trivial,
+        Terminal::False => SemanticPolicy::Unsatisfiable,
+        Terminal::Alt(ref sub)
+        | Terminal::Swap(ref sub)
+        | Terminal::Check(ref sub)
+        | Terminal::DupIf(ref sub)
+        | Terminal::Verify(ref sub)
+        | Terminal::NonZero(ref sub)
+        | Terminal::ZeroNotEqual(ref sub) => lift_ms(sub),
+        Terminal::AndV(ref left, ref right) | Terminal::AndB(ref left, ref right) => {
+            SemanticPolicy::Threshold(2, vec![lift_ms(left), lift_ms(right)])
+        }
+        // and_n(X,Y) is andor(X,Y,0)
+        Terminal::AndOr(ref a, ref b, ref c) if c.node == Terminal::False => {
+            SemanticPolicy::Threshold(2, vec![lift_ms(a), lift_ms(b)])
+        }
+        Terminal::AndOr(ref a, ref b, ref c) => SemanticPolicy::Threshold(
+            1,
+            vec![
+                SemanticPolicy::Threshold(2, vec![lift_ms(a), lift_ms(b)]),
+                lift_ms(c),
+            ],
+        ),
+        Terminal::OrB(ref left, ref right)
+        | Terminal::OrD(ref left, ref right)
+        | Terminal::OrC(ref left, ref right)
+        | Terminal::OrI(ref left, ref right) => {
+            SemanticPolicy::Threshold(1, vec![lift_ms(left), lift_ms(right)])
+        }
+        Terminal::Thresh(k, ref subs) => {
+            SemanticPolicy::Threshold(k, subs.iter().map(|sub| lift_ms(sub)).collect())
+        }
+        Terminal::Multi(k, ref keys) => SemanticPolicy::Threshold(
+            k,
+            keys.iter()
+                .map(|pk| SemanticPolicy::KeyHash(pk.to_pubkeyhash()))
+                .collect(),
+        ),
+    }
+}
+
+// Get the threshold and the keys out of a normalized k-of-n policy between keys.
+fn keys_threshold<H>(
+    policy: SemanticPolicy<impl MiniscriptKey<Hash = H>>,
+) -> Option<(usize, Vec<H>)> {
+    match policy {
+        SemanticPolicy::KeyHash(pkh) => Some((1, vec![pkh])),
+        SemanticPolicy::Threshold(k, subs) => subs
+            .into_iter()
+            .map(|sub| match sub {
+                SemanticPolicy::KeyHash(pkh) => Some(pkh),
+                _ => None,
+            })
+            .collect::<Option<Vec<H>>>()
+            .map(|keys| (k, keys)),
+        _ => None,
+    }
+}
+
+// Get the Miniscript of a P2WSH descriptor
+fn wsh_miniscript<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<&Miniscript<Pk, Segwitv0>, DescriptorTemplateError> {
+    match desc {
+        Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
+            WshInner::Ms(ms) => Ok(ms),
+            WshInner::SortedMulti(_) => Err(DescriptorTemplateError::NotP2wsh),
+        },
+        _ => Err(DescriptorTemplateError::NotP2wsh),
+    }
+}
+
+/// The timelocked recovery path of a Deposit descriptor, see
+/// [DepositDescriptor::new_with_recovery].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositRecovery<Pk> {
+    /// The recovery keys, one per stakeholder
+    pub keys: Vec<Pk>,
+    /// The number of recovery keys needed to spend
+    pub threshold: usize,
+    /// The relative timelock in blocks after which the recovery keys may spend
+    pub csv_value: u32,
+}
+
+// The stakeholders of a Deposit descriptor, along with its recovery path if any.
+type DepositParticipants<H> = (Vec<H>, Option<DepositRecovery<H>>);
+
+// Check a Deposit descriptor's policy is a N-of-N between at least 2 keys, optionally along
+// with a timelocked k-of-N between N other keys. Returns the keys of the N-of-N, and the recovery
+// path if any.
+fn deposit_template_check<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<DepositParticipants<Pk::Hash>, DescriptorTemplateError> {
+    let ms = wsh_miniscript(desc)?;
+    let policy = lift_ms(ms);
+    if policy.relative_timelocks().is_empty() {
+        return match keys_threshold(policy.normalized()) {
+            Some((k, keys)) if k == keys.len() && keys.len() >= 2 => Ok((keys, None)),
+            _ => Err(DescriptorTemplateError::DepositPolicy),
+        };
+    }
+    if ms.has_repeated_keys() {
+        return Err(DescriptorTemplateError::DepositPolicy);
+    }
+
+    let mut branches = match policy {
+        SemanticPolicy::Threshold(1, subs) if subs.len() == 2 => subs,
+        _ => return Err(DescriptorTemplateError::DepositPolicy),
+    };
+    let recovery_index = branches
+        .iter()
+        .position(|b| !b.relative_timelocks().is_empty())
+        .expect("The policy is timelocked");
+    let mut recovery_subs = match branches.remove(recovery_index) {
+        SemanticPolicy::Threshold(2, subs) if subs.len() == 2 => subs,
+        _ => return Err(DescriptorTemplateError::DepositPolicy),
+    };
+    let stakeholders_branch = branches.pop().expect("There were two branches");
+
+    let (stk_threshold, stakeholders) = keys_threshold(stakeholders_branch.normalized())
+        .ok_or(DescriptorTemplateError::DepositPolicy)?;
+    if stk_threshold != stakeholders.len() || stakeholders.len() < 2 {
+        return Err(DescriptorTemplateError::DepositPolicy);
+    }
+
+    let recovery_csv = match recovery_subs.pop() {
+        Some(SemanticPolicy::Older(csv)) => csv,
+        _ => return Err(DescriptorTemplateError::DepositPolicy),
+    };
+    if !is_valid_csv(recovery_csv) {
+        return Err(DescriptorTemplateError::DepositTimelock(recovery_csv));
+    }
+    let (recovery_threshold, recovery_keys) = recovery_subs
+        .pop()
+        .and_then(|sub| keys_threshold(sub.normalized()))
+        .ok_or(DescriptorTemplateError::DepositPolicy)?;
+    if recovery_keys.len() != stakeholders.len() {
+        return Err(DescriptorTemplateError::DepositPolicy);
+    }
+
+    Ok((
+        stakeholders,
+        Some(DepositRecovery {
+            keys: recovery_keys,
+            threshold: recovery_threshold,
+            csv_value: recovery_csv,
+        }),
+    ))
+}
+
+// Check a CPFP descriptor's policy is a 1-of-N between keys.
+fn cpfp_template_check<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<(), DescriptorTemplateError> {
+    match keys_threshold(lift_ms(wsh_miniscript(desc)?).normalized()) {
+        Some((1, keys)) if !keys.is_empty() => Ok(()),
+        _ => Err(DescriptorTemplateError::CpfpPolicy),
+    }
+}
+
+// Check an Emergency descriptor's policy is a N-of-N between at least 2 keys along with a
+// relative timelock. Returns the timelock.
+fn emergency_template_check<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<u32, DescriptorTemplateError> {
+    let ms = wsh_miniscript(desc)?;
+    if ms.has_repeated_keys() {
+        return Err(DescriptorTemplateError::EmergencyPolicy);
+    }
+
+    let mut subs = match lift_ms(ms) {
+        SemanticPolicy::Threshold(2, subs) if subs.len() == 2 => subs,
+        _ => return Err(DescriptorTemplateError::EmergencyPolicy),
+    };
+    let csv_index = subs
+        .iter()
+        .position(|sub| matches!(sub, SemanticPolicy::Older(_)))
+        .ok_or(DescriptorTemplateError::EmergencyPolicy)?;
+    let csv_value = match subs.remove(csv_index) {
+        SemanticPolicy::Older(csv) => csv,
+        _ => unreachable!("Just checked it's a timelock"),
+    };
+    if !is_valid_csv(csv_value) {
+        return Err(DescriptorTemplateError::EmergencyTimelock(csv_value));
+    }
+
+    match subs.pop().and_then(|sub| keys_threshold(sub.normalized())) {
+        Some((k, keys)) if k == keys.len() && keys.len() >= 2 => Ok(csv_value),
+        _ => Err(DescriptorTemplateError::EmergencyPolicy),
+    }
+}
+
+// Get the relative timelock of an Emergency Deep Vault witness Script, if it follows the template.
+pub(crate) fn emergency_script_csv(witness_script: &Script) -> Option<u32> {
+    let ms = Miniscript::<PublicKey, Segwitv0>::parse(witness_script).ok()?;
+    let desc = Descriptor::new_wsh(ms).ok()?;
+    emergency_template_check(&desc).ok()
+}
+
+// Whether an input with this nSequence may spend an output locked by this relative timelock in
+// blocks (BIP68).
+pub(crate) fn is_matured_sequence(sequence: u32, csv_value: u32) -> bool {
+    (sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG) == 0
+        && (sequence & SEQUENCE_LOCKTIME_TYPE_FLAG) == 0
+        && (sequence & SEQUENCE_LOCKTIME_MASK) >= csv_value
+}
+
+// The participants of an Unvault descriptor, as recovered from its policy. For
+// DescriptorPublicKey the "key hash" is the key itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UnvaultParticipants<H> {
+    stakeholders: Vec<H>,
+    stakeholders_threshold: usize,
+    managers: Vec<H>,
+    managers_threshold: usize,
+    cosigners: Vec<H>,
+    cosigners_threshold: usize,
+    csv_value: u32,
+}
+
+// Recover the participants of an Unvault descriptor. The policy must be of the form
+// `or(thresh(k, stakeholders), and(thresh(k, managers), and(thresh(k, cosigners), older(csv))))`,
+// or `or(thresh(k, stakeholders), and(thresh(k, managers), older(csv)))` without cosigners, and
+// the Miniscript must follow its structure.
+fn unvault_participants<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+) -> Result<UnvaultParticipants<Pk::Hash>, DescriptorTemplateError> {
+    let ms = wsh_miniscript(desc)?;
+
+    let mut branches = match lift_ms(ms) {
+        SemanticPolicy::Threshold(1, subs) if subs.len() == 2 => subs,
+        _ => return Err(DescriptorTemplateError::UnvaultBranches),
+    };
+    // The managers' branch is the timelocked one.
+    let managers_branch_index = branches
+        .iter()
+        .position(|b| !b.relative_timelocks().is_empty())
+        .ok_or(DescriptorTemplateError::UnvaultBranches)?;
+    let managers_branch = branches.remove(managers_branch_index);
+    let stakeholders_branch = branches.pop().expect("There were two branches");
+    if !stakeholders_branch.relative_timelocks().is_empty() {
+        return Err(DescriptorTemplateError::UnvaultBranches);
+    }
+
+    let (stakeholders_threshold, stakeholders) =
+        keys_threshold(stakeholders_branch.normalized())
+            .ok_or(DescriptorTemplateError::StakeholdersBranch)?;
+
+    let mut managers_subs = match managers_branch {
+        SemanticPolicy::Threshold(2, subs) if subs.len() == 2 => subs,
+        _ => return Err(DescriptorTemplateError::ManagersBranch),
+    };
+    let cosigners_index = managers_subs
+        .iter()
+        .position(|s| !s.relative_timelocks().is_empty())
+        .expect("The managers branch is timelocked");
+    let cosigners_sub = managers_subs.remove(cosigners_index).normalized();
+    let managers_sub = managers_subs.pop().expect("There were two subs");
+    if !managers_sub.relative_timelocks().is_empty() {
+        return Err(DescriptorTemplateError::ManagersBranch);
+    }
+
+    let (managers_threshold, managers) =
+        keys_threshold(managers_sub.normalized()).ok_or(DescriptorTemplateError::ManagersBranch)?;
+
+    // If all the cosigners are required, their keys are merged with the timelock once
+    // normalized. There may also be no cosigner at all.
+    let (cosigners_and_csv_thresh, mut cosigners_and_csv) = match cosigners_sub {
+        SemanticPolicy::Threshold(k, subs) => (k, subs),
+        older @ SemanticPolicy::Older(_) => (1, vec![older]),
+        _ => return Err(DescriptorTemplateError::ManagersBranch),
+    };
+    if cosigners_and_csv_thresh != cosigners_and_csv.len() {
+        return Err(DescriptorTemplateError::ManagersBranch);
+    }
+    let csv_index = cosigners_and_csv
+        .iter()
+        .position(|sub| matches!(sub, SemanticPolicy::Older(_)))
+        .ok_or(DescriptorTemplateError::ManagersBranch)?;
+    let csv_value = match cosigners_and_csv.remove(csv_index) {
+        SemanticPolicy::Older(csv) => csv,
+        _ => unreachable!("Just matched"),
+    };
+    if !is_valid_csv(csv_value) {
+        return Err(DescriptorTemplateError::UnvaultTimelock(csv_value));
+    }
+    let (cosigners_threshold, cosigners) = match cosigners_and_csv.len() {
+        0 => Some((0, vec![])),
+        1 => keys_threshold(cosigners_and_csv.pop().expect("Length is 1")),
+        _ => keys_threshold(SemanticPolicy::Threshold(
+            cosigners_and_csv.len(),
+            cosigners_and_csv,
+        )),
+    }
+    .ok_or(DescriptorTemplateError::ManagersBranch)?;
+
+    Ok(UnvaultParticipants {
+        stakeholders,
+        stakeholders_threshold,
+        managers,
+        managers_threshold,
+        cosigners,
+        cosigners_threshold,
+        csv_value,
+    })
+}
+
+// Check an Unvault descriptor follows the template with these thresholds. Unless specified, all
+// the stakeholders and all the cosigners must be required: a weaker threshold must be opted in.
+fn unvault_thresholds_check<Pk: MiniscriptKey>(
+    desc: &Descriptor<Pk>,
+    stakeholders_threshold: Option<usize>,
+    cosigners_threshold: Option<usize>,
+) -> Result<(), DescriptorTemplateError> {
+    let participants = unvault_participants(desc)?;
+
+    let stakeholders_threshold = stakeholders_threshold.unwrap_or(participants.stakeholders.len());
+    if participants.stakeholders_threshold != stakeholders_threshold {
+        return Err(DescriptorTemplateError::StakeholdersBranch);
+    }
+
+    let cosigners_threshold = cosigners_threshold.unwrap_or(participants.cosigners.len());
+    if participants.cosigners_threshold != cosigners_threshold {
+        return Err(DescriptorTemplateError::ManagersBranch);
+    }
+
+    Ok(())
+}
+
 impl UnvaultDescriptor {
     /// Get the miniscript descriptors for Unvault outputs.
     ///
@@ -404,10 +1223,11 @@ impl UnvaultDescriptor {
     ///
     /// # Errors
     /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
-    /// - If any of the slice contains no public key, or if the number of non_managers public keys is
-    /// not the same as the number of cosigners public key.
-    /// - If the policy compilation to miniscript failed, which should not happen (tm) and would be a
-    /// bug.
+    /// - If the stakeholders or managers slice contains no public key, or if there are cosigners
+    /// public keys but not as many as non_managers public keys.
+    /// - If the managers threshold is 0 or larger than the number of managers.
+    /// - If the policy compilation to miniscript failed, or if the compiled Miniscript does not
+    /// follow the Unvault template, which should not happen (tm) and would be a bug.
     pub fn new(
         stakeholders: Vec<DescriptorPublicKey>,
         managers: Vec<DescriptorPublicKey>,
@@ -438,29 +1258,205 @@ impl UnvaultDescriptor {
         )))
     }
 
-    /// Get the relative locktime in blocks contained in the Unvault descriptor
-    pub fn csv_value(&self) -> u32 {
-        unvault_descriptor_csv(&self.0)
-    }
-
-    /// Get all the xpubs used in this Unvault descriptor.
-    pub fn xpubs(&self) -> Vec<DescriptorPublicKey> {
-        let ms = match self.0 {
-            Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
-                WshInner::Ms(ms) => ms,
-                WshInner::SortedMulti(_) => {
-                    unreachable!("Unvault descriptor is not a sorted multi")
-                }
-            },
-            _ => unreachable!("Unvault descriptor is always a P2WSH"),
-        };
-
-        // For DescriptorPublicKey, Pk::Hash == Self.
-        ms.iter_pk_pkh()
-            .map(|pkpkh| match pkpkh {
-                PkPkh::PlainPubkey(xpub) => xpub,
-                PkPkh::HashedPubkey(xpub) => xpub,
-            })
+    /// Get the miniscript descriptors for Unvault outputs, with only `stakeholders_threshold`
+    /// of the stakeholders required to spend through the non-timelocked branch (ie to sign the
+    /// revocation transactions), and `cosigners_threshold` of the cosigners (all of them if
+    /// `None`) required along with the managers.
+    ///
+    /// This does not use the policy compiler, as it may use `pkh()` fragments for a k-of-N
+    /// which could not be dissatisfied (hence finalized) from a PSBT. See the
+    /// [module documentation](self) for the template.
+    ///
+    /// Unless all the stakeholders and all the cosigners are required, the returned descriptor
+    /// must be parsed back with [UnvaultDescriptor::from_str_with_thresholds].
+    ///
+    /// # Errors
+    /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If the stakeholders or managers slice contains no public key, or if there are cosigners
+    ///   public keys but not as many as non_managers public keys.
+    /// - If any of the thresholds is 0 or larger than the corresponding number of keys.
+    /// - If `csv_value` is not a valid relative timelock in blocks.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_with_thresholds(
+        stakeholders: Vec<DescriptorPublicKey>,
+        stakeholders_threshold: usize,
+        managers: Vec<DescriptorPublicKey>,
+        managers_threshold: usize,
+        cosigners: Vec<DescriptorPublicKey>,
+        cosigners_threshold: Option<usize>,
+        csv_value: u32,
+    ) -> Result<UnvaultDescriptor, ScriptCreationError> {
+        let cosigners_threshold = cosigners_threshold.unwrap_or(cosigners.len());
+        unvault_desc_checks!(
+            stakeholders,
+            managers,
+            managers_threshold,
+            cosigners,
+            csv_value
+        );
+        unvault_thresholds_checks!(
+            stakeholders,
+            stakeholders_threshold,
+            cosigners,
+            cosigners_threshold
+        );
+
+        // Stakeholders' and managers' must be deriveable xpubs.
+        check_deriveable(stakeholders.iter().chain(managers.iter()))?;
+
+        // Cosigners' key may not be. We use DescriptorSinglePub for them downstream with static raw
+        // keys, but it's not hardcoded into the type system there to allow a more generic usage.
+
+        Ok(UnvaultDescriptor(unvault_template(
+            stakeholders,
+            stakeholders_threshold,
+            managers,
+            managers_threshold,
+            cosigners,
+            cosigners_threshold,
+            csv_value,
+        )?))
+    }
+
+    /// Get the miniscript descriptors for Unvault outputs, without using the policy compiler.
+    /// The returned descriptor does not depend on the order of the given keys, see the
+    /// [module documentation](self) for the template.
+    ///
+    /// # Errors
+    /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If the stakeholders or managers vector is empty, or if there are cosigners but not as
+    ///   many as stakeholders.
+    /// - If the managers threshold is 0 or larger than the number of managers.
+    /// - If `csv_value` is not a valid relative timelock in blocks.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_deterministic(
+        stakeholders: Vec<DescriptorPublicKey>,
+        managers: Vec<DescriptorPublicKey>,
+        managers_threshold: usize,
+        cosigners: Vec<DescriptorPublicKey>,
+        csv_value: u32,
+    ) -> Result<UnvaultDescriptor, ScriptCreationError> {
+        unvault_desc_checks!(
+            stakeholders,
+            managers,
+            managers_threshold,
+            cosigners,
+            csv_value
+        );
+        check_deriveable(stakeholders.iter().chain(managers.iter()))?;
+
+        let (stakeholders_threshold, cosigners_threshold) = (stakeholders.len(), cosigners.len());
+        Ok(UnvaultDescriptor(unvault_template(
+            stakeholders,
+            stakeholders_threshold,
+            managers,
+            managers_threshold,
+            cosigners,
+            cosigners_threshold,
+            csv_value,
+        )?))
+    }
+
+    /// Parse an Unvault descriptor with custom stakeholders and cosigners thresholds, as created
+    /// by [UnvaultDescriptor::new_with_thresholds]. Parsing it as a string only succeeds if all
+    /// the stakeholders and all the cosigners are required.
+    ///
+    /// # Errors
+    /// - If the descriptor does not follow the Unvault template.
+    /// - If `stakeholders_threshold` of the stakeholders, and `cosigners_threshold` of the
+    ///   cosigners (all of them if `None`), are not exactly the ones required by the descriptor.
+    pub fn from_str_with_thresholds(
+        s: &str,
+        stakeholders_threshold: usize,
+        cosigners_threshold: Option<usize>,
+    ) -> Result<UnvaultDescriptor, ScriptCreationError> {
+        UnvaultDescriptor::parse(s, Some(stakeholders_threshold), cosigners_threshold)
+    }
+
+    // Parse an Unvault descriptor requiring these thresholds, all the keys if `None`.
+    fn parse(
+        s: &str,
+        stakeholders_threshold: Option<usize>,
+        cosigners_threshold: Option<usize>,
+    ) -> Result<UnvaultDescriptor, ScriptCreationError> {
+        let desc: Descriptor<DescriptorPublicKey> = FromStr::from_str(s)?;
+
+        let mut keys_check = Ok(());
+        desc.for_each_key(|k| match k.as_key() {
+            DescriptorPublicKey::SinglePub(_) => true, // For cosigning servers keys
+            xpub => {
+                keys_check = check_deriveable(std::iter::once(xpub));
+                keys_check.is_ok()
+            }
+        });
+        keys_check?;
+        unvault_thresholds_check(&desc, stakeholders_threshold, cosigners_threshold)?;
+        standardness::check_miniscript(wsh_miniscript(&desc)?)?;
+
+        Ok(UnvaultDescriptor(desc))
+    }
+
+    /// Get the relative locktime in blocks contained in the Unvault descriptor
+    pub fn csv_value(&self) -> u32 {
+        unvault_descriptor_csv(&self.0)
+    }
+
+    // The Unvault descriptor is always checked to follow the template at creation, be it
+    // compiled, created from the template or parsed.
+    fn participants(&self) -> UnvaultParticipants<DescriptorPublicKey> {
+        unvault_participants(&self.0).expect("Checked at creation")
+    }
+
+    /// Get the stakeholders xpubs used in this Unvault descriptor, from the branch of the policy
+    /// that is not timelocked.
+    pub fn stakeholders(&self) -> Vec<DescriptorPublicKey> {
+        self.participants().stakeholders
+    }
+
+    /// Get the number of stakeholders required to spend through the non-timelocked branch.
+    pub fn stakeholders_threshold(&self) -> usize {
+        self.participants().stakeholders_threshold
+    }
+
+    /// Get the managers xpubs used in this Unvault descriptor.
+    pub fn managers(&self) -> Vec<DescriptorPublicKey> {
+        self.participants().managers
+    }
+
+    /// Get the number of managers required to sign along with the cosigners.
+    pub fn managers_threshold(&self) -> usize {
+        self.participants().managers_threshold
+    }
+
+    /// Get the cosigning servers keys used in this Unvault descriptor. Empty if the managers
+    /// don't need cosigning servers.
+    pub fn cosigners(&self) -> Vec<DescriptorPublicKey> {
+        self.participants().cosigners
+    }
+
+    /// Get the number of cosigners required to sign along with the managers.
+    pub fn cosigners_threshold(&self) -> usize {
+        self.participants().cosigners_threshold
+    }
+
+    /// Get all the xpubs used in this Unvault descriptor.
+    pub fn xpubs(&self) -> Vec<DescriptorPublicKey> {
+        let ms = match self.0 {
+            Descriptor::Wsh(ref wsh) => match wsh.as_inner() {
+                WshInner::Ms(ms) => ms,
+                WshInner::SortedMulti(_) => {
+                    unreachable!("Unvault descriptor is not a sorted multi")
+                }
+            },
+            _ => unreachable!("Unvault descriptor is always a P2WSH"),
+        };
+
+        // For DescriptorPublicKey, Pk::Hash == Self.
+        ms.iter_pk_pkh()
+            .map(|pkpkh| match pkpkh {
+                PkPkh::PlainPubkey(xpub) => xpub,
+                PkPkh::HashedPubkey(xpub) => xpub,
+            })
             .collect()
     }
 }
@@ -475,16 +1471,7 @@ impl FromStr for UnvaultDescriptor {
     type Err = ScriptCreationError;
 
     fn from_str(s: &str) -> Result<UnvaultDescriptor, Self::Err> {
-        let desc: Descriptor<DescriptorPublicKey> = FromStr::from_str(s)?;
-
-        if !desc.for_each_key(|k| match k.as_key() {
-            DescriptorPublicKey::SinglePub(_) => true, // For cosigning servers keys
-            DescriptorPublicKey::XPub(xpub) => xpub.wildcard != Wildcard::None,
-        }) {
-            return Err(ScriptCreationError::NonWildcardKeys);
-        }
-
-        Ok(UnvaultDescriptor(desc))
+        UnvaultDescriptor::parse(s, None, None)
     }
 }
 
@@ -527,10 +1514,11 @@ impl DerivedUnvaultDescriptor {
     /// ```
     ///
     /// # Errors
-    /// - If any of the given vectors contains no public key, or if the number of stakeholders public keys
-    /// is not the same as the number of cosigners public keys.
-    /// - If the policy compilation to miniscript failed, which should not happen (tm) and would be a
-    /// bug.
+    /// - If the stakeholders or managers vector contains no public key, or if there are cosigners
+    /// public keys but not as many as stakeholders public keys.
+    /// - If the managers threshold is 0 or larger than the number of managers.
+    /// - If the policy compilation to miniscript failed, or if the compiled Miniscript does not
+    /// follow the Unvault template, which should not happen (tm) and would be a bug.
     pub fn new(
         stakeholders: Vec<PublicKey>,
         managers: Vec<PublicKey>,
@@ -555,6 +1543,114 @@ impl DerivedUnvaultDescriptor {
         )))
     }
 
+    /// Get the derived miniscript descriptors for Unvault outputs, with custom stakeholders and
+    /// cosigners thresholds. See [UnvaultDescriptor::new_with_thresholds].
+    ///
+    /// # Errors
+    /// - If the stakeholders or managers vector contains no public key, or if there are cosigners
+    ///   public keys but not as many as stakeholders public keys.
+    /// - If any of the thresholds is 0 or larger than the corresponding number of keys.
+    /// - If `csv_value` is not a valid relative timelock in blocks.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_with_thresholds(
+        stakeholders: Vec<PublicKey>,
+        stakeholders_threshold: usize,
+        managers: Vec<PublicKey>,
+        managers_threshold: usize,
+        cosigners: Vec<PublicKey>,
+        cosigners_threshold: Option<usize>,
+        csv_value: u32,
+    ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
+        let cosigners_threshold = cosigners_threshold.unwrap_or(cosigners.len());
+        unvault_desc_checks!(
+            stakeholders,
+            managers,
+            managers_threshold,
+            cosigners,
+            csv_value
+        );
+        unvault_thresholds_checks!(
+            stakeholders,
+            stakeholders_threshold,
+            cosigners,
+            cosigners_threshold
+        );
+
+        Ok(DerivedUnvaultDescriptor(unvault_template(
+            stakeholders,
+            stakeholders_threshold,
+            managers,
+            managers_threshold,
+            cosigners,
+            cosigners_threshold,
+            csv_value,
+        )?))
+    }
+
+    /// Get the derived miniscript descriptors for Unvault outputs, without using the policy
+    /// compiler. See [UnvaultDescriptor::new_deterministic].
+    ///
+    /// # Errors
+    /// - If the stakeholders or managers vector is empty, or if there are cosigners but not as
+    ///   many as stakeholders.
+    /// - If the managers threshold is 0 or larger than the number of managers.
+    /// - If `csv_value` is not a valid relative timelock in blocks.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_deterministic(
+        stakeholders: Vec<PublicKey>,
+        managers: Vec<PublicKey>,
+        managers_threshold: usize,
+        cosigners: Vec<PublicKey>,
+        csv_value: u32,
+    ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
+        unvault_desc_checks!(
+            stakeholders,
+            managers,
+            managers_threshold,
+            cosigners,
+            csv_value
+        );
+
+        let (stakeholders_threshold, cosigners_threshold) = (stakeholders.len(), cosigners.len());
+        Ok(DerivedUnvaultDescriptor(unvault_template(
+            stakeholders,
+            stakeholders_threshold,
+            managers,
+            managers_threshold,
+            cosigners,
+            cosigners_threshold,
+            csv_value,
+        )?))
+    }
+
+    /// Parse a derived Unvault descriptor with custom stakeholders and cosigners thresholds. See
+    /// [UnvaultDescriptor::from_str_with_thresholds].
+    ///
+    /// # Errors
+    /// - If the descriptor does not follow the Unvault template.
+    /// - If `stakeholders_threshold` of the stakeholders, and `cosigners_threshold` of the
+    ///   cosigners (all of them if `None`), are not exactly the ones required by the descriptor.
+    pub fn from_str_with_thresholds(
+        s: &str,
+        stakeholders_threshold: usize,
+        cosigners_threshold: Option<usize>,
+    ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
+        DerivedUnvaultDescriptor::parse(s, Some(stakeholders_threshold), cosigners_threshold)
+    }
+
+    // Parse an Unvault descriptor requiring these thresholds, all the keys if `None`.
+    fn parse(
+        s: &str,
+        stakeholders_threshold: Option<usize>,
+        cosigners_threshold: Option<usize>,
+    ) -> Result<DerivedUnvaultDescriptor, ScriptCreationError> {
+        let desc: Descriptor<PublicKey> = FromStr::from_str(s)?;
+        unvault_thresholds_check(&desc, stakeholders_threshold, cosigners_threshold)?;
+        standardness::check_miniscript(wsh_miniscript(&desc)?)?;
+
+        Ok(DerivedUnvaultDescriptor(desc))
+    }
+
     /// Get the relative locktime in blocks contained in the Unvault descriptor
     pub fn csv_value(&self) -> u32 {
         unvault_descriptor_csv(&self.0)
@@ -571,9 +1667,7 @@ impl FromStr for DerivedUnvaultDescriptor {
     type Err = ScriptCreationError;
 
     fn from_str(s: &str) -> Result<DerivedUnvaultDescriptor, Self::Err> {
-        let desc: Descriptor<PublicKey> = FromStr::from_str(s)?;
-
-        Ok(DerivedUnvaultDescriptor(desc))
+        DerivedUnvaultDescriptor::parse(s, None, None)
     }
 }
 
@@ -588,7 +1682,7 @@ macro_rules! cpfp_descriptor {
 
         // This handles the non-safe or malleable cases.
         let ms = policy.compile::<Segwitv0>()?;
-        Descriptor::new_wsh(ms)?
+        wsh_descriptor(ms)?
     }};
 }
 
@@ -626,6 +1720,22 @@ impl CpfpDescriptor {
         Ok(CpfpDescriptor(cpfp_descriptor!(managers)))
     }
 
+    /// Get the xpub miniscript descriptor for the Unvault transaction CPFP output, without using
+    /// the policy compiler. The returned descriptor does not depend on the order of the given
+    /// keys, see the [module documentation](self) for the template.
+    ///
+    /// # Errors
+    /// - If the given `DescriptorPublickKey`s are not wildcards (can be derived from).
+    /// - If the given vector is empty.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_deterministic(
+        managers: Vec<DescriptorPublicKey>,
+    ) -> Result<CpfpDescriptor, ScriptCreationError> {
+        check_deriveable(managers.iter())?;
+
+        Ok(CpfpDescriptor(cpfp_template(managers)?))
+    }
+
     /// Get all the xpubs used in this Cpfp descriptor.
     pub fn xpubs(&self) -> Vec<DescriptorPublicKey> {
         let ms = match self.0 {
@@ -660,9 +1770,9 @@ impl FromStr for CpfpDescriptor {
     fn from_str(s: &str) -> Result<CpfpDescriptor, Self::Err> {
         let desc: Descriptor<DescriptorPublicKey> = FromStr::from_str(s)?;
 
-        if !desc.for_each_key(|k| k.as_key().is_deriveable()) {
-            return Err(ScriptCreationError::NonWildcardKeys);
-        }
+        check_descriptor_keys(&desc)?;
+        cpfp_template_check(&desc)?;
+        standardness::check_miniscript(wsh_miniscript(&desc)?)?;
 
         Ok(CpfpDescriptor(desc))
     }
@@ -695,6 +1805,18 @@ impl DerivedCpfpDescriptor {
     pub fn new(managers: Vec<PublicKey>) -> Result<DerivedCpfpDescriptor, ScriptCreationError> {
         Ok(DerivedCpfpDescriptor(cpfp_descriptor!(managers)))
     }
+
+    /// Get the derived miniscript descriptor for the Unvault transaction CPFP output, without
+    /// using the policy compiler. See [CpfpDescriptor::new_deterministic].
+    ///
+    /// # Errors
+    /// - If the given vector is empty.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new_deterministic(
+        managers: Vec<PublicKey>,
+    ) -> Result<DerivedCpfpDescriptor, ScriptCreationError> {
+        Ok(DerivedCpfpDescriptor(cpfp_template(managers)?))
+    }
 }
 
 impl Display for DerivedCpfpDescriptor {
@@ -708,18 +1830,35 @@ impl FromStr for DerivedCpfpDescriptor {
 
     fn from_str(s: &str) -> Result<DerivedCpfpDescriptor, Self::Err> {
         let desc: Descriptor<PublicKey> = FromStr::from_str(s)?;
+        cpfp_template_check(&desc)?;
+        standardness::check_miniscript(wsh_miniscript(&desc)?)?;
 
         Ok(DerivedCpfpDescriptor(desc))
     }
 }
 
+// Whether this address was encoded for this network. Signet addresses are parsed as testnet
+// ones as they share the same encoding.
+fn is_address_for_network(address: &Address, network: Network) -> bool {
+    address.network == network
+        || (address.network == Network::Testnet && network == Network::Signet)
+}
+
 /// The "Emergency address", it's kept obfuscated for the entire duration of the vault and is
 /// necessarily a v0 P2WSH
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EmergencyAddress(Address);
 impl EmergencyAddress {
     /// Create a new Emergency Address. Will error if the address isn't a v0 P2WSH
+    #[deprecated(
+        since = "0.2.1",
+        note = "This does not check the network of the address, use from_network instead"
+    )]
     pub fn from(address: Address) -> Result<EmergencyAddress, ScriptCreationError> {
+        EmergencyAddress::from_p2wsh(address)
+    }
+
+    fn from_p2wsh(address: Address) -> Result<EmergencyAddress, ScriptCreationError> {
         if address.script_pubkey().is_v0_p2wsh() {
             Ok(EmergencyAddress(address))
         } else {
@@ -727,6 +1866,24 @@ impl EmergencyAddress {
         }
     }
 
+    /// Create a new Emergency Address for this `network`. Will error if the address isn't a v0
+    /// P2WSH, or if it isn't for this network.
+    pub fn from_network(
+        address: Address,
+        network: Network,
+    ) -> Result<EmergencyAddress, ScriptCreationError> {
+        if !is_address_for_network(&address, network) {
+            return Err(ScriptCreationError::NetworkMismatch(network));
+        }
+
+        EmergencyAddress::from_p2wsh(address)
+    }
+
+    /// Get the network this address was encoded for
+    pub fn network(&self) -> Network {
+        self.0.network
+    }
+
     /// Get the address
     pub fn address(&self) -> &Address {
         &self.0
@@ -754,43 +1911,301 @@ impl<'de> de::Deserialize<'de> for EmergencyAddress {
         // rust-bitcoin.
         let addr_str = String::deserialize(deserializer)?;
         let addr = Address::from_str(&addr_str).map_err(|e| de::Error::custom(e))?;
-        EmergencyAddress::from(addr).map_err(de::Error::custom)
+        EmergencyAddress::from_p2wsh(addr).map_err(de::Error::custom)
     }
 }
 
-#[cfg(test)]
-mod tests {
+/// Deserialize an [EmergencyAddress] for the given network through
+/// [serde::de::DeserializeSeed]. Will error if the address isn't a v0 P2WSH, or if it isn't for
+/// this network.
+#[cfg(feature = "use-serde")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmergencyAddressSeed(pub Network);
 
-    use super::{
-        CpfpDescriptor, DepositDescriptor, DerivedCpfpDescriptor, DerivedDepositDescriptor,
-        DerivedUnvaultDescriptor, PublicKey, ScriptCreationError, UnvaultDescriptor,
-    };
+#[cfg(feature = "use-serde")]
+impl<'de> de::DeserializeSeed<'de> for EmergencyAddressSeed {
+    type Value = EmergencyAddress;
 
-    use miniscript::{
-        bitcoin::{secp256k1, util::bip32, Network},
-        descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
-        policy::compiler::CompilerError,
-    };
-    use std::{iter::repeat_with, str::FromStr};
+    fn deserialize<D>(self, deserializer: D) -> Result<EmergencyAddress, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        // FIXME: the windows CI build is preventing us from using the 'use-serde' feature of
+        // rust-bitcoin.
+        let addr_str = <String as de::Deserialize>::deserialize(deserializer)?;
+        let addr = Address::from_str(&addr_str).map_err(|e| de::Error::custom(e))?;
+        EmergencyAddress::from_network(addr, self.0).map_err(de::Error::custom)
+    }
+}
 
-    fn rand_xpub<C: secp256k1::Signing>(
-        rng: &mut fastrand::Rng,
-        secp: &secp256k1::Secp256k1<C>,
-    ) -> bip32::ExtendedPrivKey {
-        let rand_bytes: Vec<u8> = repeat_with(|| rng.u8(..)).take(64).collect();
+/// The descriptor of the Emergency "Deep Vault", the Script the [EmergencyAddress] pays to.
+///
+/// It is optional: the Emergency Script may be unknown and is not derived. If used, the funds
+/// sent to the Emergency address can be swept with a
+/// [DeepVaultRecoveryTransaction](crate::transactions::DeepVaultRecoveryTransaction) once the
+/// timelock matured.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EmergencyDescriptor(Descriptor<PublicKey>);
+
+impl EmergencyDescriptor {
+    /// Get the miniscript descriptor of the Emergency Deep Vault, a N-of-N between the
+    /// stakeholders along with a (long) relative timelock. See the [module documentation](self)
+    /// for the template.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use revault_tx::{scripts, miniscript::bitcoin::{Network, PublicKey}};
+    /// use std::str::FromStr;
+    ///
+    /// let first_stakeholder = PublicKey::from_str("02a17786aca5ea2118e9209702454ab432d5b2c656f8ae19447d4ff3e7317d3b41").unwrap();
+    /// let second_stakeholder = PublicKey::from_str("036edaec85bb1eee1a19ca9f9fd5620134ec98bc21cc14c4e8e3d0f8f121e1b6d1").unwrap();
+    ///
+    /// let emergency_descriptor =
+    ///     scripts::EmergencyDescriptor::new(vec![first_stakeholder, second_stakeholder], 52_560)
+    ///         .expect("Creating descriptor");
+    /// println!("Emergency address: {}", emergency_descriptor.emergency_address(Network::Bitcoin));
+    ///
+    /// let desc_str = emergency_descriptor.to_string();
+    /// assert_eq!(emergency_descriptor, scripts::EmergencyDescriptor::from_str(&desc_str).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    /// - If there are less than 2 keys, or if a key is repeated.
+    /// - If the timelock is disabled, not in blocks, or not minimal.
+    /// - If the resulting Script exceeds the resource limits.
+    pub fn new(
+        stakeholders: Vec<PublicKey>,
+        csv_value: u32,
+    ) -> Result<EmergencyDescriptor, ScriptCreationError> {
+        deposit_desc_checks!(stakeholders);
+        if !is_valid_csv(csv_value) {
+            return Err(ScriptCreationError::BadParameters);
+        }
 
-        bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &rand_bytes)
-            .unwrap_or_else(|_| rand_xpub(rng, secp))
+        Ok(EmergencyDescriptor(emergency_template(
+            stakeholders,
+            csv_value,
+        )?))
     }
 
-    fn get_random_pubkey<C: secp256k1::Signing>(
-        rng: &mut fastrand::Rng,
-        secp: &secp256k1::Secp256k1<C>,
-    ) -> DescriptorPublicKey {
-        DescriptorPublicKey::XPub(DescriptorXKey {
-            origin: None,
-            xkey: bip32::ExtendedPubKey::from_private(&secp, &rand_xpub(rng, secp)),
-            derivation_path: bip32::DerivationPath::from(vec![]),
+    pub fn inner(&self) -> &Descriptor<PublicKey> {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Descriptor<PublicKey> {
+        self.0
+    }
+
+    /// Get the relative locktime in blocks contained in the Emergency descriptor
+    pub fn csv_value(&self) -> u32 {
+        emergency_template_check(&self.0).expect("Checked at creation")
+    }
+
+    /// Get the Emergency address for this descriptor on the given network
+    pub fn emergency_address(&self, network: Network) -> EmergencyAddress {
+        EmergencyAddress::from_network(self.0.address(network).expect("It's a P2WSH"), network)
+            .expect("It's a P2WSH")
+    }
+}
+
+impl Display for EmergencyDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for EmergencyDescriptor {
+    type Err = ScriptCreationError;
+
+    fn from_str(s: &str) -> Result<EmergencyDescriptor, Self::Err> {
+        let desc: Descriptor<PublicKey> = FromStr::from_str(s)?;
+        emergency_template_check(&desc)?;
+        standardness::check_miniscript(wsh_miniscript(&desc)?)?;
+
+        Ok(EmergencyDescriptor(desc))
+    }
+}
+
+/// The descriptors and the Emergency address of a Revault deployment, checked to be consistent
+/// with each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevaultDescriptors {
+    deposit_descriptor: DepositDescriptor,
+    unvault_descriptor: UnvaultDescriptor,
+    cpfp_descriptor: CpfpDescriptor,
+    emergency_address: EmergencyAddress,
+    network: Network,
+}
+
+/// The descriptors of a Revault deployment derived at a given index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedRevaultDescriptors {
+    deposit_descriptor: DerivedDepositDescriptor,
+    unvault_descriptor: DerivedUnvaultDescriptor,
+    cpfp_descriptor: DerivedCpfpDescriptor,
+    network: Network,
+}
+
+impl RevaultDescriptors {
+    /// Bundle the descriptors of a Revault deployment.
+    ///
+    /// # Errors
+    /// - If the stakeholders of the Deposit descriptor aren't the ones of the Unvault descriptor.
+    /// - If the managers of the CPFP descriptor aren't the ones of the Unvault descriptor.
+    /// - If a recovery key of the Deposit descriptor is a manager's or a cosigner's key.
+    /// - If the Unvault descriptor has cosigners, but not one per stakeholder.
+    /// - If the Emergency address is not for this `network`.
+    pub fn new(
+        deposit_descriptor: DepositDescriptor,
+        unvault_descriptor: UnvaultDescriptor,
+        cpfp_descriptor: CpfpDescriptor,
+        emergency_address: EmergencyAddress,
+        network: Network,
+    ) -> Result<RevaultDescriptors, ScriptCreationError> {
+        if !is_address_for_network(emergency_address.address(), network) {
+            return Err(ScriptCreationError::NetworkMismatch(network));
+        }
+
+        let participants = unvault_descriptor.participants();
+
+        let (mut deposit_stakeholders, mut unvault_stakeholders) =
+            (deposit_descriptor.xpubs(), participants.stakeholders);
+        deposit_stakeholders.sort();
+        unvault_stakeholders.sort();
+        if deposit_stakeholders != unvault_stakeholders {
+            return Err(ScriptCreationError::StakeholdersMismatch);
+        }
+
+        let (mut cpfp_managers, mut unvault_managers) =
+            (cpfp_descriptor.xpubs(), participants.managers);
+        cpfp_managers.sort();
+        unvault_managers.sort();
+        if cpfp_managers != unvault_managers {
+            return Err(ScriptCreationError::ManagersMismatch);
+        }
+
+        // The Deposit recovery path is for the stakeholders only.
+        if let Some(recovery) = deposit_descriptor.recovery() {
+            let unvault_cosigners = &participants.cosigners;
+            if recovery
+                .keys
+                .iter()
+                .any(|key| unvault_managers.contains(key) || unvault_cosigners.contains(key))
+            {
+                return Err(ScriptCreationError::RecoveryKeysReuse);
+            }
+        }
+
+        // There may be no cosigning server, otherwise one per stakeholder.
+        if !participants.cosigners.is_empty()
+            && participants.cosigners.len() != unvault_stakeholders.len()
+        {
+            return Err(ScriptCreationError::CosignersMismatch);
+        }
+
+        Ok(RevaultDescriptors {
+            deposit_descriptor,
+            unvault_descriptor,
+            cpfp_descriptor,
+            emergency_address,
+            network,
+        })
+    }
+
+    pub fn deposit_descriptor(&self) -> &DepositDescriptor {
+        &self.deposit_descriptor
+    }
+
+    pub fn unvault_descriptor(&self) -> &UnvaultDescriptor {
+        &self.unvault_descriptor
+    }
+
+    pub fn cpfp_descriptor(&self) -> &CpfpDescriptor {
+        &self.cpfp_descriptor
+    }
+
+    pub fn emergency_address(&self) -> &EmergencyAddress {
+        &self.emergency_address
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Derive the Deposit, Unvault and CPFP descriptors using the supplied `child_number`
+    pub fn derive<C: secp256k1::Verification>(
+        &self,
+        child_number: bip32::ChildNumber,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> DerivedRevaultDescriptors {
+        DerivedRevaultDescriptors {
+            deposit_descriptor: self.deposit_descriptor.derive(child_number, secp),
+            unvault_descriptor: self.unvault_descriptor.derive(child_number, secp),
+            cpfp_descriptor: self.cpfp_descriptor.derive(child_number, secp),
+            network: self.network,
+        }
+    }
+}
+
+impl DerivedRevaultDescriptors {
+    pub fn deposit_descriptor(&self) -> &DerivedDepositDescriptor {
+        &self.deposit_descriptor
+    }
+
+    pub fn unvault_descriptor(&self) -> &DerivedUnvaultDescriptor {
+        &self.unvault_descriptor
+    }
+
+    pub fn cpfp_descriptor(&self) -> &DerivedCpfpDescriptor {
+        &self.cpfp_descriptor
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Get the Deposit address on the deployment's network
+    pub fn deposit_address(&self) -> Address {
+        self.deposit_descriptor.address(self.network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{
+        expand_multipath, CpfpDescriptor, DepositDescriptor, DerivedCpfpDescriptor,
+        DerivedDepositDescriptor, DerivedUnvaultDescriptor, DescriptorTemplateError,
+        EmergencyAddress, EmergencyDescriptor, PublicKey, RevaultDescriptors, ScriptCreationError,
+        UnvaultDescriptor, SEQUENCE_LOCKTIME_MASK,
+    };
+
+    use miniscript::{
+        bitcoin::{secp256k1, util::bip32, Address, Network},
+        descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
+        policy::compiler::CompilerError,
+        DescriptorTrait,
+    };
+    use std::{iter::repeat_with, str::FromStr};
+
+    fn rand_xpub<C: secp256k1::Signing>(
+        rng: &mut fastrand::Rng,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> bip32::ExtendedPrivKey {
+        let rand_bytes: Vec<u8> = repeat_with(|| rng.u8(..)).take(64).collect();
+
+        bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &rand_bytes)
+            .unwrap_or_else(|_| rand_xpub(rng, secp))
+    }
+
+    fn get_random_pubkey<C: secp256k1::Signing>(
+        rng: &mut fastrand::Rng,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> DescriptorPublicKey {
+        DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: None,
+            xkey: bip32::ExtendedPubKey::from_private(&secp, &rand_xpub(rng, secp)),
+            derivation_path: bip32::DerivationPath::from(vec![]),
             wildcard: Wildcard::Unhardened,
         })
     }
@@ -905,6 +2320,20 @@ mod tests {
         )
         .expect("Refusing a wildcard cosigning server xpub");
 
+        // A threshold of 0 is refused before compiling the policy
+        assert_eq!(
+            UnvaultDescriptor::new(
+                vec![first_stakeholder.clone(), second_stakeholder.clone()],
+                vec![first_manager.clone(), second_manager.clone()],
+                0,
+                vec![first_cosig.clone(), second_cosig.clone()],
+                128,
+            )
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::BadParameters.to_string()
+        );
+
         // You can't mess up by from_str a wildcard descriptor from a derived one, and the other
         // way around.
         let raw_pk_a = PublicKey::from_str(
@@ -1041,6 +2470,94 @@ mod tests {
             2018,
         )
         .expect("Valid, with xpubs");
+        let sorted = |mut keys: Vec<DescriptorPublicKey>| {
+            keys.sort();
+            keys
+        };
+        assert_eq!(
+            sorted(unvault_desc.stakeholders()),
+            sorted(vec![
+                first_stakeholder.clone(),
+                second_stakeholder.clone(),
+                third_stakeholder.clone()
+            ])
+        );
+        assert_eq!(
+            sorted(unvault_desc.managers()),
+            sorted(vec![first_manager.clone(), second_manager.clone()])
+        );
+        assert_eq!(unvault_desc.managers_threshold(), 2);
+        assert_eq!(
+            sorted(unvault_desc.cosigners()),
+            sorted(vec![
+                first_cosig.clone(),
+                second_cosig.clone(),
+                third_cosig.clone()
+            ])
+        );
+        // Round-tripping through the string representation gives the same participants
+        let unvault_desc = UnvaultDescriptor::from_str(&unvault_desc.to_string()).unwrap();
+        assert_eq!(
+            sorted(unvault_desc.managers()),
+            sorted(vec![first_manager.clone(), second_manager.clone()])
+        );
+        assert_eq!(unvault_desc.managers_threshold(), 2);
+
+        // Only 1 of the 2 stakeholders is required in the non-timelocked branch
+        assert_eq!(
+            UnvaultDescriptor::from_str(&format!(
+                "wsh(or_d(multi(1,{},{}),and_v(v:pk({}),and_v(v:pk({}),older(18)))))",
+                first_stakeholder, second_stakeholder, first_manager, first_cosig
+            ))
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::StakeholdersBranch)
+                .to_string()
+        );
+        // The non-timelocked branch is not a threshold between keys
+        assert_eq!(
+            UnvaultDescriptor::from_str(&format!(
+                "wsh(or_d(thresh(2,pk({}),a:or_d(pk({}),pk({}))),and_v(v:pk({}),and_v(v:pk({}),older(18)))))",
+                first_stakeholder, second_stakeholder, third_stakeholder, first_manager, first_cosig
+            ))
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::StakeholdersBranch)
+                .to_string()
+        );
+        // No timelock
+        assert_eq!(
+            UnvaultDescriptor::from_str(&format!(
+                "wsh(or_d(multi(2,{},{}),and_v(v:pk({}),pk({}))))",
+                first_stakeholder, second_stakeholder, first_manager, first_cosig
+            ))
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::UnvaultBranches)
+                .to_string()
+        );
+        // The managers are timelocked, along with a hash instead of the cosigners
+        assert_eq!(
+            UnvaultDescriptor::from_str(&format!(
+                "wsh(or_d(multi(2,{},{}),and_v(v:pk({}),and_v(v:sha256({}),older(18)))))",
+                first_stakeholder,
+                second_stakeholder,
+                first_manager,
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            ))
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::ManagersBranch)
+                .to_string()
+        );
+        // Not a P2WSH
+        assert_eq!(
+            UnvaultDescriptor::from_str(&format!("wpkh({})", first_stakeholder))
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::NotP2wsh).to_string()
+        );
+
         assert_eq!(
             unvault_desc.xpubs().sort(),
             vec![
@@ -1057,6 +2574,1037 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_descriptors_template() {
+        let xpubs: Vec<DescriptorPublicKey> = [
+            "xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*",
+            "xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*",
+            "xpub6Br1DUfrzxTVGo1sanuKDCUmSxDfLRrxLQBqpMqygkQLkQWodoyvvGtUV8Rp3r6d6BNYvedBSU8c7whhn2U8haRVxsWwuQiZ9LoFp7jXPQA/*",
+        ]
+        .iter()
+        .map(|s| DescriptorPublicKey::from_str(s).unwrap())
+        .collect();
+        let template_err = |e| ScriptCreationError::InvalidTemplate(e).to_string();
+
+        // The descriptors we create are valid templates
+        let deposit_desc = DepositDescriptor::new(xpubs.clone()).unwrap();
+        DepositDescriptor::from_str(&deposit_desc.to_string()).unwrap();
+        let cpfp_desc = CpfpDescriptor::new(xpubs.clone()).unwrap();
+        CpfpDescriptor::from_str(&cpfp_desc.to_string()).unwrap();
+        CpfpDescriptor::from_str(&format!("wsh(pk({}))", xpubs[0])).unwrap();
+
+        // A 1-of-N or a 2-of-3 is not a valid Deposit descriptor
+        assert_eq!(
+            DepositDescriptor::from_str(&cpfp_desc.to_string())
+                .unwrap_err()
+                .to_string(),
+            template_err(DescriptorTemplateError::DepositPolicy)
+        );
+        assert_eq!(
+            DepositDescriptor::from_str(&format!(
+                "wsh(multi(2,{},{},{}))",
+                xpubs[0], xpubs[1], xpubs[2]
+            ))
+            .unwrap_err()
+            .to_string(),
+            template_err(DescriptorTemplateError::DepositPolicy)
+        );
+        // Neither is a single key
+        assert_eq!(
+            DepositDescriptor::from_str(&format!("wsh(pk({}))", xpubs[0]))
+                .unwrap_err()
+                .to_string(),
+            template_err(DescriptorTemplateError::DepositPolicy)
+        );
+        // Nor a sortedmulti
+        assert_eq!(
+            DepositDescriptor::from_str(&format!("wsh(sortedmulti(2,{},{}))", xpubs[0], xpubs[1]))
+                .unwrap_err()
+                .to_string(),
+            template_err(DescriptorTemplateError::NotP2wsh)
+        );
+
+        // A N-of-N is not a valid CPFP descriptor
+        assert_eq!(
+            CpfpDescriptor::from_str(&deposit_desc.to_string())
+                .unwrap_err()
+                .to_string(),
+            template_err(DescriptorTemplateError::CpfpPolicy)
+        );
+        // A timelocked one neither
+        assert_eq!(
+            CpfpDescriptor::from_str(&format!("wsh(and_v(v:pk({}),older(10)))", xpubs[0]))
+                .unwrap_err()
+                .to_string(),
+            template_err(DescriptorTemplateError::CpfpPolicy)
+        );
+
+        // Same for the derived descriptors
+        let secp = secp256k1::Secp256k1::verification_only();
+        let der_deposit_desc = deposit_desc.derive(0.into(), &secp);
+        DerivedDepositDescriptor::from_str(&der_deposit_desc.to_string()).unwrap();
+        let der_cpfp_desc = cpfp_desc.derive(0.into(), &secp);
+        DerivedCpfpDescriptor::from_str(&der_cpfp_desc.to_string()).unwrap();
+        assert_eq!(
+            DerivedDepositDescriptor::from_str(&der_cpfp_desc.to_string())
+                .unwrap_err()
+                .to_string(),
+            template_err(DescriptorTemplateError::DepositPolicy)
+        );
+        assert_eq!(
+            DerivedCpfpDescriptor::from_str(&der_deposit_desc.to_string())
+                .unwrap_err()
+                .to_string(),
+            template_err(DescriptorTemplateError::CpfpPolicy)
+        );
+
+        // The Unvault timelock must be in blocks
+        let unvault_desc = UnvaultDescriptor::new(
+            xpubs[..2].to_vec(),
+            vec![xpubs[2].clone()],
+            1,
+            vec![
+                DescriptorPublicKey::from_str(
+                    "02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
+                )
+                .unwrap(),
+                DescriptorPublicKey::from_str(
+                    "02767e6dde4877dcbf64de8a45fe1a0575dfc6b0ed06648f1022412c172ebd875c",
+                )
+                .unwrap(),
+            ],
+            144,
+        )
+        .unwrap();
+        UnvaultDescriptor::from_str(&unvault_desc.to_string()).unwrap();
+        let time_based_csv = 144 | (1 << 22);
+        assert_eq!(
+            UnvaultDescriptor::from_str(
+                &unvault_desc
+                    .to_string()
+                    .split('#')
+                    .next()
+                    .unwrap()
+                    .replace("older(144)", &format!("older({})", time_based_csv))
+            )
+            .unwrap_err()
+            .to_string(),
+            template_err(DescriptorTemplateError::UnvaultTimelock(time_based_csv))
+        );
+    }
+
+    #[test]
+    fn test_deterministic_descriptors() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let sorted = |mut keys: Vec<DescriptorPublicKey>| {
+            keys.sort();
+            keys
+        };
+
+        // The template, with the keys sorted by their string representation
+        let stakeholders: Vec<DescriptorPublicKey> = [
+            "xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*",
+            "xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*",
+        ]
+        .iter()
+        .map(|s| DescriptorPublicKey::from_str(s).unwrap())
+        .collect();
+        let managers = vec![DescriptorPublicKey::from_str("xpub6Duq1ob3cQ8Wxees2fTGNK2wTsVjgTPQcKJiPquXY2rQJTDjeCxkXFxTCGhcunFDt26Ddz45KQu7pbLmmUGG2PXTRVx3iDpBPEhdrijJf4U/*").unwrap()];
+        let cosigners: Vec<DescriptorPublicKey> = [
+            "02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35",
+            "02767e6dde4877dcbf64de8a45fe1a0575dfc6b0ed06648f1022412c172ebd875c",
+        ]
+        .iter()
+        .map(|s| DescriptorPublicKey::from_str(s).unwrap())
+        .collect();
+        let deposit_desc = DepositDescriptor::new_deterministic(stakeholders.clone()).unwrap();
+        assert_eq!(
+            deposit_desc.to_string().split('#').next().unwrap(),
+            format!("wsh(multi(2,{},{}))", stakeholders[1], stakeholders[0])
+        );
+        let unvault_desc = UnvaultDescriptor::new_deterministic(
+            stakeholders.clone(),
+            managers.clone(),
+            1,
+            cosigners.clone(),
+            6,
+        )
+        .unwrap();
+        assert_eq!(
+            unvault_desc.to_string().split('#').next().unwrap(),
+            format!(
+                "wsh(or_d(multi(2,{},{}),and_v(v:multi(1,{}),and_v(v:pk({}),and_v(v:pk({}),older(6))))))",
+                stakeholders[1], stakeholders[0], managers[0], cosigners[1], cosigners[0]
+            )
+        );
+        let cpfp_desc = CpfpDescriptor::new_deterministic(managers.clone()).unwrap();
+        assert_eq!(
+            cpfp_desc.to_string().split('#').next().unwrap(),
+            format!("wsh(multi(1,{}))", managers[0])
+        );
+
+        for (n_stk, n_man, thresh) in &[
+            (2, 1, 1),
+            (3, 4, 2),
+            (5, 3, 3),
+            (22, 2, 1),
+            (4, 20, 20),
+            (3, 22, 2),
+        ] {
+            let stakeholders = (0..*n_stk)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+            let managers = (0..*n_man)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+            let cosigners = (0..*n_stk)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+
+            let deposit_desc = DepositDescriptor::new_deterministic(stakeholders.clone()).unwrap();
+            let unvault_desc = UnvaultDescriptor::new_deterministic(
+                stakeholders.clone(),
+                managers.clone(),
+                *thresh,
+                cosigners.clone(),
+                144,
+            )
+            .unwrap();
+            let cpfp_desc = CpfpDescriptor::new_deterministic(managers.clone()).unwrap();
+
+            // The order of the keys doesn't matter
+            let (mut stakeholders_rev, mut managers_rev, mut cosigners_rev) =
+                (stakeholders.clone(), managers.clone(), cosigners.clone());
+            stakeholders_rev.reverse();
+            managers_rev.reverse();
+            cosigners_rev.reverse();
+            assert_eq!(
+                deposit_desc,
+                DepositDescriptor::new_deterministic(stakeholders_rev.clone()).unwrap()
+            );
+            assert_eq!(
+                unvault_desc,
+                UnvaultDescriptor::new_deterministic(
+                    stakeholders_rev,
+                    managers_rev.clone(),
+                    *thresh,
+                    cosigners_rev,
+                    144
+                )
+                .unwrap()
+            );
+            assert_eq!(
+                cpfp_desc,
+                CpfpDescriptor::new_deterministic(managers_rev).unwrap()
+            );
+
+            // They follow the Revault templates
+            DepositDescriptor::from_str(&deposit_desc.to_string()).unwrap();
+            CpfpDescriptor::from_str(&cpfp_desc.to_string()).unwrap();
+            let unvault_desc = UnvaultDescriptor::from_str(&unvault_desc.to_string()).unwrap();
+            assert_eq!(
+                sorted(unvault_desc.stakeholders()),
+                sorted(stakeholders.clone())
+            );
+            assert_eq!(sorted(unvault_desc.managers()), sorted(managers.clone()));
+            assert_eq!(unvault_desc.managers_threshold(), *thresh);
+            assert_eq!(sorted(unvault_desc.cosigners()), sorted(cosigners.clone()));
+            assert_eq!(unvault_desc.csv_value(), 144);
+            RevaultDescriptors::new(
+                deposit_desc,
+                unvault_desc,
+                cpfp_desc,
+                EmergencyAddress::from_network(
+                    Address::from_str(
+                        "bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6",
+                    )
+                    .unwrap(),
+                    Network::Bitcoin,
+                )
+                .unwrap(),
+                Network::Bitcoin,
+            )
+            .expect("Consistent descriptors");
+        }
+
+        // Same checks as the compiled descriptors
+        assert_eq!(
+            UnvaultDescriptor::new_deterministic(
+                stakeholders.clone(),
+                managers.clone(),
+                2,
+                cosigners,
+                6
+            )
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::BadParameters.to_string()
+        );
+        assert_eq!(
+            DepositDescriptor::new_deterministic(stakeholders[..1].to_vec())
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::BadParameters.to_string()
+        );
+        CpfpDescriptor::new_deterministic(vec![]).unwrap_err();
+    }
+
+    #[test]
+    fn test_deposit_recovery() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders = (0..4)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect::<Vec<DescriptorPublicKey>>();
+        let recovery_keys = (0..4)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect::<Vec<DescriptorPublicKey>>();
+
+        let deposit_desc = DepositDescriptor::new_with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            3,
+            52_560,
+        )
+        .unwrap();
+        let deposit_desc = DepositDescriptor::from_str(&deposit_desc.to_string()).unwrap();
+        // The recovery keys aren't part of the stakeholders'
+        let mut xpubs = deposit_desc.xpubs();
+        xpubs.sort();
+        let mut sorted_stakeholders = stakeholders.clone();
+        sorted_stakeholders.sort();
+        assert_eq!(xpubs, sorted_stakeholders);
+        // But they are exposed along with the recovery threshold and timelock
+        let mut recovery = deposit_desc.recovery().unwrap();
+        recovery.keys.sort();
+        let mut sorted_recovery_keys = recovery_keys.clone();
+        sorted_recovery_keys.sort();
+        assert_eq!(recovery.keys, sorted_recovery_keys);
+        assert_eq!(recovery.threshold, 3);
+        assert_eq!(recovery.csv_value, 52_560);
+        assert_eq!(
+            DepositDescriptor::new(stakeholders.clone())
+                .unwrap()
+                .recovery(),
+            None
+        );
+        let der_deposit_desc = deposit_desc.derive(bip32::ChildNumber::from(3), &secp);
+        DerivedDepositDescriptor::from_str(&der_deposit_desc.to_string()).unwrap();
+
+        // It's a valid Deposit descriptor for a Revault deployment
+        let managers = (0..2)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect::<Vec<DescriptorPublicKey>>();
+        let cosigners = (0..4)
+            .map(|_| get_random_pubkey(&mut rng, &secp))
+            .collect::<Vec<DescriptorPublicKey>>();
+        let unvault_desc = UnvaultDescriptor::new(
+            stakeholders.clone(),
+            managers.clone(),
+            2,
+            cosigners.clone(),
+            144,
+        )
+        .unwrap();
+        let emer_address = EmergencyAddress::from_network(
+            Address::from_str("bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6")
+                .unwrap(),
+            Network::Bitcoin,
+        )
+        .unwrap();
+        RevaultDescriptors::new(
+            deposit_desc,
+            unvault_desc.clone(),
+            CpfpDescriptor::new(managers.clone()).unwrap(),
+            emer_address.clone(),
+            Network::Bitcoin,
+        )
+        .expect("Consistent descriptors");
+
+        // But not if the managers or the cosigners can use the recovery path
+        for reused_key in &[&managers[1], &cosigners[2]] {
+            let mut reused_keys = recovery_keys.clone();
+            reused_keys[0] = (*reused_key).clone();
+            assert_eq!(
+                RevaultDescriptors::new(
+                    DepositDescriptor::new_with_recovery(
+                        stakeholders.clone(),
+                        reused_keys,
+                        3,
+                        52_560
+                    )
+                    .unwrap(),
+                    unvault_desc.clone(),
+                    CpfpDescriptor::new(managers.clone()).unwrap(),
+                    emer_address.clone(),
+                    Network::Bitcoin,
+                )
+                .unwrap_err()
+                .to_string(),
+                ScriptCreationError::RecoveryKeysReuse.to_string()
+            );
+        }
+
+        // The recovery keys must be distinct from the stakeholders' keys
+        let mut repeated_keys = recovery_keys.clone();
+        repeated_keys[1] = stakeholders[0].clone();
+        assert_eq!(
+            DepositDescriptor::new_with_recovery(stakeholders.clone(), repeated_keys, 3, 52_560)
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::BadParameters.to_string()
+        );
+        // One recovery key per stakeholder
+        DepositDescriptor::new_with_recovery(
+            stakeholders.clone(),
+            recovery_keys[..3].to_vec(),
+            3,
+            52_560,
+        )
+        .unwrap_err();
+        // Invalid thresholds
+        DepositDescriptor::new_with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            0,
+            52_560,
+        )
+        .unwrap_err();
+        DepositDescriptor::new_with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            5,
+            52_560,
+        )
+        .unwrap_err();
+        // But any threshold between 1 and the number of recovery keys is fine, as is any timelock
+        DepositDescriptor::new_with_recovery(stakeholders.clone(), recovery_keys.clone(), 1, 1)
+            .unwrap();
+        // Invalid timelocks
+        DepositDescriptor::new_with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            3,
+            SEQUENCE_LOCKTIME_MASK + 1,
+        )
+        .unwrap_err();
+        let time_based_csv = 1 << 22 | 10;
+        DepositDescriptor::new_with_recovery(
+            stakeholders.clone(),
+            recovery_keys.clone(),
+            3,
+            time_based_csv,
+        )
+        .unwrap_err();
+
+        // Same when parsing
+        let recovery = |recovery_keys: &[DescriptorPublicKey], thresh: usize, csv: u32| {
+            format!(
+                "wsh(or_d(multi(2,{},{}),and_v(v:multi({},{},{}),older({}))))",
+                stakeholders[0], stakeholders[1], thresh, recovery_keys[0], recovery_keys[1], csv
+            )
+        };
+        DepositDescriptor::from_str(&recovery(&recovery_keys, 2, 10)).unwrap();
+        DepositDescriptor::from_str(&recovery(&recovery_keys, 1, 10)).unwrap();
+        assert_eq!(
+            DepositDescriptor::from_str(&recovery(&recovery_keys, 2, time_based_csv))
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::DepositTimelock(
+                time_based_csv
+            ))
+            .to_string()
+        );
+        assert_eq!(
+            DepositDescriptor::from_str(&recovery(&stakeholders, 2, 10))
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::DepositPolicy)
+                .to_string()
+        );
+        assert_eq!(
+            DepositDescriptor::from_str(&format!(
+                "wsh(or_d(multi(2,{},{}),and_v(v:pk({}),older(10))))",
+                stakeholders[0], stakeholders[1], recovery_keys[0]
+            ))
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::DepositPolicy)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_unvault_thresholds() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        for (n_stk, stk_thresh, n_man, man_thresh, cosig_thresh) in &[
+            (2, 1, 1, 1, None),
+            (3, 2, 2, 1, Some(2)),
+            (5, 3, 3, 3, Some(5)),
+            (8, 5, 4, 2, Some(1)),
+            (22, 20, 2, 2, Some(21)),
+        ] {
+            let stakeholders = (0..*n_stk)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+            let managers = (0..*n_man)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+            let cosigners = (0..*n_stk)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+
+            let unvault_desc = UnvaultDescriptor::new_with_thresholds(
+                stakeholders.clone(),
+                *stk_thresh,
+                managers.clone(),
+                *man_thresh,
+                cosigners.clone(),
+                *cosig_thresh,
+                144,
+            )
+            .unwrap();
+            let unvault_str = unvault_desc.to_string();
+
+            // A weakened threshold must be opted in when parsing, and be exactly the expected one
+            assert_eq!(
+                UnvaultDescriptor::from_str(&unvault_str)
+                    .unwrap_err()
+                    .to_string(),
+                ScriptCreationError::InvalidTemplate(DescriptorTemplateError::StakeholdersBranch)
+                    .to_string()
+            );
+            assert_eq!(
+                UnvaultDescriptor::from_str_with_thresholds(
+                    &unvault_str,
+                    stk_thresh - 1,
+                    *cosig_thresh
+                )
+                .unwrap_err()
+                .to_string(),
+                ScriptCreationError::InvalidTemplate(DescriptorTemplateError::StakeholdersBranch)
+                    .to_string()
+            );
+            assert_eq!(
+                UnvaultDescriptor::from_str_with_thresholds(
+                    &unvault_str,
+                    *stk_thresh,
+                    Some(cosig_thresh.unwrap_or(*n_stk) - 1)
+                )
+                .unwrap_err()
+                .to_string(),
+                ScriptCreationError::InvalidTemplate(DescriptorTemplateError::ManagersBranch)
+                    .to_string()
+            );
+            let unvault_desc = UnvaultDescriptor::from_str_with_thresholds(
+                &unvault_str,
+                *stk_thresh,
+                *cosig_thresh,
+            )
+            .unwrap();
+            assert_eq!(unvault_desc.stakeholders_threshold(), *stk_thresh);
+            assert_eq!(unvault_desc.managers_threshold(), *man_thresh);
+            assert_eq!(
+                unvault_desc.cosigners_threshold(),
+                cosig_thresh.unwrap_or(*n_stk)
+            );
+            assert_eq!(unvault_desc.stakeholders().len(), *n_stk);
+            assert_eq!(unvault_desc.cosigners().len(), *n_stk);
+            assert_eq!(unvault_desc.csv_value(), 144);
+
+            // It's deterministic
+            let mut stakeholders_rev = stakeholders.clone();
+            stakeholders_rev.reverse();
+            assert_eq!(
+                unvault_desc,
+                UnvaultDescriptor::new_with_thresholds(
+                    stakeholders_rev,
+                    *stk_thresh,
+                    managers.clone(),
+                    *man_thresh,
+                    cosigners.clone(),
+                    *cosig_thresh,
+                    144,
+                )
+                .unwrap()
+            );
+
+            let der_unvault_str = unvault_desc
+                .derive(bip32::ChildNumber::from(42), &secp)
+                .to_string();
+            DerivedUnvaultDescriptor::from_str(&der_unvault_str).unwrap_err();
+            DerivedUnvaultDescriptor::from_str_with_thresholds(
+                &der_unvault_str,
+                *stk_thresh,
+                *cosig_thresh,
+            )
+            .unwrap();
+
+            // The thresholds must be within bounds
+            assert_eq!(
+                UnvaultDescriptor::new_with_thresholds(
+                    stakeholders.clone(),
+                    *stk_thresh,
+                    managers.clone(),
+                    0,
+                    cosigners.clone(),
+                    *cosig_thresh,
+                    144,
+                )
+                .unwrap_err()
+                .to_string(),
+                ScriptCreationError::BadParameters.to_string()
+            );
+            for (stk_thresh, cosig_thresh) in &[
+                (0, *cosig_thresh),
+                (n_stk + 1, *cosig_thresh),
+                (*stk_thresh, Some(0)),
+                (*stk_thresh, Some(n_stk + 1)),
+            ] {
+                assert_eq!(
+                    UnvaultDescriptor::new_with_thresholds(
+                        stakeholders.clone(),
+                        *stk_thresh,
+                        managers.clone(),
+                        *man_thresh,
+                        cosigners.clone(),
+                        *cosig_thresh,
+                        144,
+                    )
+                    .unwrap_err()
+                    .to_string(),
+                    ScriptCreationError::BadParameters.to_string()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_unvault_no_cosigners() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let emer_address = EmergencyAddress::from_network(
+            Address::from_str("bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6")
+                .unwrap(),
+            Network::Bitcoin,
+        )
+        .unwrap();
+
+        for (n_stk, n_man, thresh) in &[(2, 1, 1), (3, 2, 2), (4, 5, 3), (22, 3, 1), (3, 21, 21)] {
+            let stakeholders = (0..*n_stk)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+            let managers = (0..*n_man)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+
+            let compiled = UnvaultDescriptor::new(
+                stakeholders.clone(),
+                managers.clone(),
+                *thresh,
+                vec![],
+                144,
+            )
+            .unwrap();
+            let deterministic = UnvaultDescriptor::new_deterministic(
+                stakeholders.clone(),
+                managers.clone(),
+                *thresh,
+                vec![],
+                144,
+            )
+            .unwrap();
+            let with_thresholds = UnvaultDescriptor::new_with_thresholds(
+                stakeholders.clone(),
+                n_stk - 1,
+                managers.clone(),
+                *thresh,
+                vec![],
+                None,
+                144,
+            )
+            .unwrap();
+
+            for (unvault_desc, stk_thresh) in &[
+                (compiled, *n_stk),
+                (deterministic, *n_stk),
+                (with_thresholds, n_stk - 1),
+            ] {
+                let unvault_desc = UnvaultDescriptor::from_str_with_thresholds(
+                    &unvault_desc.to_string(),
+                    *stk_thresh,
+                    None,
+                )
+                .unwrap();
+                assert_eq!(unvault_desc.cosigners(), vec![]);
+                assert_eq!(unvault_desc.cosigners_threshold(), 0);
+                assert_eq!(unvault_desc.managers().len(), *n_man);
+                assert_eq!(unvault_desc.managers_threshold(), *thresh);
+                assert_eq!(unvault_desc.stakeholders().len(), *n_stk);
+                assert_eq!(unvault_desc.csv_value(), 144);
+
+                let der_unvault_desc = unvault_desc.derive(bip32::ChildNumber::from(42), &secp);
+                DerivedUnvaultDescriptor::from_str_with_thresholds(
+                    &der_unvault_desc.to_string(),
+                    *stk_thresh,
+                    None,
+                )
+                .unwrap();
+
+                if *n_man <= 20 {
+                    RevaultDescriptors::new(
+                        DepositDescriptor::new(stakeholders.clone()).unwrap(),
+                        unvault_desc,
+                        CpfpDescriptor::new(managers.clone()).unwrap(),
+                        emer_address.clone(),
+                        Network::Bitcoin,
+                    )
+                    .expect("No cosigner is a valid configuration");
+                }
+            }
+
+            // There may not be a cosigners threshold without cosigners
+            UnvaultDescriptor::new_with_thresholds(
+                stakeholders.clone(),
+                *n_stk,
+                managers.clone(),
+                *thresh,
+                vec![],
+                Some(1),
+                144,
+            )
+            .unwrap_err();
+        }
+    }
+
+    #[test]
+    fn test_emergency_descriptor() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        let stakeholders = (0..4)
+            .map(|_| {
+                get_random_pubkey(&mut rng, &secp)
+                    .derive(0)
+                    .derive_public_key(&secp)
+                    .unwrap()
+            })
+            .collect::<Vec<PublicKey>>();
+
+        let emer_desc = EmergencyDescriptor::new(stakeholders.clone(), 52_560).unwrap();
+        assert_eq!(emer_desc.csv_value(), 52_560);
+        assert_eq!(
+            EmergencyDescriptor::from_str(&emer_desc.to_string()).unwrap(),
+            emer_desc
+        );
+        // It doesn't depend on the keys order
+        let mut reversed_stakeholders = stakeholders.clone();
+        reversed_stakeholders.reverse();
+        assert_eq!(
+            EmergencyDescriptor::new(reversed_stakeholders, 52_560).unwrap(),
+            emer_desc
+        );
+        let emer_address = emer_desc.emergency_address(Network::Bitcoin);
+        assert_eq!(
+            emer_address.address().script_pubkey(),
+            emer_desc.inner().script_pubkey()
+        );
+        assert_eq!(emer_address.network(), Network::Bitcoin);
+        EmergencyAddress::from_network(emer_address.clone().into_address(), Network::Bitcoin)
+            .unwrap();
+        assert_eq!(
+            EmergencyAddress::from_network(emer_address.into_address(), Network::Regtest)
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::NetworkMismatch(Network::Regtest).to_string()
+        );
+        // Signet addresses are encoded as testnet ones
+        let testnet_address = emer_desc.emergency_address(Network::Testnet).into_address();
+        EmergencyAddress::from_network(testnet_address.clone(), Network::Signet).unwrap();
+        EmergencyAddress::from_network(testnet_address, Network::Bitcoin).unwrap_err();
+
+        // At least 2 distinct keys and a valid timelock
+        EmergencyDescriptor::new(stakeholders[..1].to_vec(), 52_560).unwrap_err();
+        let mut repeated_keys = stakeholders.clone();
+        repeated_keys[1] = stakeholders[0];
+        EmergencyDescriptor::new(repeated_keys, 52_560).unwrap_err();
+        EmergencyDescriptor::new(stakeholders.clone(), SEQUENCE_LOCKTIME_MASK + 1).unwrap_err();
+        EmergencyDescriptor::new(stakeholders.clone(), 0).unwrap_err();
+
+        // Not the Emergency template
+        assert_eq!(
+            EmergencyDescriptor::from_str(&format!(
+                "wsh(and_v(v:multi(1,{},{}),older(52560)))",
+                stakeholders[0], stakeholders[1]
+            ))
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::EmergencyPolicy)
+                .to_string()
+        );
+        EmergencyDescriptor::from_str(&format!(
+            "wsh(multi(2,{},{}))",
+            stakeholders[0], stakeholders[1]
+        ))
+        .unwrap_err();
+        assert_eq!(
+            EmergencyDescriptor::from_str(&format!(
+                "wsh(and_v(v:multi(2,{},{}),older(4194305)))",
+                stakeholders[0], stakeholders[1]
+            ))
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::InvalidTemplate(DescriptorTemplateError::EmergencyTimelock(
+                4194305
+            ))
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_multipath_and_origins() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+
+        // Keys as exported by a hardware wallet: [fingerprint/48'/0'/0'/2']xpub
+        let origin_path = bip32::DerivationPath::from_str("m/48'/0'/0'/2'").unwrap();
+        let stakeholders_keys = (0..3)
+            .map(|_| {
+                let master = rand_xpub(&mut rng, &secp);
+                let xpub = bip32::ExtendedPubKey::from_private(
+                    &secp,
+                    &master.derive_priv(&secp, &origin_path).unwrap(),
+                );
+                (master.fingerprint(&secp), xpub)
+            })
+            .collect::<Vec<_>>();
+        let keys_str = |path: &str| {
+            stakeholders_keys
+                .iter()
+                .map(|(fg, xpub)| format!("[{}/48'/0'/0'/2']{}/{}", fg, xpub, path))
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+
+        let multipath_str = format!("wsh(multi(3,{}))", keys_str("<0;1>/*"));
+        let deposit_descs = DepositDescriptor::from_multipath_str(&multipath_str).unwrap();
+        assert_eq!(deposit_descs.len(), 2);
+        assert_eq!(
+            deposit_descs[0],
+            DepositDescriptor::from_str(&format!("wsh(multi(3,{}))", keys_str("0/*"))).unwrap()
+        );
+        assert_eq!(
+            deposit_descs[1],
+            DepositDescriptor::from_str(&format!("wsh(multi(3,{}))", keys_str("1/*"))).unwrap()
+        );
+        assert_ne!(
+            deposit_descs[0].derive(bip32::ChildNumber::from(0), &secp),
+            deposit_descs[1].derive(bip32::ChildNumber::from(0), &secp)
+        );
+        // Not a multipath descriptor
+        assert_eq!(
+            DepositDescriptor::from_multipath_str(&deposit_descs[0].to_string()).unwrap(),
+            vec![deposit_descs[0].clone()]
+        );
+
+        // Invalid multipath steps
+        for path in &["<0>/*", "<0;>/*", "<0;1/*", "0;1>/*"] {
+            assert_eq!(
+                expand_multipath(&format!("wsh(multi(3,{}))", keys_str(path)))
+                    .unwrap_err()
+                    .to_string(),
+                ScriptCreationError::InvalidMultipath.to_string()
+            );
+        }
+        // Not the same number of paths
+        expand_multipath(&format!(
+            "wsh(multi(2,{}/<0;1>/*,{}/<0;1;2>/*))",
+            stakeholders_keys[0].1, stakeholders_keys[1].1
+        ))
+        .unwrap_err();
+        expand_multipath(&format!("{}#qwertyui", multipath_str)).unwrap_err();
+
+        // Hardened derivation steps after the xpub can't be derived
+        assert_eq!(
+            DepositDescriptor::from_str(&format!("wsh(multi(3,{}))", keys_str("0/*'")))
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::HardenedDerivation.to_string()
+        );
+        DepositDescriptor::from_str(&format!("wsh(multi(3,{}))", keys_str("0'/*"))).unwrap_err();
+        assert_eq!(
+            deposit_descs[0]
+                .try_derive(bip32::ChildNumber::from_hardened_idx(0).unwrap(), &secp)
+                .unwrap_err()
+                .to_string(),
+            ScriptCreationError::HardenedDerivation.to_string()
+        );
+        assert_eq!(
+            deposit_descs[0]
+                .try_derive(bip32::ChildNumber::from(12), &secp)
+                .unwrap(),
+            deposit_descs[0].derive(bip32::ChildNumber::from(12), &secp)
+        );
+
+        // The origin must be consistent with the xpub
+        let (fg, xpub) = &stakeholders_keys[0];
+        for origin in &[
+            format!("[{}/48'/0'/0']", fg),
+            format!("[{}/48'/0'/0'/1']", fg),
+            format!("[{}]", fg),
+        ] {
+            let key = DescriptorPublicKey::from_str(&format!("{}{}/*", origin, xpub)).unwrap();
+            assert_eq!(
+                DepositDescriptor::new(vec![key.clone(), get_random_pubkey(&mut rng, &secp)])
+                    .unwrap_err()
+                    .to_string(),
+                ScriptCreationError::InvalidKeyOrigin(key.to_string()).to_string()
+            );
+        }
+        let master = rand_xpub(&mut rng, &secp);
+        let master_xpub = bip32::ExtendedPubKey::from_private(&secp, &master);
+        let key = DescriptorPublicKey::from_str(&format!(
+            "[{}]{}/*",
+            master.fingerprint(&secp),
+            master_xpub
+        ))
+        .unwrap();
+        DepositDescriptor::new(vec![key, get_random_pubkey(&mut rng, &secp)]).unwrap();
+    }
+
+    #[test]
+    fn test_revault_descriptors() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let emer_address = EmergencyAddress::from_network(
+            Address::from_str("bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6")
+                .unwrap(),
+            Network::Bitcoin,
+        )
+        .unwrap();
+
+        for (n_stk, n_man, thresh) in &[(2, 1, 1), (3, 4, 2), (5, 3, 3), (22, 2, 1), (4, 20, 20)] {
+            let stakeholders = (0..*n_stk)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+            let managers = (0..*n_man)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+            let cosigners = (0..*n_stk)
+                .map(|_| get_random_pubkey(&mut rng, &secp))
+                .collect::<Vec<DescriptorPublicKey>>();
+
+            let deposit_desc = DepositDescriptor::new(stakeholders.clone()).unwrap();
+            let unvault_desc = UnvaultDescriptor::new(
+                stakeholders.clone(),
+                managers.clone(),
+                *thresh,
+                cosigners.clone(),
+                144,
+            )
+            .unwrap();
+            let cpfp_desc = CpfpDescriptor::new(managers.clone()).unwrap();
+
+            let descs = RevaultDescriptors::new(
+                deposit_desc.clone(),
+                unvault_desc.clone(),
+                cpfp_desc.clone(),
+                emer_address.clone(),
+                Network::Bitcoin,
+            )
+            .expect("Consistent descriptors");
+            let der_descs = descs.derive(bip32::ChildNumber::from(17), &secp);
+            assert_eq!(
+                der_descs.unvault_descriptor(),
+                &unvault_desc.derive(bip32::ChildNumber::from(17), &secp)
+            );
+            assert_eq!(der_descs.network(), Network::Bitcoin);
+            assert_eq!(
+                der_descs.deposit_address(),
+                der_descs.deposit_descriptor().address(Network::Bitcoin)
+            );
+            assert_eq!(
+                der_descs.deposit_address().script_pubkey(),
+                der_descs.deposit_descriptor().inner().script_pubkey()
+            );
+
+            // Not the same stakeholders
+            let other_deposit_desc = DepositDescriptor::new(
+                (0..*n_stk)
+                    .map(|_| get_random_pubkey(&mut rng, &secp))
+                    .collect::<Vec<DescriptorPublicKey>>(),
+            )
+            .unwrap();
+            assert_eq!(
+                RevaultDescriptors::new(
+                    other_deposit_desc,
+                    unvault_desc.clone(),
+                    cpfp_desc.clone(),
+                    emer_address.clone(),
+                    Network::Bitcoin
+                )
+                .unwrap_err()
+                .to_string(),
+                ScriptCreationError::StakeholdersMismatch.to_string()
+            );
+
+            // Not the same managers
+            let other_cpfp_desc = CpfpDescriptor::new(
+                (0..*n_man)
+                    .map(|_| get_random_pubkey(&mut rng, &secp))
+                    .collect::<Vec<DescriptorPublicKey>>(),
+            )
+            .unwrap();
+            assert_eq!(
+                RevaultDescriptors::new(
+                    deposit_desc.clone(),
+                    unvault_desc.clone(),
+                    other_cpfp_desc,
+                    emer_address.clone(),
+                    Network::Bitcoin
+                )
+                .unwrap_err()
+                .to_string(),
+                ScriptCreationError::ManagersMismatch.to_string()
+            );
+
+            // Not the same network
+            assert_eq!(
+                RevaultDescriptors::new(
+                    deposit_desc.clone(),
+                    unvault_desc.clone(),
+                    cpfp_desc.clone(),
+                    emer_address.clone(),
+                    Network::Testnet
+                )
+                .unwrap_err()
+                .to_string(),
+                ScriptCreationError::NetworkMismatch(Network::Testnet).to_string()
+            );
+        }
+
+        // A single cosigner for two stakeholders
+        let deposit_desc = DepositDescriptor::from_str("wsh(multi(2,xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*,xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*))").unwrap();
+        let unvault_desc = UnvaultDescriptor::from_str("wsh(or_d(multi(2,xpub6EHLFGpTTiZgHAHfBJ1LoepGFX5iyLeZ6CVtF9HhzeB1dkxLsEfkiJda78EKhSXuo2m8gQwAs4ZAbqaJixFYHMFWTL9DJX1KsAXS2VY5JJx/*,xpub6F2U61Uh9FNX94mZE6EgdZ3p5Wg8af6MHzFhskEskkAZ9ns2uvsnHBskU47wYY63yiYv8WufvTuHCePwUjK9zhKT1Cce8JGLBptncpvALw6/*),and_v(v:pk(xpub6Duq1ob3cQ8Wxees2fTGNK2wTsVjgTPQcKJiPquXY2rQJTDjeCxkXFxTCGhcunFDt26Ddz45KQu7pbLmmUGG2PXTRVx3iDpBPEhdrijJf4U/*),and_v(v:pk(02a489e0ea42b56148d212d325b7c67c6460483ff931c303ea311edfef667c8f35),older(42)))))").unwrap();
+        let cpfp_desc = CpfpDescriptor::from_str("wsh(pk(xpub6Duq1ob3cQ8Wxees2fTGNK2wTsVjgTPQcKJiPquXY2rQJTDjeCxkXFxTCGhcunFDt26Ddz45KQu7pbLmmUGG2PXTRVx3iDpBPEhdrijJf4U/*))").unwrap();
+        assert_eq!(
+            RevaultDescriptors::new(
+                deposit_desc,
+                unvault_desc,
+                cpfp_desc,
+                emer_address,
+                Network::Bitcoin
+            )
+            .unwrap_err()
+            .to_string(),
+            ScriptCreationError::CosignersMismatch.to_string()
+        );
+    }
+
     #[test]
     fn test_possible_default_configurations() {
         // Policy compilation takes time, so just test some remarkable ones
@@ -1260,4 +3808,51 @@ mod tests {
         )
         .expect("P2WSH (mainnet)");
     }
+
+    #[cfg(feature = "use-serde")]
+    #[test]
+    fn serde_parse_emer_address_for_network() {
+        use super::EmergencyAddressSeed;
+        use serde::de::DeserializeSeed;
+
+        let parse = |addr_str: &str, network: Network| {
+            let mut deserializer = serde_json::Deserializer::from_str(addr_str);
+            EmergencyAddressSeed(network).deserialize(&mut deserializer)
+        };
+
+        parse(
+            "\"bcrt1qrht43q4xt59vr9jytlmckgde6rcvhxcp392kx9\"",
+            Network::Regtest,
+        )
+        .expect_err("P2WPKH");
+        parse(
+            "\"bcrt1q5k05km5zn2g7kp0c230r0g8znuhlk4yynne3pwklh6xl82ed087sgr902c\"",
+            Network::Regtest,
+        )
+        .expect("P2WSH");
+        parse(
+            "\"bcrt1q5k05km5zn2g7kp0c230r0g8znuhlk4yynne3pwklh6xl82ed087sgr902c\"",
+            Network::Bitcoin,
+        )
+        .expect_err("P2WSH (regtest) on mainnet");
+
+        parse("\"1KFHE7w8BhaENAswwryaoccDb6qcT6DbYY\"", Network::Bitcoin).expect_err("P2PKH");
+        parse("\"3DoB8fDRHcNxLCBcgLTvrpfQD5amk6sUce\"", Network::Bitcoin).expect_err("P2SH");
+        parse(
+            "\"bc1qw3w0nt60tzh4xqdhx7hmf5uh0nczxhcr8lt7ec\"",
+            Network::Bitcoin,
+        )
+        .expect_err("P2WPKH (mainnet)");
+        let emer_address = parse(
+            "\"bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6\"",
+            Network::Bitcoin,
+        )
+        .expect("P2WSH (mainnet)");
+        assert_eq!(emer_address.network(), Network::Bitcoin);
+        parse(
+            "\"bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6\"",
+            Network::Testnet,
+        )
+        .expect_err("P2WSH (mainnet) on testnet");
+    }
 }
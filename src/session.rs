@@ -0,0 +1,614 @@
+//! # Revault signing sessions
+//!
+//! Sign the pre-signed transactions of many deposits at once. A [SigningSession] creates the
+//! chain of pre-signed transactions of each deposit and precomputes all their sighashes, with the
+//! `SIGHASH_ALL` type for the Unvault and `SIGHASH_ALL | ANYONECANPAY` for the revocation
+//! transactions. A stakeholder can then sign all of them in one pass, and the signatures are
+//! verified and gathered in [SignatureMessage]s to be exchanged with the other participants.
+
+use crate::{
+    error::{Error, ScriptCreationError, SigningSessionError},
+    scripts::RevaultDescriptors,
+    signatures::{CompactSignature, SignatureMessage},
+    transactions::{
+        transaction_chains, CancelTransaction, EmergencyTransaction, RevaultTransaction,
+        UnvaultEmergencyTransaction, UnvaultTransaction,
+    },
+    vault::PresignedTransaction,
+};
+
+use miniscript::{
+    bitcoin::{secp256k1, util::bip32, Amount, OutPoint, SigHashType},
+    descriptor::DescriptorPublicKey,
+};
+
+use std::collections::HashMap;
+
+/// A sighash of a pre-signed transaction to be signed by each stakeholder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionSighash {
+    /// The deposit of the vault this transaction belongs to
+    pub deposit_outpoint: OutPoint,
+    /// The derivation index of the keys signing this sighash
+    pub derivation_index: bip32::ChildNumber,
+    pub transaction: PresignedTransaction,
+    pub input_index: usize,
+    pub sighash_type: SigHashType,
+    /// The message to be signed
+    pub message: secp256k1::Message,
+}
+
+/// The pre-signed transactions of a deposit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresignedChain {
+    deposit_outpoint: OutPoint,
+    derivation_index: bip32::ChildNumber,
+    // The stakeholders' keys at this derivation index, in the order of the session's xpubs
+    stakeholders: Vec<secp256k1::PublicKey>,
+    unvault_tx: UnvaultTransaction,
+    cancel_tx: CancelTransaction,
+    emergency_tx: EmergencyTransaction,
+    unvault_emergency_tx: UnvaultEmergencyTransaction,
+}
+
+impl PresignedChain {
+    pub fn deposit_outpoint(&self) -> OutPoint {
+        self.deposit_outpoint
+    }
+
+    pub fn derivation_index(&self) -> bip32::ChildNumber {
+        self.derivation_index
+    }
+
+    pub fn unvault_tx(&self) -> &UnvaultTransaction {
+        &self.unvault_tx
+    }
+
+    pub fn cancel_tx(&self) -> &CancelTransaction {
+        &self.cancel_tx
+    }
+
+    pub fn emergency_tx(&self) -> &EmergencyTransaction {
+        &self.emergency_tx
+    }
+
+    pub fn unvault_emergency_tx(&self) -> &UnvaultEmergencyTransaction {
+        &self.unvault_emergency_tx
+    }
+
+    // Apply the signatures of this message for all the transactions of the chain
+    fn apply<C: secp256k1::Verification>(
+        &mut self,
+        message: &SignatureMessage,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<usize, SigningSessionError> {
+        // Don't leave the chain half-signed if one of the signatures is invalid
+        let mut chain = self.clone();
+        let count = message.apply_to(PresignedTransaction::Unvault, &mut chain.unvault_tx, secp)?
+            + message.apply_to(PresignedTransaction::Cancel, &mut chain.cancel_tx, secp)?
+            + message.apply_to(
+                PresignedTransaction::Emergency,
+                &mut chain.emergency_tx,
+                secp,
+            )?
+            + message.apply_to(
+                PresignedTransaction::UnvaultEmergency,
+                &mut chain.unvault_emergency_tx,
+                secp,
+            )?;
+        *self = chain;
+
+        Ok(count)
+    }
+}
+
+/// The pre-signed transactions of a set of deposits and their sighashes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SigningSession {
+    // The stakeholders' xpubs of the Deposit descriptor
+    stakeholders: Vec<DescriptorPublicKey>,
+    chains: Vec<PresignedChain>,
+    // The position of each deposit in the chains
+    positions: HashMap<OutPoint, usize>,
+    sighashes: Vec<SessionSighash>,
+}
+
+impl SigningSession {
+    /// Create the pre-signed transactions for these deposits (outpoint, amount and derivation
+    /// index) and compute their sighashes.
+    ///
+    /// # Errors
+    /// - If a deposit appears twice.
+    /// - If the pre-signed transactions can't be created for one of the deposits.
+    pub fn new<C: secp256k1::Verification>(
+        deposits: &[(OutPoint, Amount, bip32::ChildNumber)],
+        descriptors: &RevaultDescriptors,
+        lock_time: u32,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<SigningSession, Error> {
+        let mut positions = HashMap::with_capacity(deposits.len());
+        for (i, (deposit_outpoint, _, _)) in deposits.iter().enumerate() {
+            if positions.insert(*deposit_outpoint, i).is_some() {
+                return Err(ScriptCreationError::BadParameters.into());
+            }
+        }
+
+        let stakeholders = descriptors.deposit_descriptor().xpubs();
+        let mut chains = Vec::with_capacity(deposits.len());
+        let mut sighashes = Vec::with_capacity(deposits.len() * 4);
+
+        let transaction_chains = transaction_chains(
+            deposits,
+            descriptors.deposit_descriptor(),
+            descriptors.unvault_descriptor(),
+            descriptors.cpfp_descriptor(),
+            descriptors.emergency_address(),
+            lock_time,
+            secp,
+        )?;
+        for ((deposit_outpoint, _, derivation_index), transaction_chain) in
+            deposits.iter().copied().zip(transaction_chains)
+        {
+            let (unvault_tx, cancel_tx, emergency_tx, unvault_emergency_tx) = transaction_chain;
+            let derived_stakeholders = stakeholders
+                .iter()
+                .map(|xpub| {
+                    xpub.clone()
+                        .derive(derivation_index.into())
+                        .derive_public_key(secp)
+                        .map(|pubkey| pubkey.key)
+                        .map_err(|_| ScriptCreationError::HardenedDerivation)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // The pre-signed transactions are created without fee-bumping input
+            sighashes.extend(
+                [
+                    (PresignedTransaction::Unvault, SigHashType::All),
+                    (
+                        PresignedTransaction::Cancel,
+                        SigHashType::AllPlusAnyoneCanPay,
+                    ),
+                    (
+                        PresignedTransaction::Emergency,
+                        SigHashType::AllPlusAnyoneCanPay,
+                    ),
+                    (
+                        PresignedTransaction::UnvaultEmergency,
+                        SigHashType::AllPlusAnyoneCanPay,
+                    ),
+                ]
+                .iter()
+                .map(|(transaction, sighash_type)| {
+                    let sighash = match transaction {
+                        PresignedTransaction::Unvault => {
+                            unvault_tx.signature_hash(0, *sighash_type)
+                        }
+                        PresignedTransaction::Cancel => cancel_tx.signature_hash(0, *sighash_type),
+                        PresignedTransaction::Emergency => {
+                            emergency_tx.signature_hash(0, *sighash_type)
+                        }
+                        PresignedTransaction::UnvaultEmergency => {
+                            unvault_emergency_tx.signature_hash(0, *sighash_type)
+                        }
+                    }
+                    .expect("The pre-signed transactions have a single input");
+
+                    SessionSighash {
+                        deposit_outpoint,
+                        derivation_index,
+                        transaction: *transaction,
+                        input_index: 0,
+                        sighash_type: *sighash_type,
+                        message: secp256k1::Message::from_slice(&sighash)
+                            .expect("sighash is 32 bytes"),
+                    }
+                }),
+            );
+
+            chains.push(PresignedChain {
+                deposit_outpoint,
+                derivation_index,
+                stakeholders: derived_stakeholders,
+                unvault_tx,
+                cancel_tx,
+                emergency_tx,
+                unvault_emergency_tx,
+            });
+        }
+
+        Ok(SigningSession {
+            stakeholders,
+            chains,
+            positions,
+            sighashes,
+        })
+    }
+
+    /// The pre-signed transactions of all the deposits
+    pub fn chains(&self) -> &[PresignedChain] {
+        &self.chains
+    }
+
+    /// The pre-signed transactions of this deposit
+    pub fn chain(&self, deposit_outpoint: &OutPoint) -> Option<&PresignedChain> {
+        self.positions
+            .get(deposit_outpoint)
+            .map(|position| &self.chains[*position])
+    }
+
+    /// All the sighashes to be signed by each stakeholder, 4 per deposit.
+    pub fn sighashes(&self) -> &[SessionSighash] {
+        &self.sighashes
+    }
+
+    /// The (derivation index of the key, message) pairs to be signed by a stakeholder, in the
+    /// order of the [SigningSession::sighashes].
+    pub fn signing_requests(&self) -> Vec<(bip32::ChildNumber, secp256k1::Message)> {
+        self.sighashes
+            .iter()
+            .map(|sighash| (sighash.derivation_index, sighash.message))
+            .collect()
+    }
+
+    // Get the derivation path from this xpriv to the stakeholder's xpub of the Deposit
+    // descriptor. The xpriv may either be the one of the xpub, or the master key of its origin.
+    fn stakeholder_path<C: secp256k1::Signing>(
+        &self,
+        xpriv: &bip32::ExtendedPrivKey,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<bip32::DerivationPath, SigningSessionError> {
+        let xpub = bip32::ExtendedPubKey::from_private(secp, xpriv);
+        let fingerprint = xpriv.fingerprint(secp);
+
+        for stakeholder in self.stakeholders.iter() {
+            let xkey = match stakeholder {
+                DescriptorPublicKey::XPub(xkey) => xkey,
+                DescriptorPublicKey::SinglePub(_) => continue,
+            };
+
+            if xkey.xkey == xpub {
+                return Ok(xkey.derivation_path.clone());
+            }
+            if let Some((origin_fingerprint, ref origin_path)) = xkey.origin {
+                if origin_fingerprint == fingerprint
+                    && bip32::ExtendedPubKey::from_private(
+                        secp,
+                        &xpriv.derive_priv(secp, origin_path)?,
+                    ) == xkey.xkey
+                {
+                    return Ok(origin_path.extend(&xkey.derivation_path));
+                }
+            }
+        }
+
+        Err(SigningSessionError::NotStakeholder)
+    }
+
+    /// Sign all the sighashes with the keys derived from this stakeholder's xpriv, following
+    /// the derivation path of its xpub in the Deposit descriptor. The xpriv may either be the
+    /// one of the xpub, or the master key of the xpub's origin.
+    ///
+    /// # Errors
+    /// - If the xpriv is not one of a stakeholder of the Deposit descriptor.
+    /// - If a key can't be derived from the xpriv.
+    pub fn sign<C: secp256k1::Signing>(
+        &self,
+        xpriv: &bip32::ExtendedPrivKey,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<(secp256k1::PublicKey, secp256k1::Signature)>, SigningSessionError> {
+        let path = self.stakeholder_path(xpriv, secp)?;
+        let mut keys = HashMap::with_capacity(self.chains.len());
+
+        self.sighashes
+            .iter()
+            .map(|sighash| {
+                let (privkey, pubkey) = match keys.get(&sighash.derivation_index) {
+                    Some(keypair) => *keypair,
+                    None => {
+                        let privkey = xpriv
+                            .derive_priv(secp, &path.child(sighash.derivation_index))?
+                            .private_key
+                            .key;
+                        let keypair = (
+                            privkey,
+                            secp256k1::PublicKey::from_secret_key(secp, &privkey),
+                        );
+                        keys.insert(sighash.derivation_index, keypair);
+                        keypair
+                    }
+                };
+                Ok((pubkey, secp.sign(&sighash.message, &privkey)))
+            })
+            .collect()
+    }
+
+    /// Verify the (public key, signature) pairs of a stakeholder for all the sighashes, in the
+    /// order of the [SigningSession::sighashes], and gather them in one message per deposit.
+    ///
+    /// # Errors
+    /// - If there is not exactly one signature per sighash.
+    /// - If a public key is not the stakeholder's key at the derivation index of the deposit, or
+    ///   if the signatures are not all from the same stakeholder.
+    /// - If a signature is invalid.
+    pub fn collect<C: secp256k1::Verification>(
+        &self,
+        signatures: &[(secp256k1::PublicKey, secp256k1::Signature)],
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vec<SignatureMessage>, SigningSessionError> {
+        if signatures.len() != self.sighashes.len() {
+            return Err(SigningSessionError::SignaturesCount(
+                self.sighashes.len(),
+                signatures.len(),
+            ));
+        }
+
+        let mut messages: Vec<SignatureMessage> = self
+            .chains
+            .iter()
+            .map(|chain| SignatureMessage::new(chain.deposit_outpoint, chain.derivation_index))
+            .collect();
+        // The position of the signing stakeholder among the Deposit descriptor's xpubs
+        let mut signer = None;
+        for (sighash, (pubkey, signature)) in self.sighashes.iter().zip(signatures.iter()) {
+            let position = self.positions[&sighash.deposit_outpoint];
+            let stakeholder = self.chains[position]
+                .stakeholders
+                .iter()
+                .position(|stakeholder| stakeholder == pubkey);
+            match (stakeholder, signer) {
+                (Some(stakeholder), None) => signer = Some(stakeholder),
+                (Some(stakeholder), Some(signer)) if stakeholder == signer => {}
+                _ => {
+                    return Err(SigningSessionError::UnknownKey(
+                        sighash.deposit_outpoint,
+                        *pubkey,
+                    ))
+                }
+            }
+
+            secp.verify(&sighash.message, signature, pubkey)
+                .map_err(|_| {
+                    SigningSessionError::InvalidSignature(
+                        sighash.deposit_outpoint,
+                        sighash.transaction,
+                    )
+                })?;
+            messages[position].push(CompactSignature {
+                transaction: sighash.transaction,
+                input_index: sighash.input_index,
+                pubkey: *pubkey,
+                signature: *signature,
+            });
+        }
+
+        Ok(messages)
+    }
+
+    /// Add the signatures of this message to the pre-signed transactions of its deposit.
+    /// Returns the number of signatures added.
+    ///
+    /// # Errors
+    /// - If the deposit is not part of this session.
+    /// - If a signature is invalid, in which case none is added.
+    pub fn apply<C: secp256k1::Verification>(
+        &mut self,
+        message: &SignatureMessage,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<usize, SigningSessionError> {
+        let deposit_outpoint = message.deposit_outpoint();
+        let position = *self
+            .positions
+            .get(&deposit_outpoint)
+            .ok_or(SigningSessionError::UnknownDeposit(deposit_outpoint))?;
+        self.chains[position].apply(message, secp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SigningSession;
+    use crate::{
+        error::SigningSessionError,
+        test_utils::{descriptors, get_random_privkey, sign_at, xpubs},
+        transactions::RevaultTransaction,
+        vault::PresignedTransaction,
+    };
+
+    use miniscript::{
+        bitcoin::{secp256k1, util::bip32, Amount, OutPoint, SigHashType, Txid},
+        DescriptorPublicKey,
+    };
+
+    use std::str::FromStr;
+
+    #[test]
+    fn signing_session() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let xprivs: Vec<_> = (0..3).map(|_| get_random_privkey(&mut rng)).collect();
+        let xpubs = xpubs(&xprivs, &secp);
+        let descriptors = descriptors(xpubs[..2].to_vec(), xpubs[2..].to_vec(), 1, 6);
+        let deposits: Vec<_> = (0..20)
+            .map(|i| {
+                (
+                    OutPoint::new(Txid::default(), i),
+                    Amount::from_sat(1_000_000 + i as u64),
+                    bip32::ChildNumber::from(i * 2),
+                )
+            })
+            .collect();
+        let mut session = SigningSession::new(&deposits, &descriptors, 0, &secp).unwrap();
+        assert_eq!(session.chains().len(), 20);
+        assert_eq!(session.sighashes().len(), 80);
+        assert_eq!(session.signing_requests().len(), 80);
+
+        // The sighashes are the ones of the transactions
+        let chain = session.chain(&deposits[7].0).unwrap();
+        for sighash in session
+            .sighashes()
+            .iter()
+            .filter(|s| s.deposit_outpoint == deposits[7].0)
+        {
+            let expected = match sighash.transaction {
+                PresignedTransaction::Unvault => {
+                    assert_eq!(sighash.sighash_type, SigHashType::All);
+                    chain.unvault_tx().signature_hash(0, SigHashType::All)
+                }
+                PresignedTransaction::Cancel => chain
+                    .cancel_tx()
+                    .signature_hash(0, SigHashType::AllPlusAnyoneCanPay),
+                PresignedTransaction::Emergency => chain
+                    .emergency_tx()
+                    .signature_hash(0, SigHashType::AllPlusAnyoneCanPay),
+                PresignedTransaction::UnvaultEmergency => chain
+                    .unvault_emergency_tx()
+                    .signature_hash(0, SigHashType::AllPlusAnyoneCanPay),
+            }
+            .unwrap();
+            assert_eq!(&sighash.message[..], &expected[..]);
+        }
+
+        // Both stakeholders sign everything, the signatures are checked and exchanged
+        let mut messages = Vec::new();
+        for xpriv in xprivs[..2].iter() {
+            let signatures = session.sign(xpriv, &secp).unwrap();
+            assert_eq!(
+                session.collect(&signatures[1..], &secp),
+                Err(SigningSessionError::SignaturesCount(80, 79))
+            );
+            let mut invalid_signatures = signatures.clone();
+            invalid_signatures.swap(0, 1);
+            assert_eq!(
+                session.collect(&invalid_signatures, &secp),
+                Err(SigningSessionError::InvalidSignature(
+                    deposits[0].0,
+                    PresignedTransaction::Unvault
+                ))
+            );
+            messages.extend(session.collect(&signatures, &secp).unwrap());
+        }
+        // The manager is not a stakeholder
+        assert_eq!(
+            session.sign(&xprivs[2], &secp),
+            Err(SigningSessionError::NotStakeholder)
+        );
+        // Valid signatures, but not from a stakeholder's key for this deposit
+        let manager_key = xprivs[2]
+            .derive_priv(&secp, &[deposits[0].2])
+            .unwrap()
+            .private_key
+            .key;
+        let mut signatures = session.sign(&xprivs[0], &secp).unwrap();
+        signatures[0] = (
+            secp256k1::PublicKey::from_secret_key(&secp, &manager_key),
+            secp.sign(&session.sighashes()[0].message, &manager_key),
+        );
+        assert_eq!(
+            session.collect(&signatures, &secp),
+            Err(SigningSessionError::UnknownKey(
+                deposits[0].0,
+                signatures[0].0
+            ))
+        );
+        // Nor mixed signatures from both stakeholders
+        let mut signatures = session.sign(&xprivs[0], &secp).unwrap();
+        signatures[79] = session.sign(&xprivs[1], &secp).unwrap()[79];
+        assert_eq!(
+            session.collect(&signatures, &secp),
+            Err(SigningSessionError::UnknownKey(
+                deposits[19].0,
+                signatures[79].0
+            ))
+        );
+
+        assert_eq!(messages.len(), 40);
+        for message in messages.iter() {
+            assert_eq!(session.apply(message, &secp).unwrap(), 4);
+        }
+        for chain in session.chains() {
+            assert!(chain.unvault_tx().is_finalizable(&secp));
+            assert!(chain.cancel_tx().is_finalizable(&secp));
+            assert!(chain.emergency_tx().is_finalizable(&secp));
+            assert!(chain.unvault_emergency_tx().is_finalizable(&secp));
+        }
+    }
+
+    #[test]
+    fn signing_session_derivation_paths() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let xprivs: Vec<_> = (0..3).map(|_| get_random_privkey(&mut rng)).collect();
+        // The stakeholders use an account key with its origin, and derive under it
+        let account_path = bip32::DerivationPath::from_str("m/48'/0'/0'/2'").unwrap();
+        let xpubs: Vec<DescriptorPublicKey> = xprivs
+            .iter()
+            .map(|xpriv| {
+                let account_xpriv = xpriv.derive_priv(&secp, &account_path).unwrap();
+                DescriptorPublicKey::from_str(&format!(
+                    "[{}/48'/0'/0'/2']{}/0/*",
+                    xpriv.fingerprint(&secp),
+                    bip32::ExtendedPubKey::from_private(&secp, &account_xpriv)
+                ))
+                .unwrap()
+            })
+            .collect();
+        let descriptors = descriptors(xpubs[..2].to_vec(), xpubs[2..].to_vec(), 1, 6);
+        let deposits: Vec<_> = (0..2)
+            .map(|i| {
+                (
+                    OutPoint::new(Txid::default(), i),
+                    Amount::from_sat(1_000_000),
+                    bip32::ChildNumber::from(i + 5),
+                )
+            })
+            .collect();
+
+        // A deposit can't be signed for twice
+        SigningSession::new(
+            &[deposits[0], deposits[1], deposits[0]],
+            &descriptors,
+            0,
+            &secp,
+        )
+        .unwrap_err();
+        let mut session = SigningSession::new(&deposits, &descriptors, 0, &secp).unwrap();
+
+        // The first stakeholder signs with their master key, the second with their account key
+        let account_xpriv = xprivs[1].derive_priv(&secp, &account_path).unwrap();
+        for xpriv in &[xprivs[0], account_xpriv] {
+            let signatures = session.sign(xpriv, &secp).unwrap();
+            for message in session.collect(&signatures, &secp).unwrap() {
+                assert_eq!(session.apply(&message, &secp).unwrap(), 4);
+            }
+        }
+        for chain in session.chains() {
+            assert!(chain.unvault_tx().is_finalizable(&secp));
+            assert!(chain.cancel_tx().is_finalizable(&secp));
+            assert!(chain.emergency_tx().is_finalizable(&secp));
+            assert!(chain.unvault_emergency_tx().is_finalizable(&secp));
+        }
+
+        // Signing directly at the derivation index of the master key gives unknown keys
+        let xpriv = xprivs[0];
+        let signatures: Vec<_> = session
+            .sighashes()
+            .iter()
+            .map(|sighash| {
+                sign_at(
+                    &sighash.message[..],
+                    &xpriv,
+                    sighash.derivation_index,
+                    &secp,
+                )
+            })
+            .collect();
+        assert_eq!(
+            session.collect(&signatures, &secp),
+            Err(SigningSessionError::UnknownKey(
+                deposits[0].0,
+                signatures[0].0
+            ))
+        );
+    }
+}
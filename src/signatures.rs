@@ -0,0 +1,562 @@
+//! # Revault signatures exchange
+//!
+//! A compact format for the participants to exchange their signatures of the pre-signed
+//! transactions of a vault, instead of whole PSBTs. A [SignatureMessage] carries the signatures
+//! of a single participant for the transactions of a single deposit.
+//!
+//! The binary encoding (version 1) is:
+//! - The version, as 1 byte.
+//! - The deposit outpoint, consensus-encoded (36 bytes).
+//! - The derivation index, as 4 bytes little-endian.
+//! - The number of signatures, as a Bitcoin `VarInt`.
+//! - For each signature, the transaction type as 1 byte (`0` for the Unvault, `1` for the
+//!   Cancel, `2` for the Emergency and `3` for the UnvaultEmergency), the input index as a
+//!   `VarInt`, the 33 bytes compressed public key and the 64 bytes compact signature.
+//!
+//! With the `use-serde` feature, the message is serialized as a map in human-readable formats
+//! (such as JSON) and as the binary encoding otherwise.
+
+use crate::{
+    error::SignatureMessageError, transactions::RevaultTransaction, vault::PresignedTransaction,
+};
+
+use miniscript::bitcoin::{
+    consensus::encode::{Decodable, Encodable, VarInt},
+    secp256k1,
+    util::bip32,
+    OutPoint,
+};
+
+#[cfg(feature = "use-serde")]
+use {
+    serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor},
+    serde::ser::{Serialize, SerializeStruct, Serializer},
+    std::{fmt, str::FromStr},
+};
+
+use std::{convert::TryInto, io};
+
+/// The version of the format we produce.
+pub const SIGNATURE_MESSAGE_VERSION: u8 = 1;
+
+// The transaction types, in the order of their binary encoding
+const TRANSACTION_TYPES: [PresignedTransaction; 4] = [
+    PresignedTransaction::Unvault,
+    PresignedTransaction::Cancel,
+    PresignedTransaction::Emergency,
+    PresignedTransaction::UnvaultEmergency,
+];
+
+/// A signature for an input of a pre-signed transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactSignature {
+    pub transaction: PresignedTransaction,
+    pub input_index: usize,
+    pub pubkey: secp256k1::PublicKey,
+    pub signature: secp256k1::Signature,
+}
+
+/// The signatures of a participant for the pre-signed transactions of a deposit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureMessage {
+    deposit_outpoint: OutPoint,
+    derivation_index: bip32::ChildNumber,
+    signatures: Vec<CompactSignature>,
+}
+
+impl SignatureMessage {
+    /// An empty message for the vault of this deposit.
+    pub fn new(
+        deposit_outpoint: OutPoint,
+        derivation_index: bip32::ChildNumber,
+    ) -> SignatureMessage {
+        SignatureMessage {
+            deposit_outpoint,
+            derivation_index,
+            signatures: Vec::new(),
+        }
+    }
+
+    /// The deposit outpoint of the vault
+    pub fn deposit_outpoint(&self) -> OutPoint {
+        self.deposit_outpoint
+    }
+
+    /// The derivation index of the vault
+    pub fn derivation_index(&self) -> bip32::ChildNumber {
+        self.derivation_index
+    }
+
+    /// The signatures carried by this message
+    pub fn signatures(&self) -> &[CompactSignature] {
+        &self.signatures
+    }
+
+    /// Add a signature to the message
+    pub fn push(&mut self, signature: CompactSignature) {
+        self.signatures.push(signature);
+    }
+
+    /// Add to the message all the signatures of this key in this pre-signed transaction.
+    /// Returns the number of signatures added.
+    ///
+    /// # Errors
+    /// - If a signature in the PSBT can't be parsed.
+    pub fn add_signatures_from(
+        &mut self,
+        transaction: PresignedTransaction,
+        tx: &impl RevaultTransaction,
+        pubkey: secp256k1::PublicKey,
+    ) -> Result<usize, SignatureMessageError> {
+        let mut count = 0;
+        for (input_index, psbtin) in tx.psbt().inputs.iter().enumerate() {
+            let rawsig = match psbtin.partial_sigs.iter().find(|(pk, _)| pk.key == pubkey) {
+                Some((_, rawsig)) => rawsig,
+                None => continue,
+            };
+            let signature = rawsig
+                .split_last()
+                .and_then(|(_, der_sig)| secp256k1::Signature::from_der(der_sig).ok())
+                .ok_or_else(|| {
+                    SignatureMessageError::InvalidEncoding(format!(
+                        "Invalid signature in PSBT input {}",
+                        input_index
+                    ))
+                })?;
+            self.push(CompactSignature {
+                transaction,
+                input_index,
+                pubkey,
+                signature,
+            });
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Add the signatures of the message for this pre-signed transaction to `tx`, checking
+    /// each of them. Returns the number of signatures added.
+    ///
+    /// # Errors
+    /// - If a signature is invalid for `tx`, in which case none is added.
+    pub fn apply_to<C: secp256k1::Verification>(
+        &self,
+        transaction: PresignedTransaction,
+        tx: &mut impl RevaultTransaction,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<usize, SignatureMessageError> {
+        // Don't leave the transaction half-signed if one of the signatures is invalid
+        let mut original_psbt = tx.psbt().clone();
+        let mut count = 0;
+        for sig in self
+            .signatures
+            .iter()
+            .filter(|sig| sig.transaction == transaction)
+        {
+            tx.add_signature(sig.input_index, sig.pubkey, sig.signature, secp)
+                .map_err(|e| {
+                    std::mem::swap(tx.psbt_mut(), &mut original_psbt);
+                    SignatureMessageError::from(e)
+                })?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Get the binary encoding of the message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(42 + self.signatures.len() * 99);
+        bytes.push(SIGNATURE_MESSAGE_VERSION);
+        self.deposit_outpoint
+            .consensus_encode(&mut bytes)
+            .expect("Writing to a Vec");
+        bytes.extend_from_slice(&u32::from(self.derivation_index).to_le_bytes());
+        VarInt(self.signatures.len() as u64)
+            .consensus_encode(&mut bytes)
+            .expect("Writing to a Vec");
+
+        for sig in self.signatures.iter() {
+            let transaction_type = TRANSACTION_TYPES
+                .iter()
+                .position(|t| *t == sig.transaction)
+                .expect("All types are listed");
+            bytes.push(transaction_type as u8);
+            VarInt(sig.input_index as u64)
+                .consensus_encode(&mut bytes)
+                .expect("Writing to a Vec");
+            bytes.extend_from_slice(&sig.pubkey.serialize());
+            bytes.extend_from_slice(&sig.signature.serialize_compact());
+        }
+
+        bytes
+    }
+
+    /// Parse a message from its binary encoding.
+    ///
+    /// # Errors
+    /// - If the version is unknown.
+    /// - If the encoding is invalid, including if there are trailing bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SignatureMessage, SignatureMessageError> {
+        let mut cursor = io::Cursor::new(bytes);
+
+        let version = u8::consensus_decode(&mut cursor).map_err(invalid_encoding)?;
+        if version != SIGNATURE_MESSAGE_VERSION {
+            return Err(SignatureMessageError::UnknownVersion(version));
+        }
+        let deposit_outpoint = OutPoint::consensus_decode(&mut cursor).map_err(invalid_encoding)?;
+        let derivation_index =
+            bip32::ChildNumber::from(u32::consensus_decode(&mut cursor).map_err(invalid_encoding)?);
+        let mut message = SignatureMessage::new(deposit_outpoint, derivation_index);
+
+        let VarInt(count) = VarInt::consensus_decode(&mut cursor).map_err(invalid_encoding)?;
+        for _ in 0..count {
+            let transaction_type = u8::consensus_decode(&mut cursor).map_err(invalid_encoding)?;
+            let transaction = *TRANSACTION_TYPES
+                .get(transaction_type as usize)
+                .ok_or_else(|| invalid_encoding("Invalid transaction type"))?;
+            let VarInt(input_index) =
+                VarInt::consensus_decode(&mut cursor).map_err(invalid_encoding)?;
+            let (mut pubkey, mut signature) = ([0; 33], [0; 64]);
+            io::Read::read_exact(&mut cursor, &mut pubkey).map_err(invalid_encoding)?;
+            io::Read::read_exact(&mut cursor, &mut signature).map_err(invalid_encoding)?;
+
+            message.push(CompactSignature {
+                transaction,
+                input_index: input_index
+                    .try_into()
+                    .map_err(|_| invalid_encoding("Input index overflow"))?,
+                pubkey: secp256k1::PublicKey::from_slice(&pubkey).map_err(invalid_encoding)?,
+                signature: secp256k1::Signature::from_compact(&signature)
+                    .map_err(invalid_encoding)?,
+            });
+        }
+        if cursor.position() as usize != bytes.len() {
+            return Err(invalid_encoding("Trailing bytes"));
+        }
+
+        Ok(message)
+    }
+}
+
+fn invalid_encoding(e: impl std::fmt::Display) -> SignatureMessageError {
+    SignatureMessageError::InvalidEncoding(e.to_string())
+}
+
+#[cfg(feature = "use-serde")]
+impl Serialize for CompactSignature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("CompactSignature", 4)?;
+        state.serialize_field("transaction", &self.transaction.to_string())?;
+        state.serialize_field("input_index", &self.input_index)?;
+        state.serialize_field("pubkey", &self.pubkey.to_string())?;
+        state.serialize_field("signature", &self.signature.to_string())?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "use-serde")]
+impl<'de> Deserialize<'de> for CompactSignature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CompactSignatureVisitor;
+
+        impl<'de> Visitor<'de> for CompactSignatureVisitor {
+            type Value = CompactSignature;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a signature for a pre-signed transaction input")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let (mut transaction, mut input_index, mut pubkey, mut signature) =
+                    (None, None, None, None);
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "transaction" => {
+                            let value = map.next_value::<String>()?;
+                            transaction = Some(
+                                *TRANSACTION_TYPES
+                                    .iter()
+                                    .find(|t| t.to_string() == value)
+                                    .ok_or_else(|| {
+                                        de::Error::custom(format!(
+                                            "Unknown transaction '{}'",
+                                            value
+                                        ))
+                                    })?,
+                            );
+                        }
+                        "input_index" => input_index = Some(map.next_value::<usize>()?),
+                        "pubkey" => {
+                            pubkey = Some(
+                                secp256k1::PublicKey::from_str(&map.next_value::<String>()?)
+                                    .map_err(de::Error::custom)?,
+                            )
+                        }
+                        "signature" => {
+                            signature = Some(
+                                secp256k1::Signature::from_str(&map.next_value::<String>()?)
+                                    .map_err(de::Error::custom)?,
+                            )
+                        }
+                        _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                    }
+                }
+
+                Ok(CompactSignature {
+                    transaction: transaction
+                        .ok_or_else(|| de::Error::missing_field("transaction"))?,
+                    input_index: input_index
+                        .ok_or_else(|| de::Error::missing_field("input_index"))?,
+                    pubkey: pubkey.ok_or_else(|| de::Error::missing_field("pubkey"))?,
+                    signature: signature.ok_or_else(|| de::Error::missing_field("signature"))?,
+                })
+            }
+        }
+
+        const FIELDS: &[&str] = &["transaction", "input_index", "pubkey", "signature"];
+        deserializer.deserialize_struct("CompactSignature", FIELDS, CompactSignatureVisitor)
+    }
+}
+
+#[cfg(feature = "use-serde")]
+impl Serialize for SignatureMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            let mut state = serializer.serialize_struct("SignatureMessage", 4)?;
+            state.serialize_field("version", &SIGNATURE_MESSAGE_VERSION)?;
+            state.serialize_field("deposit_outpoint", &self.deposit_outpoint.to_string())?;
+            state.serialize_field("derivation_index", &u32::from(self.derivation_index))?;
+            state.serialize_field("signatures", &self.signatures)?;
+            state.end()
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "use-serde")]
+impl<'de> Deserialize<'de> for SignatureMessage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SignatureMessageVisitor;
+
+        impl<'de> Visitor<'de> for SignatureMessageVisitor {
+            type Value = SignatureMessage;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "the signatures for the pre-signed transactions of a deposit"
+                )
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let (mut version, mut deposit_outpoint, mut derivation_index, mut signatures) =
+                    (None, None, None, None);
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "version" => version = Some(map.next_value::<u8>()?),
+                        "deposit_outpoint" => {
+                            deposit_outpoint = Some(
+                                OutPoint::from_str(&map.next_value::<String>()?)
+                                    .map_err(de::Error::custom)?,
+                            )
+                        }
+                        "derivation_index" => {
+                            derivation_index =
+                                Some(bip32::ChildNumber::from(map.next_value::<u32>()?))
+                        }
+                        "signatures" => {
+                            signatures = Some(map.next_value::<Vec<CompactSignature>>()?)
+                        }
+                        _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                    }
+                }
+
+                let version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+                if version != SIGNATURE_MESSAGE_VERSION {
+                    return Err(de::Error::custom(SignatureMessageError::UnknownVersion(
+                        version,
+                    )));
+                }
+                Ok(SignatureMessage {
+                    deposit_outpoint: deposit_outpoint
+                        .ok_or_else(|| de::Error::missing_field("deposit_outpoint"))?,
+                    derivation_index: derivation_index
+                        .ok_or_else(|| de::Error::missing_field("derivation_index"))?,
+                    signatures: signatures.ok_or_else(|| de::Error::missing_field("signatures"))?,
+                })
+            }
+        }
+
+        const FIELDS: &[&str] = &[
+            "version",
+            "deposit_outpoint",
+            "derivation_index",
+            "signatures",
+        ];
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_struct("SignatureMessage", FIELDS, SignatureMessageVisitor)
+        } else {
+            SignatureMessage::from_bytes(&Vec::<u8>::deserialize(deserializer)?)
+                .map_err(de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompactSignature, SignatureMessage};
+    use crate::{
+        error::{InputSatisfactionError, SignatureMessageError},
+        test_utils::{descriptors, get_random_privkey, sign_at, vault_sighash, xpubs},
+        transactions::RevaultTransaction,
+        vault::{PresignedTransaction, Vault},
+    };
+
+    use miniscript::bitcoin::{secp256k1, util::bip32, Amount, OutPoint, Txid};
+
+    #[test]
+    fn signature_message() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let xprivs: Vec<_> = (0..3).map(|_| get_random_privkey(&mut rng)).collect();
+        let xpubs = xpubs(&xprivs, &secp);
+        let descriptors = descriptors(xpubs[..2].to_vec(), xpubs[2..].to_vec(), 1, 6);
+        let (deposit_outpoint, derivation_index) = (
+            OutPoint::new(Txid::default(), 3),
+            bip32::ChildNumber::from(7),
+        );
+        let vault = Vault::new(
+            deposit_outpoint,
+            Amount::from_sat(10_000_000),
+            derivation_index,
+            &descriptors,
+            0,
+            &secp,
+        )
+        .unwrap();
+
+        // The first stakeholder signs the Unvault and the Cancel
+        let (pubkey, unvault_sig) = sign_at(
+            &vault_sighash(&vault, PresignedTransaction::Unvault),
+            &xprivs[0],
+            derivation_index,
+            &secp,
+        );
+        let (_, cancel_sig) = sign_at(
+            &vault_sighash(&vault, PresignedTransaction::Cancel),
+            &xprivs[0],
+            derivation_index,
+            &secp,
+        );
+        let mut cancel_tx = vault.cancel_tx().clone();
+        cancel_tx.add_cancel_sig(pubkey, cancel_sig, &secp).unwrap();
+
+        let mut message = SignatureMessage::new(deposit_outpoint, derivation_index);
+        message.push(CompactSignature {
+            transaction: PresignedTransaction::Unvault,
+            input_index: 0,
+            pubkey,
+            signature: unvault_sig,
+        });
+        assert_eq!(
+            message
+                .add_signatures_from(PresignedTransaction::Cancel, &cancel_tx, pubkey)
+                .unwrap(),
+            1
+        );
+        assert_eq!(message.signatures()[1].signature, cancel_sig);
+
+        // Binary roundtrip
+        let bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 1 + 36 + 4 + 1 + 2 * (1 + 1 + 33 + 64));
+        assert_eq!(SignatureMessage::from_bytes(&bytes).unwrap(), message);
+        let mut invalid_bytes = bytes.clone();
+        invalid_bytes[0] = 2;
+        assert_eq!(
+            SignatureMessage::from_bytes(&invalid_bytes),
+            Err(SignatureMessageError::UnknownVersion(2))
+        );
+        for invalid_bytes in &[
+            &bytes[..bytes.len() - 1],
+            &[bytes.clone(), vec![0]].concat(),
+        ] {
+            match SignatureMessage::from_bytes(invalid_bytes) {
+                Err(SignatureMessageError::InvalidEncoding(_)) => {}
+                e => panic!("Unexpected result: {:?}", e),
+            }
+        }
+
+        // Apply them to the transactions
+        let mut unvault_tx = vault.unvault_tx().clone();
+        assert_eq!(
+            message
+                .apply_to(PresignedTransaction::Unvault, &mut unvault_tx, &secp)
+                .unwrap(),
+            1
+        );
+        assert!(unvault_tx.psbt().inputs[0]
+            .partial_sigs
+            .keys()
+            .any(|pk| pk.key == pubkey));
+        let mut cancel_tx = vault.cancel_tx().clone();
+        assert_eq!(
+            message
+                .apply_to(PresignedTransaction::Cancel, &mut cancel_tx, &secp)
+                .unwrap(),
+            1
+        );
+        let mut emergency_tx = vault.emergency_tx().clone();
+        assert_eq!(
+            message
+                .apply_to(PresignedTransaction::Emergency, &mut emergency_tx, &secp)
+                .unwrap(),
+            0
+        );
+
+        // If a signature is invalid, none is applied
+        message.push(CompactSignature {
+            transaction: PresignedTransaction::Unvault,
+            input_index: 0,
+            pubkey,
+            signature: cancel_sig,
+        });
+        let mut unvault_tx = vault.unvault_tx().clone();
+        match message.apply_to(PresignedTransaction::Unvault, &mut unvault_tx, &secp) {
+            Err(SignatureMessageError::InputSatisfaction(
+                InputSatisfactionError::InvalidSignature(..),
+            )) => {}
+            e => panic!("Unexpected result: {:?}", e),
+        }
+        assert_eq!(&unvault_tx, vault.unvault_tx());
+
+        #[cfg(feature = "use-serde")]
+        {
+            let json = serde_json::to_string(&message).unwrap();
+            assert!(json.contains("\"transaction\":\"cancel\""));
+            assert!(json.contains(&format!("\"deposit_outpoint\":\"{}\"", deposit_outpoint)));
+            assert_eq!(
+                serde_json::from_str::<SignatureMessage>(&json).unwrap(),
+                message
+            );
+            serde_json::from_str::<SignatureMessage>(
+                &json.replace("\"version\":1", "\"version\":2"),
+            )
+            .expect_err("Unknown version");
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! # Revault standardness checks
+//!
+//! Bitcoin Core's policy rules, beyond the [transaction
+//! weight](crate::transactions::MAX_STANDARD_TX_WEIGHT) and the dust limit, a transaction must
+//! follow to be relayed. A pre-signed transaction that would not be relayed is useless, so these
+//! are checked when creating the descriptors and the transactions, and when parsing PSBTs.
+//!
+//! See <https://github.com/bitcoin/bitcoin/blob/590e49ccf2af27c6c1f1e0eb8be3a4bf4d92ce8b/src/policy/policy.h>.
+
+use crate::error::StandardnessError;
+
+use miniscript::{
+    bitcoin::{
+        blockdata::{opcodes, script::Instruction},
+        util::psbt::PartiallySignedTransaction as Psbt,
+        Script, TxOut,
+    },
+    Miniscript, MiniscriptKey, Segwitv0,
+};
+
+/// The maximum size of a P2WSH witness Script, in bytes.
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3_600;
+
+/// The maximum number of items in a P2WSH witness stack, without the witness Script.
+pub const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+
+/// The maximum size of an item of a P2WSH witness stack, without the witness Script, in bytes.
+pub const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+
+/// The maximum sigops cost of a transaction.
+pub const MAX_STANDARD_TX_SIGOPS_COST: usize = 16_000;
+
+/// The maximum size of a data carrier (`OP_RETURN`) output Script, in bytes.
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+// The maximum number of keys of a standard bare multisig.
+const MAX_BARE_MULTISIG_KEYS: usize = 3;
+
+// Legacy sigops are scaled by this factor in the sigops cost, witness ones aren't.
+const WITNESS_SCALE_FACTOR: usize = 4;
+
+// The number of sigops of a CHECKMULTISIG whose number of keys we don't know.
+const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+// The value of a OP_1 to OP_16 opcode, if it's one.
+fn pushnum_value(op: opcodes::All) -> Option<usize> {
+    let code = op.into_u8();
+    let first = opcodes::all::OP_PUSHNUM_1.into_u8();
+    let last = opcodes::all::OP_PUSHNUM_16.into_u8();
+    if code >= first && code <= last {
+        Some((code - first + 1) as usize)
+    } else {
+        None
+    }
+}
+
+/// Count the signature operations of this Script, as Bitcoin Core does.
+///
+/// If `accurate`, the number of sigops of a `CHECKMULTISIG` preceded by a `OP_n` is `n`,
+/// otherwise it's always 20. Witness Scripts are counted accurately, output Scripts are not.
+pub fn script_sigops(script: &Script, accurate: bool) -> usize {
+    let mut sigops = 0;
+    let mut last_op = None;
+
+    for instruction in script.instructions() {
+        let op = match instruction {
+            Ok(Instruction::Op(op)) => op,
+            Ok(Instruction::PushBytes(_)) => {
+                last_op = None;
+                continue;
+            }
+            // Core stops counting at the first invalid opcode
+            Err(_) => break,
+        };
+
+        if op == opcodes::all::OP_CHECKSIG || op == opcodes::all::OP_CHECKSIGVERIFY {
+            sigops += 1;
+        } else if op == opcodes::all::OP_CHECKMULTISIG || op == opcodes::all::OP_CHECKMULTISIGVERIFY
+        {
+            sigops += match last_op.and_then(pushnum_value) {
+                Some(n) if accurate => n,
+                _ => MAX_PUBKEYS_PER_MULTISIG,
+            };
+        }
+        last_op = Some(op);
+    }
+
+    sigops
+}
+
+// Whether this Script is a bare k-of-n multisig with at most MAX_BARE_MULTISIG_KEYS keys.
+fn is_standard_bare_multisig(script: &Script) -> bool {
+    let instructions = match script.instructions().collect::<Result<Vec<_>, _>>() {
+        Ok(instructions) => instructions,
+        Err(_) => return false,
+    };
+    if instructions.len() < 4 {
+        return false;
+    }
+
+    let (k, n) = match (
+        &instructions[0],
+        &instructions[instructions.len() - 2],
+        &instructions[instructions.len() - 1],
+    ) {
+        (Instruction::Op(k), Instruction::Op(n), Instruction::Op(op))
+            if *op == opcodes::all::OP_CHECKMULTISIG =>
+        {
+            match (pushnum_value(*k), pushnum_value(*n)) {
+                (Some(k), Some(n)) => (k, n),
+                _ => return false,
+            }
+        }
+        _ => return false,
+    };
+    let keys = &instructions[1..instructions.len() - 2];
+
+    k <= n
+        && n == keys.len()
+        && n <= MAX_BARE_MULTISIG_KEYS
+        && keys.iter().all(|key| match key {
+            Instruction::PushBytes(key) => key.len() == 33 || key.len() == 65,
+            _ => false,
+        })
+}
+
+// Whether this Script is a data carrier output Script that would be relayed.
+fn is_standard_data_carrier(script: &Script) -> bool {
+    script.is_op_return()
+        && script.len() <= MAX_OP_RETURN_RELAY
+        && Script::from(script.as_bytes()[1..].to_vec())
+            .instructions()
+            .all(|instruction| match instruction {
+                Ok(Instruction::PushBytes(_)) => true,
+                Ok(Instruction::Op(op)) => op.into_u8() <= opcodes::all::OP_PUSHNUM_16.into_u8(),
+                Err(_) => false,
+            })
+}
+
+/// Check this output Script is of a standard type: P2PK, P2PKH, P2SH, v0 P2WPKH or P2WSH, a
+/// Segwit v1+ witness program, a bare multisig of at most 3 keys or a data carrier.
+pub fn check_output(script_pubkey: &Script) -> Result<(), StandardnessError> {
+    let is_standard = script_pubkey.is_p2pkh()
+        || script_pubkey.is_p2sh()
+        || script_pubkey.is_v0_p2wpkh()
+        || script_pubkey.is_v0_p2wsh()
+        // v0 programs of another length are invalid, unknown versions are reserved for upgrades
+        || (script_pubkey.is_witness_program() && script_pubkey.as_bytes()[0] != 0)
+        || script_pubkey.is_p2pk()
+        || is_standard_bare_multisig(script_pubkey)
+        || is_standard_data_carrier(script_pubkey);
+
+    if is_standard {
+        Ok(())
+    } else {
+        Err(StandardnessError::NonStandardOutput(script_pubkey.clone()))
+    }
+}
+
+/// Check these transaction outputs are all of a standard type, and that there is at most one
+/// data carrier among them.
+pub fn check_outputs(outputs: &[TxOut]) -> Result<(), StandardnessError> {
+    let mut data_carriers = 0;
+    for txo in outputs {
+        check_output(&txo.script_pubkey)?;
+        if txo.script_pubkey.is_op_return() {
+            data_carriers += 1;
+        }
+    }
+
+    if data_carriers > 1 {
+        return Err(StandardnessError::MultipleDataCarriers);
+    }
+
+    Ok(())
+}
+
+/// Check the limits on the size of a P2WSH witness Script and on the number of items needed to
+/// satisfy it (not counting the witness Script).
+pub fn check_p2wsh_limits(script_size: usize, stack_items: usize) -> Result<(), StandardnessError> {
+    if script_size > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+        return Err(StandardnessError::WitnessScriptTooLarge(script_size));
+    }
+
+    if stack_items > MAX_STANDARD_P2WSH_STACK_ITEMS {
+        return Err(StandardnessError::TooManyStackItems(stack_items));
+    }
+
+    Ok(())
+}
+
+/// Check the satisfaction of this P2WSH Miniscript would be relayed.
+pub fn check_miniscript<Pk: MiniscriptKey>(
+    ms: &Miniscript<Pk, Segwitv0>,
+) -> Result<(), StandardnessError> {
+    let stack_items = ms
+        .ext
+        .stack_elem_count_sat
+        .ok_or(StandardnessError::Unsatisfiable)?;
+    check_p2wsh_limits(ms.script_size(), stack_items)
+}
+
+/// Check a finalized P2WSH witness, whose last item is the witness Script, would be relayed.
+pub fn check_p2wsh_witness(witness: &[Vec<u8>]) -> Result<(), StandardnessError> {
+    let (witness_script, stack) = match witness.split_last() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    check_p2wsh_limits(witness_script.len(), stack.len())?;
+    if let Some(item) = stack
+        .iter()
+        .find(|item| item.len() > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE)
+    {
+        return Err(StandardnessError::StackItemTooLarge(item.len()));
+    }
+
+    Ok(())
+}
+
+/// Get the sigops cost of the transaction this PSBT represents, once finalized.
+///
+/// Inputs must all be native Segwit, which is checked by the PSBT sanity checks.
+pub fn sigops_cost(psbt: &Psbt) -> usize {
+    let inputs_sigops: usize = psbt
+        .inputs
+        .iter()
+        .map(|psbtin| {
+            let spk = match psbtin.witness_utxo {
+                Some(ref txo) => &txo.script_pubkey,
+                None => return 0,
+            };
+            if spk.is_v0_p2wpkh() {
+                return 1;
+            }
+
+            if let Some(ref witness_script) = psbtin.witness_script {
+                script_sigops(witness_script, true)
+            } else {
+                psbtin
+                    .final_script_witness
+                    .as_ref()
+                    .and_then(|witness| witness.last())
+                    .map(|witness_script| {
+                        script_sigops(&Script::from(witness_script.clone()), true)
+                    })
+                    .unwrap_or(0)
+            }
+        })
+        .sum();
+    let outputs_sigops: usize = psbt
+        .global
+        .unsigned_tx
+        .output
+        .iter()
+        .map(|txo| script_sigops(&txo.script_pubkey, false) * WITNESS_SCALE_FACTOR)
+        .sum();
+
+    inputs_sigops + outputs_sigops
+}
+
+/// Check the transaction this PSBT represents would be relayed: its outputs, its sigops cost and
+/// the witnesses of its finalized P2WSH inputs.
+///
+/// The witness Scripts of the non-finalized inputs need a Miniscript analysis to be checked, see
+/// [check_miniscript] and [check_p2wsh_limits].
+pub fn check_psbt(psbt: &Psbt) -> Result<(), StandardnessError> {
+    check_outputs(&psbt.global.unsigned_tx.output)?;
+
+    let sigops = sigops_cost(psbt);
+    if sigops > MAX_STANDARD_TX_SIGOPS_COST {
+        return Err(StandardnessError::TooManySigops(sigops));
+    }
+
+    for psbtin in psbt.inputs.iter() {
+        let is_p2wsh = psbtin
+            .witness_utxo
+            .as_ref()
+            .map(|txo| txo.script_pubkey.is_v0_p2wsh())
+            == Some(true);
+        if let (true, Some(ref witness)) = (is_p2wsh, &psbtin.final_script_witness) {
+            check_p2wsh_witness(witness)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use miniscript::{
+        bitcoin::{blockdata::script::Builder, PublicKey},
+        Descriptor, DescriptorTrait,
+    };
+
+    use std::str::FromStr;
+
+    fn pubkey(i: u8) -> PublicKey {
+        let secp = miniscript::bitcoin::secp256k1::Secp256k1::signing_only();
+        let secret = miniscript::bitcoin::secp256k1::SecretKey::from_slice(&[i + 1; 32]).unwrap();
+        PublicKey {
+            compressed: true,
+            key: miniscript::bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &secret),
+        }
+    }
+
+    #[test]
+    fn output_types() {
+        let key = pubkey(0);
+        let wsh = Builder::new()
+            .push_int(0)
+            .push_slice(&[0x42; 32])
+            .into_script();
+        check_output(&wsh).unwrap();
+        check_output(&Script::new_v0_wpkh(&key.wpubkey_hash().unwrap())).unwrap();
+        check_output(&Script::new_p2pkh(&key.pubkey_hash())).unwrap();
+        check_output(&Script::new_p2sh(&wsh.script_hash())).unwrap();
+        check_output(&Script::new_p2pk(&key)).unwrap();
+        // A Taproot output
+        check_output(
+            &Builder::new()
+                .push_opcode(opcodes::all::OP_PUSHNUM_1)
+                .push_slice(&[0x42; 32])
+                .into_script(),
+        )
+        .unwrap();
+
+        // A 2-of-3 bare multisig is standard, a 2-of-4 isn't
+        let multisig = |n: u8| {
+            let mut builder = Builder::new().push_int(2);
+            for i in 0..n {
+                builder = builder.push_key(&pubkey(i));
+            }
+            builder
+                .push_int(n.into())
+                .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+                .into_script()
+        };
+        check_output(&multisig(3)).unwrap();
+        assert_eq!(
+            check_output(&multisig(4)),
+            Err(StandardnessError::NonStandardOutput(multisig(4)))
+        );
+
+        // Data carriers must be small and push-only
+        let op_return = |data: &[u8]| {
+            Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .push_slice(data)
+                .into_script()
+        };
+        check_output(&op_return(&[0x42; 80])).unwrap();
+        check_output(&op_return(&[0x42; 81])).unwrap_err();
+        check_output(
+            &Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .push_opcode(opcodes::all::OP_CHECKSIG)
+                .into_script(),
+        )
+        .unwrap_err();
+        let txo = |script_pubkey: Script| TxOut {
+            value: 0,
+            script_pubkey,
+        };
+        check_outputs(&[txo(op_return(&[0x01])), txo(wsh.clone())]).unwrap();
+        assert_eq!(
+            check_outputs(&[txo(op_return(&[0x01])), txo(op_return(&[0x02]))]),
+            Err(StandardnessError::MultipleDataCarriers)
+        );
+
+        // Neither are random Scripts nor a v0 witness program of an invalid size
+        let v0_invalid = Builder::new()
+            .push_int(0)
+            .push_slice(&[0x42; 25])
+            .into_script();
+        check_output(&v0_invalid).unwrap_err();
+        check_output(
+            &Builder::new()
+                .push_opcode(opcodes::all::OP_CHECKSIG)
+                .into_script(),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn sigops() {
+        let desc = Descriptor::<PublicKey>::from_str(&format!(
+            "wsh(or_d(multi(2,{},{},{}),and_v(v:pk({}),older(42))))",
+            pubkey(0),
+            pubkey(1),
+            pubkey(2),
+            pubkey(3)
+        ))
+        .unwrap();
+        let witness_script = desc.explicit_script();
+        assert_eq!(script_sigops(&witness_script, true), 4);
+        assert_eq!(script_sigops(&witness_script, false), 21);
+        assert_eq!(script_sigops(&desc.script_pubkey(), false), 0);
+    }
+
+    #[test]
+    fn witness_limits() {
+        let sig = vec![0x30; 72];
+        let witness_script = vec![0x51; 100];
+        check_p2wsh_witness(&[sig.clone(), sig.clone(), witness_script.clone()]).unwrap();
+        check_p2wsh_witness(&[vec![0x51; 100]]).unwrap();
+        check_p2wsh_witness(&[]).unwrap();
+
+        assert_eq!(
+            check_p2wsh_witness(&[vec![0x30; 81], witness_script.clone()]),
+            Err(StandardnessError::StackItemTooLarge(81))
+        );
+        let mut witness = vec![sig; 101];
+        witness.push(witness_script);
+        assert_eq!(
+            check_p2wsh_witness(&witness),
+            Err(StandardnessError::TooManyStackItems(101))
+        );
+        assert_eq!(
+            check_p2wsh_witness(&[vec![0x51; 3_601]]),
+            Err(StandardnessError::WitnessScriptTooLarge(3_601))
+        );
+
+        // A chain of 101 keys needs 101 signatures
+        let ms_str = (1..101).fold(format!("pk({})", pubkey(0)), |ms, i| {
+            format!("and_v(v:pk({}),{})", pubkey(i), ms)
+        });
+        let ms = Miniscript::<PublicKey, Segwitv0>::from_str_insane(&ms_str).unwrap();
+        assert_eq!(
+            check_miniscript(&ms),
+            Err(StandardnessError::TooManyStackItems(101))
+        );
+
+        // A Script that can't be satisfied would never be relayed
+        let ms = Miniscript::<PublicKey, Segwitv0>::from_str_insane(&format!(
+            "and_v(v:pk({}),0)",
+            pubkey(0)
+        ))
+        .unwrap();
+        assert_eq!(check_miniscript(&ms), Err(StandardnessError::Unsatisfiable));
+    }
+}
@@ -0,0 +1,157 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{
+    error::VaultError,
+    scripts::{
+        CpfpDescriptor, DepositDescriptor, EmergencyAddress, RevaultDescriptors, UnvaultDescriptor,
+    },
+    transactions::RevaultTransaction,
+    vault::{PresignedTransaction, Vault},
+};
+
+use miniscript::{
+    bitcoin::{secp256k1, util::bip32, Address, Network, PublicKey, SigHash, SigHashType},
+    descriptor::{DescriptorPublicKey, DescriptorSinglePub, DescriptorXKey, Wildcard},
+};
+
+use std::{iter::repeat_with, str::FromStr};
+
+/// A random master extended private key
+pub fn get_random_privkey(rng: &mut fastrand::Rng) -> bip32::ExtendedPrivKey {
+    let rand_bytes: Vec<u8> = repeat_with(|| rng.u8(..)).take(64).collect();
+
+    bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &rand_bytes)
+        .unwrap_or_else(|_| get_random_privkey(rng))
+}
+
+/// The `xpub/*` descriptor key of a random master extended private key
+pub fn get_random_pubkey<C: secp256k1::Signing>(
+    rng: &mut fastrand::Rng,
+    secp: &secp256k1::Secp256k1<C>,
+) -> DescriptorPublicKey {
+    DescriptorPublicKey::XPub(DescriptorXKey {
+        origin: None,
+        xkey: bip32::ExtendedPubKey::from_private(secp, &get_random_privkey(rng)),
+        derivation_path: bip32::DerivationPath::from(vec![]),
+        wildcard: Wildcard::Unhardened,
+    })
+}
+
+/// A random secret key
+pub fn get_random_secret_key(rng: &mut fastrand::Rng) -> secp256k1::SecretKey {
+    let rand_bytes: Vec<u8> = repeat_with(|| rng.u8(..)).take(32).collect();
+    secp256k1::SecretKey::from_slice(&rand_bytes).unwrap_or_else(|_| get_random_secret_key(rng))
+}
+
+/// The static descriptor key of a cosigning server with this secret key
+pub fn cosigner_pubkey<C: secp256k1::Signing>(
+    secret_key: &secp256k1::SecretKey,
+    secp: &secp256k1::Secp256k1<C>,
+) -> DescriptorPublicKey {
+    DescriptorPublicKey::SinglePub(DescriptorSinglePub {
+        origin: None,
+        key: PublicKey {
+            compressed: true,
+            key: secp256k1::PublicKey::from_secret_key(secp, secret_key),
+        },
+    })
+}
+
+/// The `xpub/*` descriptor keys of these master extended private keys
+pub fn xpubs(
+    xprivs: &[bip32::ExtendedPrivKey],
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+) -> Vec<DescriptorPublicKey> {
+    xprivs
+        .iter()
+        .map(|xpriv| {
+            DescriptorPublicKey::from_str(&format!(
+                "{}/*",
+                bip32::ExtendedPubKey::from_private(secp, xpriv)
+            ))
+            .unwrap()
+        })
+        .collect()
+}
+
+/// A mainnet P2WSH Emergency address
+pub fn emergency_address() -> EmergencyAddress {
+    EmergencyAddress::from_network(
+        Address::from_str("bc1qnz0msqjqaw59zex2aw00rm565yg0rlpc5h3dvtps38w60ggw0seqwgjaa6")
+            .unwrap(),
+        Network::Bitcoin,
+    )
+    .unwrap()
+}
+
+/// The mainnet descriptors of a deployment with these stakeholders and managers, without
+/// cosigning servers
+pub fn descriptors(
+    stakeholders: Vec<DescriptorPublicKey>,
+    managers: Vec<DescriptorPublicKey>,
+    managers_threshold: usize,
+    csv_value: u32,
+) -> RevaultDescriptors {
+    RevaultDescriptors::new(
+        DepositDescriptor::new(stakeholders.clone()).unwrap(),
+        UnvaultDescriptor::new(
+            stakeholders,
+            managers.clone(),
+            managers_threshold,
+            vec![],
+            csv_value,
+        )
+        .unwrap(),
+        CpfpDescriptor::new(managers).unwrap(),
+        emergency_address(),
+        Network::Bitcoin,
+    )
+    .unwrap()
+}
+
+/// Sign this signature hash with the key derived from this extended private key at this index
+pub fn sign_at(
+    sighash: &[u8],
+    xpriv: &bip32::ExtendedPrivKey,
+    index: bip32::ChildNumber,
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+) -> (secp256k1::PublicKey, secp256k1::Signature) {
+    let privkey = xpriv.derive_priv(secp, &[index]).unwrap().private_key;
+    let msg = secp256k1::Message::from_slice(sighash).unwrap();
+
+    (privkey.public_key(secp).key, secp.sign(&msg, &privkey.key))
+}
+
+/// The signature hash of this pre-signed transaction of the vault
+pub fn vault_sighash(vault: &Vault, transaction: PresignedTransaction) -> SigHash {
+    match transaction {
+        PresignedTransaction::Unvault => vault.unvault_tx().signature_hash(0, SigHashType::All),
+        PresignedTransaction::Cancel => vault
+            .cancel_tx()
+            .signature_hash(0, SigHashType::AllPlusAnyoneCanPay),
+        PresignedTransaction::Emergency => vault
+            .emergency_tx()
+            .signature_hash(0, SigHashType::AllPlusAnyoneCanPay),
+        PresignedTransaction::UnvaultEmergency => vault
+            .unvault_emergency_tx()
+            .signature_hash(0, SigHashType::AllPlusAnyoneCanPay),
+    }
+    .unwrap()
+}
+
+/// Have all the stakeholders sign this pre-signed transaction of the vault
+pub fn sign_vault(
+    vault: &mut Vault,
+    transaction: PresignedTransaction,
+    stakeholders: &[bip32::ExtendedPrivKey],
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+) -> Result<(), VaultError> {
+    let sighash = vault_sighash(vault, transaction);
+
+    for xpriv in stakeholders {
+        let (pubkey, signature) = sign_at(&sighash, xpriv, vault.derivation_index(), secp);
+        vault.add_signature(transaction, pubkey, signature, secp)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use crate::{
+    error::*,
+    scripts::{emergency_script_csv, is_matured_sequence},
+    standardness,
+    transactions::{utils, RevaultTransaction, INSANE_FEES, MAX_STANDARD_TX_WEIGHT, TX_VERSION},
+    txins::*,
+    txouts::*,
+};
+
+use miniscript::bitcoin::{
+    blockdata::constants::max_money,
+    consensus::encode::Decodable,
+    util::psbt::{
+        Global as PsbtGlobal, Input as PsbtIn, Output as PsbtOut,
+        PartiallySignedTransaction as Psbt,
+    },
+    Address, Network, Script, SigHashType, Transaction, TxOut,
+};
+
+#[cfg(feature = "use-serde")]
+use {
+    serde::de::{self, Deserialize, Deserializer},
+    serde::ser::{Serialize, Serializer},
+};
+
+use std::{collections::BTreeMap, convert::TryInto};
+
+impl_revault_transaction!(
+    DeepVaultRecoveryTransaction,
+    doc = "The transaction spending Emergency Deep Vault outputs once their timelock matured, \
+    paying to a single address."
+);
+impl DeepVaultRecoveryTransaction {
+    // Internal DRY routine for creating the inner PSBT
+    fn create_psbt(deep_vault_inputs: Vec<DeepVaultTxIn>, txout: TxOut, lock_time: u32) -> Psbt {
+        Psbt {
+            global: PsbtGlobal {
+                unsigned_tx: Transaction {
+                    version: TX_VERSION,
+                    lock_time,
+                    input: deep_vault_inputs
+                        .iter()
+                        .map(|input| input.unsigned_txin())
+                        .collect(),
+                    output: vec![txout],
+                },
+                version: 0,
+                xpub: BTreeMap::new(),
+                proprietary: BTreeMap::new(),
+                unknown: BTreeMap::new(),
+            },
+            inputs: deep_vault_inputs
+                .into_iter()
+                .map(|input| {
+                    let prev_txout = input.into_txout();
+                    PsbtIn {
+                        witness_script: Some(prev_txout.witness_script().clone()),
+                        sighash_type: Some(SigHashType::All),
+                        witness_utxo: Some(prev_txout.into_txout()),
+                        ..PsbtIn::default()
+                    }
+                })
+                .collect(),
+            outputs: vec![PsbtOut::default()],
+        }
+    }
+
+    /// A Deep Vault recovery transaction sweeps one or more Emergency outputs to a single
+    /// `destination`, paying `feerate` sats per weight unit. It is not pre-signed: it is created
+    /// and signed by the stakeholders once the Emergency timelock matured, therefore the inputs'
+    /// sequence must be at least the [Emergency descriptor's
+    /// timelock](crate::scripts::EmergencyDescriptor::csv_value).
+    ///
+    /// BIP174 Creator and Updater roles.
+    pub fn new(
+        deep_vault_inputs: Vec<DeepVaultTxIn>,
+        destination: Address,
+        feerate: u64,
+        lock_time: u32,
+    ) -> Result<DeepVaultRecoveryTransaction, TransactionCreationError> {
+        // The Deep Vault outputs may only be spent once the Emergency timelock matured.
+        for txin in deep_vault_inputs.iter() {
+            let sequence = txin.unsigned_txin().sequence;
+            if !emergency_script_csv(txin.txout().witness_script())
+                .map(|csv| is_matured_sequence(sequence, csv))
+                .unwrap_or(false)
+            {
+                return Err(TransactionCreationError::InvalidSequence(
+                    txin.outpoint(),
+                    sequence,
+                ));
+            }
+        }
+
+        // First, create a dummy transaction to get its weight without Witness.
+        let dummy_txo = TxOut {
+            value: u64::MAX,
+            script_pubkey: destination.script_pubkey(),
+        };
+        let dummy_tx = DeepVaultRecoveryTransaction::create_psbt(
+            deep_vault_inputs.clone(),
+            dummy_txo,
+            lock_time,
+        )
+        .global
+        .unsigned_tx;
+
+        // The weight of the transaction is the weight of the witness-stripped transaction plus
+        // the weight required to satisfy each of the Deep Vault txins.
+        let sat_weight = deep_vault_inputs
+            .iter()
+            .map(|txin| txin.txout().max_sat_weight())
+            .sum::<usize>();
+        let total_weight = dummy_tx
+            .get_weight()
+            .checked_add(sat_weight)
+            .expect("Weight computation bug: cannot overflow");
+        if total_weight > MAX_STANDARD_TX_WEIGHT as usize {
+            return Err(TransactionCreationError::TooLarge);
+        }
+        let total_weight: u64 = total_weight.try_into().expect("usize in u64");
+        let fees = feerate
+            .checked_mul(total_weight)
+            .ok_or(TransactionCreationError::InsaneFees)?;
+        if fees > INSANE_FEES {
+            return Err(TransactionCreationError::InsaneFees);
+        }
+
+        let value_in = deep_vault_inputs
+            .iter()
+            .try_fold(0u64, |value, txin| {
+                value.checked_add(txin.txout().txout().value)
+            })
+            .ok_or(TransactionCreationError::InsaneAmounts)?;
+        if value_in > max_money(Network::Bitcoin) {
+            return Err(TransactionCreationError::InsaneAmounts);
+        }
+        let value_out = value_in
+            .checked_sub(fees)
+            .ok_or(TransactionCreationError::Dust)?;
+        let txout = TxOut {
+            value: value_out,
+            script_pubkey: destination.script_pubkey(),
+        };
+        if txout.value < txout.script_pubkey.dust_value() {
+            return Err(TransactionCreationError::Dust);
+        }
+
+        let psbt = DeepVaultRecoveryTransaction::create_psbt(deep_vault_inputs, txout, lock_time);
+        standardness::check_psbt(&psbt)?;
+
+        Ok(DeepVaultRecoveryTransaction(psbt))
+    }
+
+    /// Parse a Deep Vault recovery transaction from a PSBT. Inputs whose nSequence does not
+    /// satisfy the Emergency timelock are rejected.
+    pub fn from_raw_psbt(raw_psbt: &[u8]) -> Result<Self, TransactionSerialisationError> {
+        let psbt = Decodable::consensus_decode(raw_psbt)?;
+        let psbt = utils::psbt_common_sanity_checks(psbt)?;
+
+        // Destination txo
+        let output_count = psbt.global.unsigned_tx.output.len();
+        if output_count != 1 {
+            return Err(PsbtValidationError::InvalidOutputCount(output_count).into());
+        }
+
+        let input_count = psbt.global.unsigned_tx.input.len();
+        if input_count < 1 {
+            return Err(PsbtValidationError::InvalidInputCount(input_count).into());
+        }
+        for (txin, input) in psbt.global.unsigned_tx.input.iter().zip(psbt.inputs.iter()) {
+            // Deep Vault outputs are P2WSH, and we sign with ALL.
+            if input
+                .witness_utxo
+                .as_ref()
+                .map(|u| u.script_pubkey.is_v0_p2wsh())
+                != Some(true)
+            {
+                return Err(PsbtValidationError::InvalidPrevoutType(input.clone()).into());
+            }
+
+            // They may only be spent once the Emergency timelock matured. The witness Script is
+            // the last element of the witness once finalized.
+            let witness_script = match input.final_script_witness {
+                Some(ref witness) => witness.last().map(|script| Script::from(script.clone())),
+                None => input.witness_script.clone(),
+            };
+            if !witness_script
+                .as_ref()
+                .and_then(emergency_script_csv)
+                .map(|csv| is_matured_sequence(txin.sequence, csv))
+                .unwrap_or(false)
+            {
+                return Err(PsbtValidationError::InvalidSequence(
+                    txin.previous_output,
+                    txin.sequence,
+                )
+                .into());
+            }
+
+            if input.final_script_witness.is_some() {
+                continue;
+            }
+
+            if input.sighash_type != Some(SigHashType::All) {
+                return Err(PsbtValidationError::InvalidSighashType(input.clone()).into());
+            }
+        }
+
+        // Make sure it's not a Monster Tx once satisfied
+        let tx = DeepVaultRecoveryTransaction(psbt);
+        if tx.max_weight() > MAX_STANDARD_TX_WEIGHT as u64 {
+            return Err(PsbtValidationError::TransactionTooLarge.into());
+        }
+
+        Ok(tx)
+    }
+
+    /// Get the size of this transaction, assuming fully-satisfied inputs. If the transaction
+    /// is already finalized, returns the exact size in witness units. Otherwise computes the
+    /// maximum reasonable weight of a satisfaction.
+    pub fn max_weight(&self) -> u64 {
+        let psbt = self.psbt();
+        let tx = &psbt.global.unsigned_tx;
+
+        let mut weight: u64 = tx.get_weight().try_into().expect("Can't be >u64::MAX");
+        for txin in psbt.inputs.iter() {
+            let txin_weight: usize = if let Some(ref witness) = txin.final_script_witness {
+                witness.iter().map(|e| e.len()).sum()
+            } else {
+                utils::max_sat_weight(
+                    txin.witness_script
+                        .as_ref()
+                        .expect("Non-final Deep Vault txins always have a witness Script"),
+                )
+                .expect("Checked in the common sanity checks")
+            };
+            weight = weight
+                .checked_add(txin_weight.try_into().expect("Can't be >u64::MAX"))
+                .expect("Weight computation bug: overflow computing the transaction weight");
+        }
+
+        weight
+    }
+}
@@ -178,4 +178,14 @@ impl EmergencyTransaction {
             vout: 0,
         }
     }
+
+    /// Get the Deep Vault txin spending the Emergency UTXO, if the Emergency descriptor is known.
+    pub fn deep_vault_txin(&self, emergency_descriptor: &EmergencyDescriptor) -> DeepVaultTxIn {
+        let txo = &self.tx().output[0];
+        DeepVaultTxIn::new(
+            self.emergency_outpoint(),
+            DeepVaultTxOut::new(Amount::from_sat(txo.value), emergency_descriptor),
+            emergency_descriptor.csv_value(),
+        )
+    }
 }
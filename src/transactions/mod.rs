@@ -7,27 +7,32 @@
 
 use crate::{error::*, scripts::*, txins::*, txouts::*};
 use miniscript::bitcoin::{
-    consensus::encode::Encodable,
+    consensus::encode::{Encodable, VarInt},
     hash_types,
     hashes::Hash,
     secp256k1,
     util::{bip143::SigHashCache, bip32::ChildNumber, psbt::PartiallySignedTransaction as Psbt},
-    Address, Amount, Network, OutPoint, PublicKey as BitcoinPubKey, Script, SigHash, SigHashType,
-    Transaction, Txid, Wtxid,
+    Amount, OutPoint, PublicKey as BitcoinPubKey, Script, SigHash, SigHashType, Transaction, Txid,
+    Wtxid,
 };
 
-use std::fmt;
+use std::{collections::HashMap, fmt, io};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 #[macro_use]
 mod utils;
 
 mod cancel;
+mod deepvaultrecovery;
 mod emergency;
 mod spend;
 mod unvault;
 mod unvaultemergency;
 
 pub use cancel::CancelTransaction;
+pub use deepvaultrecovery::DeepVaultRecoveryTransaction;
 pub use emergency::EmergencyTransaction;
 pub use spend::SpendTransaction;
 pub use unvault::UnvaultTransaction;
@@ -129,9 +134,31 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
         }
     }
 
+    /// Get the sighash of all the inputs of a Revault transaction, for the sighash type set in
+    /// their PSBT input. The sighash cache is shared across inputs.
+    ///
+    /// Will error if an input was already finalized.
+    fn signature_hashes(&self) -> Result<Vec<SigHash>, InputSatisfactionError> {
+        let psbt = self.psbt();
+        let mut cache = SigHashCache::new(&psbt.global.unsigned_tx);
+
+        psbt.inputs
+            .iter()
+            .enumerate()
+            .map(|(input_index, psbtin)| {
+                let sighash_type = psbtin
+                    .sighash_type
+                    .ok_or(InputSatisfactionError::AlreadyFinalized)?;
+                self.signature_hash_cached(input_index, sighash_type, &mut cache)
+            })
+            .collect()
+    }
+
     /// Add a signature in order to eventually satisfy this input.
     ///
     /// Checks the signature according to the specified expected sighash type in the PSBT input.
+    /// Only ECDSA signatures over BIP143 sighashes are supported, Schnorr signatures are blocked
+    /// on our Miniscript and rust-bitcoin versions.
     ///
     /// The BIP174 Signer role.
     fn add_signature<C: secp256k1::Verification>(
@@ -147,43 +174,7 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
             .get(input_index)
             .ok_or(InputSatisfactionError::OutOfBounds)?;
 
-        // If we were already finalized, our witness script was wiped.
-        if psbtin.final_script_witness.is_some() {
-            return Err(InputSatisfactionError::AlreadyFinalized);
-        }
-
-        // BIP174:
-        // For a Signer to only produce valid signatures for what it expects to sign, it must
-        // check that the following conditions are true:
-        // -- If a witness UTXO is provided, no non-witness signature may be created.
-        let prev_txo = psbtin
-            .witness_utxo
-            .as_ref()
-            .expect("Cannot be reached. We only create transactions with witness_utxo.");
-        assert!(
-            psbtin.non_witness_utxo.is_none(),
-            "We never create transactions with non_witness_utxo."
-        );
-
-        // -- If a witnessScript is provided, the scriptPubKey or the redeemScript must be for
-        // that witnessScript
-        if let Some(witness_script) = &psbtin.witness_script {
-            // Note the network is irrelevant here.
-            let expected_script_pubkey =
-                Address::p2wsh(witness_script, Network::Bitcoin).script_pubkey();
-            assert!(
-                expected_script_pubkey == prev_txo.script_pubkey,
-                "We create TxOut scriptPubKey out of this exact witnessScript."
-            );
-        } else {
-            // We only use P2WSH utxos internally. External inputs are only ever added for fee
-            // bumping, for which we require P2WPKH.
-            assert!(prev_txo.script_pubkey.is_v0_p2wpkh());
-        }
-        assert!(
-            psbtin.redeem_script.is_none(),
-            "We never create Psbt input with legacy txos."
-        );
+        utils::signer_checks(psbtin)?;
 
         let expected_sighash_type = psbtin
             .sighash_type
@@ -208,6 +199,54 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
         Ok(psbtin.partial_sigs.insert(pubkey, rawsig))
     }
 
+    /// Add many (input index, public key, signature) at once, sharing the sighash cache across
+    /// inputs. Either all signatures are added, or none is.
+    ///
+    /// See [RevaultTransaction::add_signature].
+    fn add_signatures<C: secp256k1::Verification>(
+        &mut self,
+        signatures: &[(usize, secp256k1::PublicKey, secp256k1::Signature)],
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), InputSatisfactionError> {
+        let mut rawsigs = Vec::with_capacity(signatures.len());
+
+        let psbt = self.psbt();
+        let mut cache = SigHashCache::new(&psbt.global.unsigned_tx);
+        for (input_index, pubkey, signature) in signatures.iter().copied() {
+            let psbtin = psbt
+                .inputs
+                .get(input_index)
+                .ok_or(InputSatisfactionError::OutOfBounds)?;
+            utils::signer_checks(psbtin)?;
+
+            let expected_sighash_type = psbtin
+                .sighash_type
+                .expect("We always set the SigHashType in the constructor.");
+            let sighash =
+                self.signature_hash_cached(input_index, expected_sighash_type, &mut cache)?;
+            let sighash =
+                secp256k1::Message::from_slice(&sighash).expect("sighash is 32 a bytes hash");
+            secp.verify(&sighash, &signature, &pubkey).map_err(|_| {
+                InputSatisfactionError::InvalidSignature(signature, pubkey, sighash)
+            })?;
+
+            let mut rawsig = signature.serialize_der().to_vec();
+            rawsig.push(expected_sighash_type.as_u32() as u8);
+            let pubkey = BitcoinPubKey {
+                compressed: true,
+                key: pubkey,
+            };
+            rawsigs.push((input_index, pubkey, rawsig));
+        }
+
+        let psbt = self.psbt_mut();
+        for (input_index, pubkey, rawsig) in rawsigs {
+            psbt.inputs[input_index].partial_sigs.insert(pubkey, rawsig);
+        }
+
+        Ok(())
+    }
+
     /// Check and satisfy the scripts, create the witnesses.
     ///
     /// The BIP174 Input Finalizer role.
@@ -231,10 +270,13 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
     }
 
     /// Check the transaction is valid (fully-signed) and can be finalized.
-    /// Slighty more efficient than calling [RevaultTransaction::finalize] on a clone as it gets
-    /// rid of the belt-and-suspenders checks.
+    /// More efficient than calling [RevaultTransaction::finalize] on a clone as it gets
+    /// rid of the belt-and-suspenders checks, and only creates the witnesses to check them.
     fn is_finalizable(&self, ctx: &secp256k1::Secp256k1<impl secp256k1::Verification>) -> bool {
-        miniscript::psbt::finalize(&mut self.psbt().clone(), ctx).is_ok()
+        let psbt = self.psbt();
+
+        psbt.inputs.len() == psbt.global.unsigned_tx.input.len()
+            && (0..psbt.inputs.len()).all(|i| utils::is_input_finalizable(psbt, i, ctx))
     }
 
     /// Check if the transaction was already finalized.
@@ -267,7 +309,7 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
 
     /// Verify all PSBT inputs against libbitcoinconsensus
     fn verify_inputs(&self) -> Result<(), Error> {
-        let ser_tx = self.clone().into_bitcoin_serialized();
+        let ser_tx = self.as_bitcoin_serialized();
 
         for (i, psbtin) in self.psbt().inputs.iter().enumerate() {
             let utxo = psbtin
@@ -296,6 +338,60 @@ pub trait RevaultTransaction: fmt::Debug + Clone + PartialEq {
         buf
     }
 
+    /// Get the network-serialized (inner) transaction, without consuming it.
+    ///
+    /// Same as [RevaultTransaction::into_bitcoin_serialized], but serializes the final scripts
+    /// from the PSBT inputs in place instead of extracting a copy of the transaction.
+    fn as_bitcoin_serialized(&self) -> Vec<u8> {
+        let psbt = self.psbt();
+        let tx = &psbt.global.unsigned_tx;
+        let empty_script = Script::new();
+        let empty_witness = Vec::new();
+        let mut buf = Vec::with_capacity(256);
+
+        // Follows rust-bitcoin's Transaction encoding
+        let have_witness = tx.input.is_empty()
+            || psbt.inputs.iter().any(|psbtin| {
+                psbtin
+                    .final_script_witness
+                    .as_ref()
+                    .map(|witness| !witness.is_empty())
+                    == Some(true)
+            });
+        (|| -> Result<(), io::Error> {
+            tx.version.consensus_encode(&mut buf)?;
+            if have_witness {
+                0u8.consensus_encode(&mut buf)?;
+                1u8.consensus_encode(&mut buf)?;
+            }
+            VarInt(tx.input.len() as u64).consensus_encode(&mut buf)?;
+            for (txin, psbtin) in tx.input.iter().zip(psbt.inputs.iter()) {
+                txin.previous_output.consensus_encode(&mut buf)?;
+                psbtin
+                    .final_script_sig
+                    .as_ref()
+                    .unwrap_or(&empty_script)
+                    .consensus_encode(&mut buf)?;
+                txin.sequence.consensus_encode(&mut buf)?;
+            }
+            tx.output.consensus_encode(&mut buf)?;
+            if have_witness {
+                for psbtin in psbt.inputs.iter() {
+                    psbtin
+                        .final_script_witness
+                        .as_ref()
+                        .unwrap_or(&empty_witness)
+                        .consensus_encode(&mut buf)?;
+                }
+            }
+            tx.lock_time.consensus_encode(&mut buf)?;
+            Ok(())
+        })()
+        .expect("We only create valid PSBT, serialization cannot fail");
+
+        buf
+    }
+
     /// Get the BIP174-serialized (inner) transaction.
     fn as_psbt_serialized(&self) -> Vec<u8> {
         let mut buff = Vec::with_capacity(256);
@@ -432,6 +528,51 @@ pub fn transaction_chain_manager<C: secp256k1::Verification>(
     Ok((unvault_tx, cancel_tx))
 }
 
+/// The entire chain of pre-signed transactions of a deposit.
+pub type TransactionChain = (
+    UnvaultTransaction,
+    CancelTransaction,
+    EmergencyTransaction,
+    UnvaultEmergencyTransaction,
+);
+
+// Get the entire chain of pre-signed transactions out of a deposit, given the descriptors
+// derived at its derivation index.
+pub(crate) fn transaction_chain_derived(
+    deposit_outpoint: OutPoint,
+    deposit_amount: Amount,
+    der_deposit_descriptor: &DerivedDepositDescriptor,
+    der_unvault_descriptor: &DerivedUnvaultDescriptor,
+    der_cpfp_descriptor: &DerivedCpfpDescriptor,
+    emer_address: EmergencyAddress,
+    lock_time: u32,
+) -> Result<TransactionChain, Error> {
+    let deposit_txin = DepositTxIn::new(
+        deposit_outpoint,
+        DepositTxOut::new(deposit_amount, der_deposit_descriptor),
+    );
+    let unvault_tx = UnvaultTransaction::new(
+        deposit_txin.clone(),
+        der_unvault_descriptor,
+        der_cpfp_descriptor,
+        lock_time,
+    )?;
+
+    let unvault_txin = unvault_tx.revault_unvault_txin(der_unvault_descriptor);
+    let cancel_tx = CancelTransaction::new(
+        unvault_txin.clone(),
+        None,
+        der_deposit_descriptor,
+        lock_time,
+    );
+    let emergency_tx =
+        EmergencyTransaction::new(deposit_txin, None, emer_address.clone(), lock_time)?;
+    let unvault_emergency_tx =
+        UnvaultEmergencyTransaction::new(unvault_txin, None, emer_address, lock_time);
+
+    Ok((unvault_tx, cancel_tx, emergency_tx, unvault_emergency_tx))
+}
+
 /// Get the entire chain of pre-signed transaction for this derivation index out of a deposit. No feebump input.
 #[allow(clippy::too_many_arguments)]
 pub fn transaction_chain<C: secp256k1::Verification>(
@@ -444,40 +585,78 @@ pub fn transaction_chain<C: secp256k1::Verification>(
     emer_address: EmergencyAddress,
     lock_time: u32,
     secp: &secp256k1::Secp256k1<C>,
-) -> Result<
-    (
-        UnvaultTransaction,
-        CancelTransaction,
-        EmergencyTransaction,
-        UnvaultEmergencyTransaction,
-    ),
-    Error,
-> {
-    let (unvault_tx, cancel_tx) = transaction_chain_manager(
+) -> Result<TransactionChain, Error> {
+    transaction_chain_derived(
         deposit_outpoint,
         deposit_amount,
-        deposit_descriptor,
-        unvault_descriptor,
-        cpfp_descriptor,
-        derivation_index,
+        &deposit_descriptor.derive(derivation_index, secp),
+        &unvault_descriptor.derive(derivation_index, secp),
+        &cpfp_descriptor.derive(derivation_index, secp),
+        emer_address,
         lock_time,
-        secp,
-    )?;
-
-    let der_deposit_descriptor = deposit_descriptor.derive(derivation_index, secp);
-    let deposit_txin = DepositTxIn::new(
-        deposit_outpoint,
-        DepositTxOut::new(deposit_amount, &der_deposit_descriptor),
-    );
-    let emergency_tx =
-        EmergencyTransaction::new(deposit_txin, None, emer_address.clone(), lock_time)?;
+    )
+}
 
-    let der_unvault_descriptor = unvault_descriptor.derive(derivation_index, secp);
-    let unvault_txin = unvault_tx.revault_unvault_txin(&der_unvault_descriptor);
-    let unvault_emergency_tx =
-        UnvaultEmergencyTransaction::new(unvault_txin, None, emer_address, lock_time);
+/// Get the entire chain of pre-signed transactions out of many deposits (outpoint, amount and
+/// derivation index), in the same order. No feebump input.
+///
+/// The descriptors are derived once per derivation index. With the `parallel` feature, the
+/// derivation and the creation of the transactions are spread over a thread pool.
+///
+/// Will error if the derivation index of a deposit is hardened, or if the transactions of a
+/// deposit could not be created.
+pub fn transaction_chains<C: secp256k1::Verification>(
+    deposits: &[(OutPoint, Amount, ChildNumber)],
+    deposit_descriptor: &DepositDescriptor,
+    unvault_descriptor: &UnvaultDescriptor,
+    cpfp_descriptor: &CpfpDescriptor,
+    emer_address: &EmergencyAddress,
+    lock_time: u32,
+    secp: &secp256k1::Secp256k1<C>,
+) -> Result<Vec<TransactionChain>, Error> {
+    let mut derivation_indexes: Vec<ChildNumber> =
+        deposits.iter().map(|(_, _, index)| *index).collect();
+    derivation_indexes.sort_unstable();
+    derivation_indexes.dedup();
+
+    let derive = |index: &ChildNumber| {
+        let derived = (|| -> Result<_, ScriptCreationError> {
+            Ok((
+                deposit_descriptor.try_derive(*index, secp)?,
+                unvault_descriptor.try_derive(*index, secp)?,
+                cpfp_descriptor.try_derive(*index, secp)?,
+            ))
+        })();
+        (*index, derived)
+    };
+    #[cfg(feature = "parallel")]
+    let derived_descriptors: HashMap<_, _> = derivation_indexes.par_iter().map(derive).collect();
+    #[cfg(not(feature = "parallel"))]
+    let derived_descriptors: HashMap<_, _> = derivation_indexes.iter().map(derive).collect();
+
+    let chain = |(outpoint, amount, index): &(OutPoint, Amount, ChildNumber)| {
+        let (der_deposit_descriptor, der_unvault_descriptor, der_cpfp_descriptor) =
+            match &derived_descriptors[index] {
+                Ok(derived) => derived,
+                // The descriptors can only fail to derive for a hardened derivation index
+                Err(_) => return Err(ScriptCreationError::HardenedDerivation.into()),
+            };
+        transaction_chain_derived(
+            *outpoint,
+            *amount,
+            der_deposit_descriptor,
+            der_unvault_descriptor,
+            der_cpfp_descriptor,
+            emer_address.clone(),
+            lock_time,
+        )
+    };
+    #[cfg(feature = "parallel")]
+    let chains = deposits.par_iter().map(chain).collect();
+    #[cfg(not(feature = "parallel"))]
+    let chains = deposits.iter().map(chain).collect();
 
-    Ok((unvault_tx, cancel_tx, emergency_tx, unvault_emergency_tx))
+    chains
 }
 
 /// Get a spend transaction out of a list of deposits and derivation indexes.
@@ -524,32 +703,27 @@ pub fn spend_tx_from_deposits<C: secp256k1::Verification>(
 #[cfg(test)]
 mod tests {
     use super::{
-        transaction_chain, CancelTransaction, DepositTransaction, EmergencyAddress,
-        EmergencyTransaction, FeeBumpTransaction, RevaultTransaction, SpendTransaction,
-        UnvaultEmergencyTransaction, UnvaultTransaction,
+        transaction_chain, transaction_chains, CancelTransaction, DeepVaultRecoveryTransaction,
+        DepositTransaction, EmergencyAddress, EmergencyTransaction, FeeBumpTransaction,
+        RevaultTransaction, SpendTransaction, UnvaultEmergencyTransaction, UnvaultTransaction,
     };
-    use crate::{error::*, scripts::*, txins::*, txouts::*};
+    use crate::{error::*, scripts::*, test_utils::get_random_privkey, txins::*, txouts::*};
 
-    use std::{iter::repeat_with, str::FromStr};
+    use std::str::FromStr;
 
     use miniscript::{
         bitcoin::{
             blockdata::constants::COIN_VALUE,
+            consensus::encode::serialize,
             secp256k1,
             util::{bip143::SigHashCache, bip32},
-            Address, Amount, Network, OutPoint, SigHash, SigHashType, Transaction, TxIn, TxOut,
+            Address, Amount, Network, OutPoint, Script, SigHash, SigHashType, Transaction, TxIn,
+            TxOut,
         },
         descriptor::{DescriptorPublicKey, DescriptorXKey, Wildcard},
         Descriptor, DescriptorTrait,
     };
 
-    fn get_random_privkey(rng: &mut fastrand::Rng) -> bip32::ExtendedPrivKey {
-        let rand_bytes: Vec<u8> = repeat_with(|| rng.u8(..)).take(64).collect();
-
-        bip32::ExtendedPrivKey::new_master(Network::Bitcoin, &rand_bytes)
-            .unwrap_or_else(|_| get_random_privkey(rng))
-    }
-
     /// This generates the master private keys to derive directly from master, so it's
     /// [None]<xpub_goes_here>m/* descriptor pubkeys
     fn get_participants_sets(
@@ -615,6 +789,37 @@ mod tests {
         )
     }
 
+    // The checks done on a borrowed transaction must give the same result as on a clone
+    fn check_borrowed_checks(
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+        tx: &impl RevaultTransaction,
+    ) {
+        assert_eq!(
+            tx.is_finalizable(secp),
+            miniscript::psbt::finalize(&mut tx.psbt().clone(), secp).is_ok()
+        );
+        assert_eq!(
+            tx.as_bitcoin_serialized(),
+            tx.clone().into_bitcoin_serialized()
+        );
+    }
+
+    // Same as check_borrowed_checks, for every possible sighash flag of the signatures
+    fn check_borrowed_checks_sighash_flags(
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+        tx: &impl RevaultTransaction,
+    ) {
+        for flag in 0..=u8::MAX {
+            let mut tx = tx.clone();
+            for psbtin in tx.psbt_mut().inputs.iter_mut() {
+                for rawsig in psbtin.partial_sigs.values_mut() {
+                    *rawsig.last_mut().unwrap() = flag;
+                }
+            }
+            check_borrowed_checks(secp, &tx);
+        }
+    }
+
     // Routine for ""signing"" a transaction
     fn satisfy_transaction_input(
         secp: &secp256k1::Secp256k1<secp256k1::All>,
@@ -673,35 +878,87 @@ mod tests {
 
         // Test the dust limit
         assert_eq!(
-            derive_transactions(2, 1, csv, 234_631, &secp)
+            derive_transactions(2, 1, csv, 234_631, None, None, &secp)
                 .unwrap_err()
                 .to_string(),
             Error::TransactionCreation(TransactionCreationError::Dust).to_string()
         );
         // Non-minimal CSV
-        derive_transactions(2, 1, SEQUENCE_LOCKTIME_MASK + 1, 300_000, &secp)
+        derive_transactions(2, 1, SEQUENCE_LOCKTIME_MASK + 1, 300_000, None, None, &secp)
             .expect_err("Unclean CSV");
 
         // Absolute minimum
-        derive_transactions(2, 1, csv, 234_632, &secp).expect(&format!(
+        derive_transactions(2, 1, csv, 234_632, None, None, &secp).expect(&format!(
             "Tx chain with 2 stakeholders, 1 manager, {} csv, 235_250 deposit",
             csv
         ));
         // 1 BTC
-        derive_transactions(8, 3, csv, COIN_VALUE, &secp).expect(&format!(
+        derive_transactions(8, 3, csv, COIN_VALUE, None, None, &secp).expect(&format!(
             "Tx chain with 8 stakeholders, 3 managers, {} csv, 1_000_000 deposit",
             csv
         ));
         // 100 000 BTC
-        derive_transactions(8, 3, csv, 100_000 * COIN_VALUE, &secp).expect(&format!(
+        derive_transactions(8, 3, csv, 100_000 * COIN_VALUE, None, None, &secp).expect(&format!(
             "Tx chain with 8 stakeholders, 3 managers, {} csv, 100_000_000_000_000 deposit",
             csv
         ));
         // 100 BTC
-        derive_transactions(38, 5, csv, 100 * COIN_VALUE, &secp).expect(&format!(
+        derive_transactions(38, 5, csv, 100 * COIN_VALUE, None, None, &secp).expect(&format!(
             "Tx chain with 38 stakeholders, 5 manager, {} csv, 100_000_000_000 deposit",
             csv
         ));
+
+        // With a recovery path in the deposit descriptor, the chain is unchanged
+        derive_transactions(2, 1, csv, COIN_VALUE, Some((1, 4032)), None, &secp).unwrap_or_else(
+            |_| {
+                panic!(
+                    "Tx chain with 2 stakeholders, 1 manager, {} csv, 1-of-2 recovery deposit",
+                    csv
+                )
+            },
+        );
+        derive_transactions(8, 3, csv, COIN_VALUE, Some((5, 52_560)), None, &secp).unwrap_or_else(
+            |_| {
+                panic!(
+                    "Tx chain with 8 stakeholders, 3 managers, {} csv, 5-of-8 recovery deposit",
+                    csv
+                )
+            },
+        );
+
+        // With only a subset of the stakeholders or the cosigners required in the Unvault
+        derive_transactions(3, 2, csv, COIN_VALUE, None, Some((2, None)), &secp).unwrap_or_else(
+            |_| {
+                panic!(
+                    "Tx chain with 3 stakeholders, 2 managers, {} csv, 2-of-3 revocation",
+                    csv
+                )
+            },
+        );
+        derive_transactions(8, 3, csv, COIN_VALUE, None, Some((5, Some(6))), &secp)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Tx chain with 8 stakeholders, 3 managers, {} csv, 5-of-8 revocation, 6-of-8 cosigners",
+                    csv
+                )
+            });
+
+        // Without cosigning servers
+        derive_transactions(3, 2, csv, COIN_VALUE, None, Some((3, Some(0))), &secp).unwrap_or_else(
+            |_| {
+                panic!(
+                    "Tx chain with 3 stakeholders, 2 managers, {} csv, no cosigner",
+                    csv
+                )
+            },
+        );
+        derive_transactions(4, 3, csv, COIN_VALUE, None, Some((3, Some(0))), &secp)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Tx chain with 4 stakeholders, 3 managers, {} csv, 3-of-4 revocation, no cosigner",
+                    csv
+                )
+            });
     }
 
     fn derive_transactions(
@@ -709,6 +966,8 @@ mod tests {
         n_man: usize,
         csv: u32,
         deposit_value: u64,
+        deposit_recovery: Option<(usize, u32)>,
+        unvault_thresholds: Option<(usize, Option<usize>)>,
         secp: &secp256k1::Secp256k1<secp256k1::All>,
     ) -> Result<(), Error> {
         // Let's get the 10th key of each
@@ -722,27 +981,83 @@ mod tests {
         ) = get_participants_sets(n_stk, n_man, secp);
 
         // Get the script descriptors for the txos we're going to create
-        let unvault_descriptor = UnvaultDescriptor::new(
-            stakeholders.clone(),
-            managers.clone(),
-            managers.len(),
-            cosigners.clone(),
-            csv,
-        )?;
+        let (stakeholders_threshold, cosigners_threshold) =
+            unvault_thresholds.unwrap_or((stakeholders.len(), None));
+        // A cosigners threshold of 0 means a deployment without cosigning servers
+        let (cosigners_priv, cosigners) = if cosigners_threshold == Some(0) {
+            (vec![], vec![])
+        } else {
+            (cosigners_priv, cosigners)
+        };
+        let unvault_descriptor = if unvault_thresholds.is_some() {
+            UnvaultDescriptor::new_with_thresholds(
+                stakeholders.clone(),
+                stakeholders_threshold,
+                managers.clone(),
+                managers.len(),
+                cosigners.clone(),
+                cosigners_threshold,
+                csv,
+            )?
+        } else {
+            UnvaultDescriptor::new(
+                stakeholders.clone(),
+                managers.clone(),
+                managers.len(),
+                cosigners.clone(),
+                csv,
+            )?
+        };
+        assert_eq!(
+            unvault_descriptor.stakeholders_threshold(),
+            stakeholders_threshold
+        );
+        assert_eq!(
+            unvault_descriptor.cosigners_threshold(),
+            cosigners_threshold.unwrap_or(cosigners.len())
+        );
         assert_eq!(unvault_descriptor.csv_value(), csv);
         let cpfp_descriptor =
             CpfpDescriptor::new(managers).expect("Unvault CPFP descriptor generation error");
-        let deposit_descriptor =
-            DepositDescriptor::new(stakeholders).expect("Deposit descriptor generation error");
+        let deposit_descriptor = match deposit_recovery {
+            Some((recovery_threshold, recovery_csv)) => {
+                // Another derivation of the stakeholders' xpubs
+                let recovery_keys = stakeholders
+                    .iter()
+                    .map(|xpub| match xpub {
+                        DescriptorPublicKey::XPub(xpub) => {
+                            DescriptorPublicKey::XPub(DescriptorXKey {
+                                derivation_path: bip32::DerivationPath::from(vec![
+                                    bip32::ChildNumber::from(1),
+                                ]),
+                                ..xpub.clone()
+                            })
+                        }
+                        DescriptorPublicKey::SinglePub(_) => unreachable!("Stakeholders use xpubs"),
+                    })
+                    .collect();
+                DepositDescriptor::new_with_recovery(
+                    stakeholders,
+                    recovery_keys,
+                    recovery_threshold,
+                    recovery_csv,
+                )
+            }
+            None => DepositDescriptor::new(stakeholders),
+        }
+        .expect("Deposit descriptor generation error");
 
         // We reuse the deposit descriptor for the emergency address
-        let emergency_address = EmergencyAddress::from(Address::p2wsh(
-            &deposit_descriptor
-                .derive(child_number, secp)
-                .inner()
-                .explicit_script(),
+        let emergency_address = EmergencyAddress::from_network(
+            Address::p2wsh(
+                &deposit_descriptor
+                    .derive(child_number, secp)
+                    .inner()
+                    .explicit_script(),
+                Network::Bitcoin,
+            ),
             Network::Bitcoin,
-        ))
+        )
         .expect("It's a P2WSH");
 
         let der_deposit_descriptor = deposit_descriptor.derive(child_number, secp);
@@ -794,6 +1109,80 @@ mod tests {
             secp,
         )?;
 
+        // Creating them in bulk gives the same transactions, in the same order
+        let deposits = vec![
+            (
+                deposit_outpoint,
+                Amount::from_sat(deposit_txo.txout().value),
+                child_number,
+            ),
+            (
+                OutPoint::new(deposit_outpoint.txid, 1),
+                Amount::from_sat(deposit_txo.txout().value * 2),
+                bip32::ChildNumber::from(3),
+            ),
+            (
+                OutPoint::new(deposit_outpoint.txid, 2),
+                Amount::from_sat(deposit_txo.txout().value),
+                child_number,
+            ),
+        ];
+        let chains = transaction_chains(
+            &deposits,
+            &deposit_descriptor,
+            &unvault_descriptor,
+            &cpfp_descriptor,
+            &emergency_address,
+            0,
+            secp,
+        )?;
+        assert_eq!(chains.len(), deposits.len());
+        assert_eq!(
+            chains[0],
+            (
+                h_unvault.clone(),
+                h_cancel.clone(),
+                h_emer.clone(),
+                h_unemer.clone()
+            )
+        );
+        for ((outpoint, amount, index), chain) in deposits.into_iter().zip(chains) {
+            assert_eq!(
+                chain,
+                transaction_chain(
+                    outpoint,
+                    amount,
+                    &deposit_descriptor,
+                    &unvault_descriptor,
+                    &cpfp_descriptor,
+                    index,
+                    emergency_address.clone(),
+                    0,
+                    secp,
+                )?
+            );
+        }
+        // A hardened derivation index errors instead of panicking
+        let hardened_deposits = [(
+            deposit_outpoint,
+            Amount::from_sat(deposit_txo.txout().value),
+            bip32::ChildNumber::from_hardened_idx(3).unwrap(),
+        )];
+        assert_eq!(
+            transaction_chains(
+                &hardened_deposits,
+                &deposit_descriptor,
+                &unvault_descriptor,
+                &cpfp_descriptor,
+                &emergency_address,
+                0,
+                secp,
+            )
+            .unwrap_err()
+            .to_string(),
+            Error::from(ScriptCreationError::HardenedDerivation).to_string()
+        );
+
         // The fee-bumping utxo, used in revaulting transactions inputs to bump their feerate.
         // We simulate a wallet utxo.
         let mut rng = fastrand::Rng::new();
@@ -1024,7 +1413,12 @@ mod tests {
             &vec![feebump_xpriv],
             None, // No derivation path for the feebump key
         )?;
+        check_borrowed_checks(secp, &cancel_tx);
+        check_borrowed_checks_sighash_flags(secp, &cancel_tx);
+        assert!(cancel_tx.is_finalizable(secp));
         cancel_tx.finalize(&secp)?;
+        check_borrowed_checks(secp, &cancel_tx);
+        assert!(!cancel_tx.is_finalizable(secp));
 
         // We can create it without the feebump input
         let mut unemergency_tx_no_feebump = UnvaultEmergencyTransaction::new(
@@ -1090,6 +1484,8 @@ mod tests {
             Some(child_number),
         )?;
         // We don't have satisfied the feebump input yet!
+        check_borrowed_checks(secp, &unemergency_tx);
+        assert!(!unemergency_tx.is_finalizable(secp));
         // Note that we clone because Miniscript's finalize() will wipe the PSBT input..
         match unemergency_tx.clone().finalize(&secp) {
             Err(e) => assert!(
@@ -1131,7 +1527,10 @@ mod tests {
 
         // Create and sign a spend transaction
         let spend_unvault_txin = unvault_tx.spend_unvault_txin(&der_unvault_descriptor);
-        let dummy_txo = TxOut::default();
+        let dummy_txo = TxOut {
+            script_pubkey: der_deposit_descriptor.inner().script_pubkey(),
+            ..TxOut::default()
+        };
         let cpfp_value = SpendTransaction::cpfp_txout(
             vec![spend_unvault_txin.clone()],
             vec![SpendTxOut::Destination(dummy_txo.clone())],
@@ -1144,7 +1543,7 @@ mod tests {
         let spend_txo = TxOut {
             // The CPFP output value won't be > 150k sats for our parameters
             value: spend_unvault_txin.txout().txout().value - cpfp_value - fees,
-            ..TxOut::default()
+            ..dummy_txo.clone()
         };
         let mut spend_tx = SpendTransaction::new(
             vec![spend_unvault_txin.clone()],
@@ -1173,11 +1572,11 @@ mod tests {
 
         // We can't create a dust output with the Spend
         let dust_txo = TxOut {
-            value: 470,
-            ..TxOut::default()
+            value: 329,
+            ..dummy_txo.clone()
         };
         SpendTransaction::new(
-            vec![spend_unvault_txin],
+            vec![spend_unvault_txin.clone()],
             vec![SpendTxOut::Destination(dust_txo.clone())],
             &der_cpfp_descriptor,
             0,
@@ -1185,6 +1584,24 @@ mod tests {
         )
         .expect_err("Creating a dust output");
 
+        // Nor one that wouldn't be relayed
+        let nonstandard_txo = TxOut {
+            script_pubkey: Script::new(),
+            ..spend_txo.clone()
+        };
+        assert_eq!(
+            SpendTransaction::new(
+                vec![spend_unvault_txin],
+                vec![SpendTxOut::Destination(nonstandard_txo)],
+                &der_cpfp_descriptor,
+                0,
+                true,
+            ),
+            Err(TransactionCreationError::NonStandard(
+                StandardnessError::NonStandardOutput(Script::new())
+            ))
+        );
+
         // The spend transaction can also batch multiple unvault txos
         let spend_unvault_txins = vec![
             UnvaultTxIn::new(
@@ -1223,8 +1640,28 @@ mod tests {
                 csv,
             ),
         ];
+        // The satisfaction weight carried by the txouts is the one of their witness Script
+        for txin in spend_unvault_txins.iter() {
+            let txout = txin.txout();
+            assert_eq!(
+                txout.max_sat_weight(),
+                miniscript::descriptor::Wsh::new(
+                    miniscript::Miniscript::parse(txout.witness_script()).unwrap()
+                )
+                .unwrap()
+                .max_satisfaction_weight()
+                .unwrap()
+            );
+            assert_eq!(
+                super::utils::max_sat_weight(txout.witness_script()),
+                Some(txout.max_sat_weight())
+            );
+        }
         let n_txins = spend_unvault_txins.len();
-        let dummy_txo = TxOut::default();
+        let dummy_txo = TxOut {
+            script_pubkey: der_deposit_descriptor.inner().script_pubkey(),
+            ..TxOut::default()
+        };
         let cpfp_value = SpendTransaction::cpfp_txout(
             spend_unvault_txins.clone(),
             vec![SpendTxOut::Destination(dummy_txo.clone())],
@@ -1241,7 +1678,7 @@ mod tests {
                 .sum::<u64>()
                 - cpfp_value
                 - fees,
-            ..TxOut::default()
+            ..dummy_txo.clone()
         };
         let mut spend_tx = SpendTransaction::new(
             spend_unvault_txins,
@@ -1252,6 +1689,12 @@ mod tests {
         )
         .expect("Amounts Ok");
         assert_eq!(spend_tx.fees(), fees);
+        assert_eq!(
+            SpendTransaction::from_psbt_serialized(&spend_tx.as_psbt_serialized())
+                .unwrap()
+                .max_weight(),
+            spend_tx.max_weight()
+        );
         let mut hash_cache = SigHashCache::new(spend_tx.tx());
         let sighashes: Vec<SigHash> = (0..n_txins)
             .into_iter()
@@ -1261,6 +1704,52 @@ mod tests {
                     .expect("Input exists")
             })
             .collect();
+        assert_eq!(spend_tx.signature_hashes().unwrap(), sighashes);
+
+        // Adding signatures in batch is all-or-nothing
+        let manager_key = managers_priv[0]
+            .derive_priv(secp, &[child_number])
+            .unwrap()
+            .private_key
+            .key;
+        let manager_pubkey = secp256k1::PublicKey::from_secret_key(secp, &manager_key);
+        let manager_sigs: Vec<_> = sighashes
+            .iter()
+            .map(|sighash| {
+                secp.sign(
+                    &secp256k1::Message::from_slice(sighash).unwrap(),
+                    &manager_key,
+                )
+            })
+            .collect();
+        let mut invalid_sigs: Vec<_> = manager_sigs
+            .iter()
+            .enumerate()
+            .map(|(i, sig)| (i, manager_pubkey, *sig))
+            .collect();
+        invalid_sigs.swap(0, 1);
+        invalid_sigs[0].0 = 0;
+        invalid_sigs[1].0 = 1;
+        spend_tx
+            .add_signatures(&invalid_sigs, secp)
+            .expect_err("Invalid signatures");
+        assert!(spend_tx
+            .psbt()
+            .inputs
+            .iter()
+            .all(|psbtin| psbtin.partial_sigs.is_empty()));
+        let valid_sigs: Vec<_> = manager_sigs
+            .into_iter()
+            .enumerate()
+            .map(|(i, sig)| (i, manager_pubkey, sig))
+            .collect();
+        spend_tx.add_signatures(&valid_sigs, secp).unwrap();
+        assert!(spend_tx
+            .psbt()
+            .inputs
+            .iter()
+            .all(|psbtin| psbtin.partial_sigs.len() == 1));
+
         for (i, spend_tx_sighash) in sighashes.into_iter().enumerate() {
             satisfy_transaction_input(
                 &secp,
@@ -1275,7 +1764,10 @@ mod tests {
                 Some(child_number),
             )?
         }
+        check_borrowed_checks(secp, &spend_tx);
+        assert!(spend_tx.is_finalizable(secp));
         spend_tx.finalize(&secp)?;
+        check_borrowed_checks(secp, &spend_tx);
 
         #[cfg(feature = "use-serde")]
         {
@@ -1297,11 +1789,272 @@ mod tests {
         Ok(())
     }
 
+    // Sign the first input with exactly the first `k` of `xprivs` and check it finalizes, then
+    // check it can't be finalized with only `k - 1` of them.
+    fn check_threshold_satisfaction(
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+        tx: &impl RevaultTransaction,
+        xprivs: &[bip32::ExtendedPrivKey],
+        k: usize,
+        child_number: bip32::ChildNumber,
+    ) {
+        let sighash = tx.signature_hashes().unwrap()[0];
+
+        let mut tx_k = tx.clone();
+        satisfy_transaction_input(
+            secp,
+            &mut tx_k,
+            0,
+            &sighash,
+            &xprivs[..k].to_vec(),
+            Some(child_number),
+        )
+        .unwrap();
+        check_borrowed_checks(secp, &tx_k);
+        check_borrowed_checks_sighash_flags(secp, &tx_k);
+        tx_k.finalize(secp).unwrap();
+        tx_k.verify_inputs().unwrap();
+
+        let mut tx_k_1 = tx.clone();
+        satisfy_transaction_input(
+            secp,
+            &mut tx_k_1,
+            0,
+            &sighash,
+            &xprivs[..k - 1].to_vec(),
+            Some(child_number),
+        )
+        .unwrap();
+        check_borrowed_checks(secp, &tx_k_1);
+        tx_k_1.finalize(secp).unwrap_err();
+    }
+
+    #[test]
+    fn unvault_thresholds_satisfaction() {
+        let secp = secp256k1::Secp256k1::new();
+        let child_number = bip32::ChildNumber::from(3);
+        let csv = 144;
+
+        let (
+            (managers_priv, managers),
+            (stakeholders_priv, stakeholders),
+            (cosigners_priv, cosigners),
+        ) = get_participants_sets(4, 3, &secp);
+        let unvault_descriptor = UnvaultDescriptor::new_with_thresholds(
+            stakeholders.clone(),
+            3,
+            managers.clone(),
+            2,
+            cosigners,
+            Some(2),
+            csv,
+        )
+        .unwrap();
+        let deposit_descriptor = DepositDescriptor::new(stakeholders).unwrap();
+        let cpfp_descriptor = CpfpDescriptor::new(managers).unwrap();
+        let emergency_address = EmergencyAddress::from_network(
+            Address::p2wsh(
+                &deposit_descriptor
+                    .derive(child_number, &secp)
+                    .inner()
+                    .explicit_script(),
+                Network::Bitcoin,
+            ),
+            Network::Bitcoin,
+        )
+        .unwrap();
+        let (unvault_tx, cancel_tx, _, unvault_emer_tx) = transaction_chain(
+            OutPoint::from_str(
+                "39a8212c6a9b467680d43e47b61b8363fe1febb761f9f548eb4a432b2bc9bbec:0",
+            )
+            .unwrap(),
+            Amount::from_sat(COIN_VALUE),
+            &deposit_descriptor,
+            &unvault_descriptor,
+            &cpfp_descriptor,
+            child_number,
+            emergency_address,
+            0,
+            &secp,
+        )
+        .unwrap();
+
+        // 3 of the 4 stakeholders can revoke, but not 2 of them
+        check_threshold_satisfaction(&secp, &cancel_tx, &stakeholders_priv, 3, child_number);
+        check_threshold_satisfaction(&secp, &unvault_emer_tx, &stakeholders_priv, 3, child_number);
+
+        // 2 of the 3 managers can spend along with 2 of the 4 cosigners, but not with only 1
+        let der_unvault_descriptor = unvault_descriptor.derive(child_number, &secp);
+        let der_cpfp_descriptor = cpfp_descriptor.derive(child_number, &secp);
+        let spend_txin = unvault_tx.spend_unvault_txin(&der_unvault_descriptor);
+        let spend_txo = TxOut {
+            value: spend_txin.txout().txout().value - 200_000,
+            script_pubkey: Address::from_str("bc1qw3w0nt60tzh4xqdhx7hmf5uh0nczxhcr8lt7ec")
+                .unwrap()
+                .script_pubkey(),
+        };
+        let mut spend_tx = SpendTransaction::new(
+            vec![spend_txin],
+            vec![SpendTxOut::Destination(spend_txo)],
+            &der_cpfp_descriptor,
+            0,
+            true,
+        )
+        .unwrap();
+        let spend_sighash = spend_tx.signature_hashes().unwrap()[0];
+        satisfy_transaction_input(
+            &secp,
+            &mut spend_tx,
+            0,
+            &spend_sighash,
+            &managers_priv[..2].to_vec(),
+            Some(child_number),
+        )
+        .unwrap();
+        check_threshold_satisfaction(&secp, &spend_tx, &cosigners_priv, 2, child_number);
+    }
+
+    #[test]
+    fn deep_vault_recovery() {
+        let secp = secp256k1::Secp256k1::new();
+        let child_number = bip32::ChildNumber::from(10);
+        let csv = fastrand::u32(1..SEQUENCE_LOCKTIME_MASK);
+
+        let (_, (stakeholders_priv, stakeholders), _) = get_participants_sets(4, 1, &secp);
+        let deposit_descriptor = DepositDescriptor::new(stakeholders.clone()).unwrap();
+        let der_deposit_descriptor = deposit_descriptor.derive(child_number, &secp);
+        let emergency_descriptor = EmergencyDescriptor::new(
+            stakeholders
+                .into_iter()
+                .map(|xpub| xpub.derive(child_number.into()).derive_public_key(&secp))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            csv,
+        )
+        .unwrap();
+        assert_eq!(emergency_descriptor.csv_value(), csv);
+        let emergency_address = emergency_descriptor.emergency_address(Network::Bitcoin);
+
+        // Two vaults were sent to the Deep Vault
+        let deep_vault_txins: Vec<DeepVaultTxIn> = (0..2)
+            .map(|i| {
+                let deposit_txin = DepositTxIn::new(
+                    OutPoint::from_str(&format!(
+                        "39a8212c6a9b467680d43e47b61b8363fe1febb761f9f548eb4a432b2bc9bbec:{}",
+                        i
+                    ))
+                    .unwrap(),
+                    DepositTxOut::new(Amount::from_sat(COIN_VALUE), &der_deposit_descriptor),
+                );
+                let emer_tx =
+                    EmergencyTransaction::new(deposit_txin, None, emergency_address.clone(), 0)
+                        .unwrap();
+                let txin = emer_tx.deep_vault_txin(&emergency_descriptor);
+                assert_eq!(txin.outpoint(), emer_tx.emergency_outpoint());
+                assert_eq!(
+                    txin.txout().txout().script_pubkey,
+                    emergency_address.address().script_pubkey()
+                );
+                txin
+            })
+            .collect();
+
+        let destination = Address::from_str("bc1qw3w0nt60tzh4xqdhx7hmf5uh0nczxhcr8lt7ec").unwrap();
+        // Can't pay more fees than it spends
+        assert_eq!(
+            DeepVaultRecoveryTransaction::new(
+                deep_vault_txins.clone(),
+                destination.clone(),
+                COIN_VALUE,
+                0
+            ),
+            Err(TransactionCreationError::InsaneFees)
+        );
+        let mut recovery_tx =
+            DeepVaultRecoveryTransaction::new(deep_vault_txins.clone(), destination, 10, 0)
+                .unwrap();
+        assert_eq!(recovery_tx.fees(), recovery_tx.max_weight() * 10);
+
+        for i in 0..deep_vault_txins.len() {
+            let sighash = recovery_tx.signature_hash(i, SigHashType::All).unwrap();
+            satisfy_transaction_input(
+                &secp,
+                &mut recovery_tx,
+                i,
+                &sighash,
+                &stakeholders_priv,
+                Some(child_number),
+            )
+            .unwrap();
+        }
+        let psbt_str = recovery_tx.as_psbt_string();
+        assert_eq!(
+            DeepVaultRecoveryTransaction::from_psbt_str(&psbt_str).unwrap(),
+            recovery_tx
+        );
+        recovery_tx.finalize(&secp).unwrap();
+        assert!(recovery_tx.max_weight() <= recovery_tx.fees() / 10);
+
+        // The timelock must have matured
+        let immature_txins: Vec<DeepVaultTxIn> = deep_vault_txins
+            .iter()
+            .cloned()
+            .map(|txin| DeepVaultTxIn::new(txin.outpoint(), txin.into_txout(), csv - 1))
+            .collect();
+        assert_eq!(
+            DeepVaultRecoveryTransaction::new(
+                immature_txins.clone(),
+                Address::from_str("bc1qw3w0nt60tzh4xqdhx7hmf5uh0nczxhcr8lt7ec").unwrap(),
+                10,
+                0,
+            ),
+            Err(TransactionCreationError::InvalidSequence(
+                immature_txins[0].outpoint(),
+                csv - 1
+            ))
+        );
+        // Also when the timelock is disabled or not in blocks
+        for flag in &[1 << 31, 1 << 22] {
+            let txins = deep_vault_txins
+                .iter()
+                .cloned()
+                .map(|txin| DeepVaultTxIn::new(txin.outpoint(), txin.into_txout(), csv | flag))
+                .collect();
+            DeepVaultRecoveryTransaction::new(
+                txins,
+                Address::from_str("bc1qw3w0nt60tzh4xqdhx7hmf5uh0nczxhcr8lt7ec").unwrap(),
+                10,
+                0,
+            )
+            .unwrap_err();
+        }
+        // Same when parsing, whether the input is finalized or not
+        let mut immature_psbt = recovery_tx.psbt().clone();
+        immature_psbt.global.unsigned_tx.input[1].sequence = csv - 1;
+        assert_eq!(
+            DeepVaultRecoveryTransaction::from_psbt_serialized(&serialize(&immature_psbt)),
+            Err(TransactionSerialisationError::from(
+                PsbtValidationError::InvalidSequence(immature_txins[1].outpoint(), csv - 1)
+            ))
+        );
+        let mut unfinalized_psbt = immature_psbt.clone();
+        unfinalized_psbt.inputs[1].final_script_witness = None;
+        unfinalized_psbt.inputs[1].witness_script =
+            Some(deep_vault_txins[1].txout().witness_script().clone());
+        unfinalized_psbt.inputs[1].sighash_type = Some(SigHashType::All);
+        DeepVaultRecoveryTransaction::from_psbt_serialized(&serialize(&unfinalized_psbt))
+            .unwrap_err();
+    }
+
     // Small sanity checks, see fuzzing targets for more.
     #[cfg(feature = "use-serde")]
     #[test]
     fn test_deserialize_psbt() {
-        use crate::bitcoin::consensus::encode::serialize_hex;
+        use crate::bitcoin::{
+            consensus::encode::{deserialize, serialize, serialize_hex},
+            util::psbt::PartiallySignedTransaction as Psbt,
+            PublicKey,
+        };
 
         let emergency_psbt_str = "\"cHNidP8BAIcCAAAAAuEAZNxAy8+vO2xoZFvsBYlgw6wk5hMFlx2QfdJAB5dwAAAAAAD9////RpNyUTczj4LUHy4abwuVEH/ha2LhNEkhCljpi+DXvV4AAAAAAP3///8B92ADAAAAAAAiACB0FMmRlU42BMGHgxBjusio4tqifT6ICZ4n3kLt+3y8aAAAAAAAAQErh5QDAAAAAAAiACB0FMmRlU42BMGHgxBjusio4tqifT6ICZ4n3kLt+3y8aCICAtWJr8yKNegqMu9EXe0itf+ZHUpXnhy3kfQeJhP2ofJvSDBFAiEAze1vfVVe1iXV5BZRn4g2bVAmmIoT8nBIzzwxY5yC7eICIEtOnT/7Fw8mS08BbWW19gsTYZzFEBLmJi16OY7DLUPsgSICAg8j1MWiUjZfCK95R07epNukSEsiq1dD/LUlYdW6UArSSDBFAiEArazAnifYyQiE520TFE+qVHrRhtQIhhkJVZ01Aw4OEvUCIEuqzr2McD3zGnEc/yiv1oT1HAuPj0SMIAbk+qgQbHGLgQEDBIEAAAABBUdSIQIPI9TFolI2XwiveUdO3qTbpEhLIqtXQ/y1JWHVulAK0iEC1YmvzIo16Coy70Rd7SK1/5kdSleeHLeR9B4mE/ah8m9SrgABAR+a3QAAAAAAABYAFB5/7V9SvO31sHrYLQ+kuyZaMDkXIgIC5AXAiBkRjiyCnRA7ERx5zxHpEf0/DmrWiF9CstSuJeFIMEUCIQCQ/tFT2iK7rAl57tiXidM7JJ+TVx1FXg4Vu+4EJp5bSwIgOnfEV+xO59P7DJvvEue7qSRDNTGpzRQwwsP5yokME9YBAQMEAQAAAAAA\"";
         let emergency_tx: EmergencyTransaction = serde_json::from_str(&emergency_psbt_str).unwrap();
@@ -1321,7 +2074,43 @@ mod tests {
         assert_eq!(serialize_hex(unemergency_tx.tx()), "02000000023ca9946a73c2437f73f27be48e0a5e3ed3d08c5d85b805bc3e6d5380659eeeca0000000000fdffffffbabf64948c063da00969c691423669a93f4e6ecee5b246bf50c21840d207d2b70000000000fdffffff01d2ca02000000000022002093c229bd08f511591d6d547d30e18da1b669b512187796adafd465e1acd52b2700000000");
 
         let spend_psbt_str = "\"cHNidP8BAOICAAAABCqeuW7WKzo1iD/mMt74WOi4DJRupF8Ys2QTjf4U3NcOAAAAAABe0AAAOjPsA68jDPWuRjwrZF8AN1O/sG2oB7AriUKJMsrPqiMBAAAAAF7QAAAdmwWqMhBuu2zxKu+hEVxUG2GEeql4I6BL5Ld3QL/K/AAAAAAAXtAAAOEKg+2uhHsUgQDxZt3WVCjfgjKELfnCbE7VhDEwBNxxAAAAAABe0AAAAgBvAgAAAAAAIgAgKjuiJEE1EeX8hEfJEB1Hfi+V23ETrp/KCx74SqwSLGBc9sMAAAAAAAAAAAAAAAEBK4iUAwAAAAAAIgAgRAzbIqFTxU8vRmZJTINVkIFqQsv6nWgsBrqsPSo3yg4BCP2IAQUASDBFAiEAo2IX4SPeqXGdu8cEB13BkfCDk1N+kf8mMOrwx6uJZ3gCIHYEspD4EUjt+PM8D4T5qtE5GjUT56aH9yEmf8SCR63eAUcwRAIgVdpttzz0rxS/gpSTPcG3OIQcLWrTcSFc6vthcBrBTZQCIDYm952TZ644IEETblK7N434NrFql7ccFTM7+jUj+9unAUgwRQIhALKhtFWbyicZtKuqfBcjKfl7GY1e2i2UTSS2hMtCKRIyAiA410YD546ONeAq2+CPk86Q1dQHUIRj+OQl3dmKvo/aFwGrIQPazx7E2MqqusRekjfgnWmq3OG4lF3MR3b+c/ufTDH3pKxRh2R2qRRZT2zQxRaHYRlox31j9A8EIu4mroisa3apFH7IHjHORqjFOYgmE+5URE+rT+iiiKxsk1KHZ1IhAr+ZWb/U4iUT5Vu1kF7zoqKfn5JK2wDGJ/0dkrZ/+c+UIQL+mr8QPqouEYAyh3QmEVU4Dv9BaheeYbCkvpmryviNm1KvA17QALJoAAEBKyBSDgAAAAAAIgAgRAzbIqFTxU8vRmZJTINVkIFqQsv6nWgsBrqsPSo3yg4BCP2GAQUARzBEAiAZR0TO1PRje6KzUb0lYmMuk6DjnMCHcCUU/Ct/otpMCgIgcAgD7H5oGx6jG2RjcRkS3HC617v1C58+BjyUKowb/nIBRzBEAiAhYwZTODb8zAjwfNjt5wL37yg1OZQ9wQuTV2iS7YByFwIgGb008oD3RXgzE3exXLDzGE0wst24ft15oLxj2xeqcmsBRzBEAiA6JMEwOeGlq92NItxEA2tBW5akps9EkUX1vMiaSM8yrwIgUsaiU94sOOQf/5zxb0hpp44HU17FgGov8/mFy3mT++IBqyED2s8exNjKqrrEXpI34J1pqtzhuJRdzEd2/nP7n0wx96SsUYdkdqkUWU9s0MUWh2EZaMd9Y/QPBCLuJq6IrGt2qRR+yB4xzkaoxTmIJhPuVERPq0/oooisbJNSh2dSIQK/mVm/1OIlE+VbtZBe86Kin5+SStsAxif9HZK2f/nPlCEC/pq/ED6qLhGAMod0JhFVOA7/QWoXnmGwpL6Zq8r4jZtSrwNe0ACyaAABAStEygEAAAAAACIAIEQM2yKhU8VPL0ZmSUyDVZCBakLL+p1oLAa6rD0qN8oOAQj9iAEFAEgwRQIhAL6mDIPbQZc8Y51CzTUl7+grFUVr+6CpBPt3zLio4FTLAiBkmNSnd8VvlD84jrDx12Xug5XRwueBSG0N1PBwCtyPCQFHMEQCIFLryPMdlr0XLySRzYWw75tKofJAjhhXgc1XpVDXtPRjAiBp+eeNA5Zl1aU8E3UtFxnlZ5KMRlIZpkqn7lvIlXi0rQFIMEUCIQCym/dSaqtfrTb3fs1ig1KvwS0AwyoHR62R3WGq52fk0gIgI/DAQO6EyvZT1UHYtfGsZHLlIZkFYRLZnTpznle/qsUBqyED2s8exNjKqrrEXpI34J1pqtzhuJRdzEd2/nP7n0wx96SsUYdkdqkUWU9s0MUWh2EZaMd9Y/QPBCLuJq6IrGt2qRR+yB4xzkaoxTmIJhPuVERPq0/oooisbJNSh2dSIQK/mVm/1OIlE+VbtZBe86Kin5+SStsAxif9HZK2f/nPlCEC/pq/ED6qLhGAMod0JhFVOA7/QWoXnmGwpL6Zq8r4jZtSrwNe0ACyaAABASuQArMAAAAAACIAIEQM2yKhU8VPL0ZmSUyDVZCBakLL+p1oLAa6rD0qN8oOAQj9iQEFAEgwRQIhAK8fSyw0VbBElw6L9iyedbSz6HtbrHrzs+M6EB4+6+1yAiBMN3s3ZKff7Msvgq8yfrI9v0CK5IKEoacgb0PcBKCzlwFIMEUCIQDyIe5RXWOu8PJ1Rbc2Nn0NGuPORDO4gYaGWH3swEixzAIgU2/ft0cNzSjbgT0O/MKss2Sk0e7OevzclRBSWZP3SHQBSDBFAiEA+spp4ejHuWnwymZqNYaTtrrFC5wCw3ItwtJ6DMxmRWMCIAbOYDm/yuiijXSz1YTDdyO0Zpg6TAzLY1kd90GFhQpRAashA9rPHsTYyqq6xF6SN+Cdaarc4biUXcxHdv5z+59MMfekrFGHZHapFFlPbNDFFodhGWjHfWP0DwQi7iauiKxrdqkUfsgeMc5GqMU5iCYT7lRET6tP6KKIrGyTUodnUiECv5lZv9TiJRPlW7WQXvOiop+fkkrbAMYn/R2Stn/5z5QhAv6avxA+qi4RgDKHdCYRVTgO/0FqF55hsKS+mavK+I2bUq8DXtAAsmgAAQElIQPazx7E2MqqusRekjfgnWmq3OG4lF3MR3b+c/ufTDH3pKxRhwAA\"";
-        let spend_tx: SpendTransaction = serde_json::from_str(&spend_psbt_str).unwrap();
-        assert_eq!(serialize_hex(&spend_tx.into_tx()), "020000000001042a9eb96ed62b3a35883fe632def858e8b80c946ea45f18b364138dfe14dcd70e00000000005ed000003a33ec03af230cf5ae463c2b645f003753bfb06da807b02b89428932cacfaa2301000000005ed000001d9b05aa32106ebb6cf12aefa1115c541b61847aa97823a04be4b77740bfcafc00000000005ed00000e10a83edae847b148100f166ddd65428df8232842df9c26c4ed584313004dc7100000000005ed0000002006f0200000000002200202a3ba224413511e5fc8447c9101d477e2f95db7113ae9fca0b1ef84aac122c605cf6c30000000000000500483045022100a36217e123dea9719dbbc704075dc191f08393537e91ff2630eaf0c7ab89677802207604b290f81148edf8f33c0f84f9aad1391a3513e7a687f721267fc48247adde01473044022055da6db73cf4af14bf8294933dc1b738841c2d6ad371215ceafb61701ac14d9402203626f79d9367ae382041136e52bb378df836b16a97b71c15333bfa3523fbdba701483045022100b2a1b4559bca2719b4abaa7c172329f97b198d5eda2d944d24b684cb42291232022038d74603e78e8e35e02adbe08f93ce90d5d407508463f8e425ddd98abe8fda1701ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b26805004730440220194744ced4f4637ba2b351bd2562632e93a0e39cc087702514fc2b7fa2da4c0a0220700803ec7e681b1ea31b6463711912dc70bad7bbf50b9f3e063c942a8c1bfe72014730440220216306533836fccc08f07cd8ede702f7ef283539943dc10b93576892ed807217022019bd34f280f74578331377b15cb0f3184d30b2ddb87edd79a0bc63db17aa726b0147304402203a24c13039e1a5abdd8d22dc44036b415b96a4a6cf449145f5bcc89a48cf32af022052c6a253de2c38e41fff9cf16f4869a78e07535ec5806a2ff3f985cb7993fbe201ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b2680500483045022100bea60c83db41973c639d42cd3525efe82b15456bfba0a904fb77ccb8a8e054cb02206498d4a777c56f943f388eb0f1d765ee8395d1c2e781486d0dd4f0700adc8f0901473044022052ebc8f31d96bd172f2491cd85b0ef9b4aa1f2408e185781cd57a550d7b4f463022069f9e78d039665d5a53c13752d1719e567928c465219a64aa7ee5bc89578b4ad01483045022100b29bf7526aab5fad36f77ecd628352afc12d00c32a0747ad91dd61aae767e4d2022023f0c040ee84caf653d541d8b5f1ac6472e52199056112d99d3a739e57bfaac501ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b2680500483045022100af1f4b2c3455b044970e8bf62c9e75b4b3e87b5bac7af3b3e33a101e3eebed7202204c377b3764a7dfeccb2f82af327eb23dbf408ae48284a1a7206f43dc04a0b39701483045022100f221ee515d63aef0f27545b736367d0d1ae3ce4433b8818686587decc048b1cc0220536fdfb7470dcd28db813d0efcc2acb364a4d1eece7afcdc9510525993f7487401483045022100faca69e1e8c7b969f0ca666a358693b6bac50b9c02c3722dc2d27a0ccc664563022006ce6039bfcae8a28d74b3d584c37723b466983a4c0ccb63591df74185850a5101ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b26800000000");
+        // This Spend pays to an empty scriptPubKey, it wouldn't be relayed
+        let raw_spend_psbt = base64::decode(spend_psbt_str.trim_matches('"')).unwrap();
+        assert_eq!(
+            SpendTransaction::from_psbt_serialized(&raw_spend_psbt),
+            Err(TransactionSerialisationError::Validation(
+                PsbtValidationError::NonStandard(StandardnessError::NonStandardOutput(
+                    Script::new()
+                ))
+            ))
+        );
+        let secp = secp256k1::Secp256k1::new();
+        check_borrowed_checks(&secp, &emergency_tx);
+        check_borrowed_checks(&secp, &unvault_tx);
+        check_borrowed_checks(&secp, &cancel_tx);
+        check_borrowed_checks(&secp, &unemergency_tx);
+
+        // A witness Script that can't be satisfied is not standard
+        let unsatisfiable_ms =
+            miniscript::Miniscript::<PublicKey, miniscript::Segwitv0>::from_str_insane(
+                "and_v(v:pk(02a17786aca5ea2118e9209702454ab432d5b2c656f8ae19447d4ff3e7317d3b41),0)",
+            )
+            .unwrap();
+        let mut unsatisfiable_psbt = unvault_tx.psbt().clone();
+        unsatisfiable_psbt.inputs[0].witness_script = Some(unsatisfiable_ms.encode());
+        unsatisfiable_psbt.inputs[0]
+            .witness_utxo
+            .as_mut()
+            .unwrap()
+            .script_pubkey = unsatisfiable_ms.encode().to_v0_p2wsh();
+        assert_eq!(
+            UnvaultTransaction::from_psbt_serialized(&serialize(&unsatisfiable_psbt)),
+            Err(TransactionSerialisationError::Validation(
+                PsbtValidationError::NonStandard(StandardnessError::Unsatisfiable)
+            ))
+        );
+
+        let spend_psbt: Psbt = deserialize(&raw_spend_psbt).unwrap();
+        assert_eq!(serialize_hex(&spend_psbt.extract_tx()), "020000000001042a9eb96ed62b3a35883fe632def858e8b80c946ea45f18b364138dfe14dcd70e00000000005ed000003a33ec03af230cf5ae463c2b645f003753bfb06da807b02b89428932cacfaa2301000000005ed000001d9b05aa32106ebb6cf12aefa1115c541b61847aa97823a04be4b77740bfcafc00000000005ed00000e10a83edae847b148100f166ddd65428df8232842df9c26c4ed584313004dc7100000000005ed0000002006f0200000000002200202a3ba224413511e5fc8447c9101d477e2f95db7113ae9fca0b1ef84aac122c605cf6c30000000000000500483045022100a36217e123dea9719dbbc704075dc191f08393537e91ff2630eaf0c7ab89677802207604b290f81148edf8f33c0f84f9aad1391a3513e7a687f721267fc48247adde01473044022055da6db73cf4af14bf8294933dc1b738841c2d6ad371215ceafb61701ac14d9402203626f79d9367ae382041136e52bb378df836b16a97b71c15333bfa3523fbdba701483045022100b2a1b4559bca2719b4abaa7c172329f97b198d5eda2d944d24b684cb42291232022038d74603e78e8e35e02adbe08f93ce90d5d407508463f8e425ddd98abe8fda1701ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b26805004730440220194744ced4f4637ba2b351bd2562632e93a0e39cc087702514fc2b7fa2da4c0a0220700803ec7e681b1ea31b6463711912dc70bad7bbf50b9f3e063c942a8c1bfe72014730440220216306533836fccc08f07cd8ede702f7ef283539943dc10b93576892ed807217022019bd34f280f74578331377b15cb0f3184d30b2ddb87edd79a0bc63db17aa726b0147304402203a24c13039e1a5abdd8d22dc44036b415b96a4a6cf449145f5bcc89a48cf32af022052c6a253de2c38e41fff9cf16f4869a78e07535ec5806a2ff3f985cb7993fbe201ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b2680500483045022100bea60c83db41973c639d42cd3525efe82b15456bfba0a904fb77ccb8a8e054cb02206498d4a777c56f943f388eb0f1d765ee8395d1c2e781486d0dd4f0700adc8f0901473044022052ebc8f31d96bd172f2491cd85b0ef9b4aa1f2408e185781cd57a550d7b4f463022069f9e78d039665d5a53c13752d1719e567928c465219a64aa7ee5bc89578b4ad01483045022100b29bf7526aab5fad36f77ecd628352afc12d00c32a0747ad91dd61aae767e4d2022023f0c040ee84caf653d541d8b5f1ac6472e52199056112d99d3a739e57bfaac501ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b2680500483045022100af1f4b2c3455b044970e8bf62c9e75b4b3e87b5bac7af3b3e33a101e3eebed7202204c377b3764a7dfeccb2f82af327eb23dbf408ae48284a1a7206f43dc04a0b39701483045022100f221ee515d63aef0f27545b736367d0d1ae3ce4433b8818686587decc048b1cc0220536fdfb7470dcd28db813d0efcc2acb364a4d1eece7afcdc9510525993f7487401483045022100faca69e1e8c7b969f0ca666a358693b6bac50b9c02c3722dc2d27a0ccc664563022006ce6039bfcae8a28d74b3d584c37723b466983a4c0ccb63591df74185850a5101ab2103dacf1ec4d8caaabac45e9237e09d69aadce1b8945dcc4776fe73fb9f4c31f7a4ac51876476a914594f6cd0c51687611968c77d63f40f0422ee26ae88ac6b76a9147ec81e31ce46a8c539882613ee54444fab4fe8a288ac6c93528767522102bf9959bfd4e22513e55bb5905ef3a2a29f9f924adb00c627fd1d92b67ff9cf942102fe9abf103eaa2e1180328774261155380eff416a179e61b0a4be99abcaf88d9b52af035ed000b26800000000");
     }
 }
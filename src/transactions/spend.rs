@@ -1,22 +1,20 @@
 use crate::{
     error::*,
     scripts::*,
+    standardness,
     transactions::{utils, RevaultTransaction, INSANE_FEES, MAX_STANDARD_TX_WEIGHT, TX_VERSION},
     txins::*,
     txouts::*,
 };
 
-use miniscript::{
-    bitcoin::{
-        blockdata::constants::max_money,
-        consensus::encode::Decodable,
-        util::psbt::{
-            Global as PsbtGlobal, Input as PsbtIn, Output as PsbtOut,
-            PartiallySignedTransaction as Psbt,
-        },
-        Amount, Network, SigHashType, Transaction,
+use miniscript::bitcoin::{
+    blockdata::constants::max_money,
+    consensus::encode::Decodable,
+    util::psbt::{
+        Global as PsbtGlobal, Input as PsbtIn, Output as PsbtOut,
+        PartiallySignedTransaction as Psbt,
     },
-    DescriptorTrait,
+    Amount, Network, SigHashType, Transaction,
 };
 
 #[cfg(feature = "use-serde")]
@@ -124,6 +122,8 @@ impl SpendTransaction {
         if total_weight > MAX_STANDARD_TX_WEIGHT as usize {
             return Err(TransactionCreationError::TooLarge);
         }
+        // .. nor one that wouldn't be relayed.
+        standardness::check_psbt(&psbt)?;
 
         if value_out > max_money(Network::Bitcoin) {
             return Err(TransactionCreationError::InsaneAmounts);
@@ -219,18 +219,12 @@ impl SpendTransaction {
                     .try_into()
                     .expect("Bug: witness size >u64::MAX")
             } else {
-                // FIXME: this panic can probably be triggered...
-                miniscript::descriptor::Wsh::new(
-                    miniscript::Miniscript::parse(
-                        txin.witness_script
-                            .as_ref()
-                            .expect("Unvault txins always have a witness Script"),
-                    )
-                    .expect("UnvaultTxIn witness_script is created from a Miniscript"),
+                utils::max_sat_weight(
+                    txin.witness_script
+                        .as_ref()
+                        .expect("Unvault txins always have a witness Script"),
                 )
-                .expect("")
-                .max_satisfaction_weight()
-                .expect("It's a sane Script, derived from a Miniscript")
+                .expect("Checked in the common sanity checks")
                 .try_into()
                 .expect("Can't be >u64::MAX")
             };
@@ -281,18 +275,9 @@ impl SpendTransaction {
                 return Err(PsbtValidationError::MissingInWitnessScript(input.clone()).into());
             }
 
-            max_sat_weight += miniscript::descriptor::Wsh::new(
-                miniscript::Miniscript::parse(
-                    input
-                        .witness_script
-                        .as_ref()
-                        .ok_or_else(|| PsbtValidationError::InvalidInputField(input.clone()))?,
-                )
-                .map_err(|_| PsbtValidationError::InvalidInputField(input.clone()))?,
-            )
-            .map_err(|_| PsbtValidationError::InvalidInputField(input.clone()))?
-            .max_satisfaction_weight()
-            .map_err(|_| PsbtValidationError::InvalidInputField(input.clone()))?;
+            max_sat_weight +=
+                utils::max_sat_weight(input.witness_script.as_ref().expect("Checked just above"))
+                    .expect("Checked in the common sanity checks");
         }
 
         // Make sure the transaction cannot get out of standardness bounds once finalized
@@ -1,12 +1,31 @@
-use crate::{error::*, transactions::TX_VERSION};
+use crate::{error::*, standardness, transactions::TX_VERSION};
+
+use miniscript::{
+    bitcoin::{
+        blockdata::constants::max_money,
+        secp256k1,
+        util::psbt::{Input as PsbtIn, PartiallySignedTransaction as Psbt},
+        Network, OutPoint, Script, SigHashType,
+    },
+    psbt::PsbtInputSatisfier,
+    Descriptor, DescriptorTrait, Interpreter,
+};
 
-use miniscript::bitcoin::{
-    blockdata::constants::max_money,
-    util::psbt::{Input as PsbtIn, PartiallySignedTransaction as Psbt},
-    Network, OutPoint, SigHashType,
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
 };
 
-use std::collections::HashSet;
+// The maximum number of witness Scripts we keep the analysis of.
+const SCRIPT_ANALYSIS_CACHE_SIZE: usize = 1_024;
+
+thread_local! {
+    // The satisfaction weight and number of stack items of the witness Scripts we already
+    // analysed. Reparsing the Miniscript is costly, and we query the same Scripts over and over
+    // when estimating fees or checking standardness.
+    static SCRIPT_ANALYSIS_CACHE: RefCell<HashMap<Script, (usize, usize)>> =
+        RefCell::new(HashMap::new());
+}
 
 /// Boilerplate for defining a Revault transaction as a newtype over a Psbt and implementing
 /// RevaultTransaction for it.
@@ -150,11 +169,12 @@ pub fn psbt_common_sanity_checks(psbt: Psbt) -> Result<Psbt, PsbtValidationError
             is_final = Some(false);
         }
 
-        // If the witness script is provided, it must be a sane Miniscript
+        // If the witness script is provided, it must be a sane Miniscript whose satisfaction
+        // would be relayed
         if let Some(ref script) = input.witness_script {
-            let _: miniscript::Miniscript<_, miniscript::Segwitv0> =
-                miniscript::Miniscript::parse(script)
-                    .map_err(|_| PsbtValidationError::InvalidInWitnessScript(input.clone()))?;
+            let (_, stack_items) = analyse_witness_script(script)
+                .ok_or_else(|| PsbtValidationError::InvalidInWitnessScript(input.clone()))??;
+            standardness::check_p2wsh_limits(script.len(), stack_items)?;
         }
 
         // We'll then check it doesn't create more than it spends
@@ -209,6 +229,9 @@ pub fn psbt_common_sanity_checks(psbt: Psbt) -> Result<Psbt, PsbtValidationError
         return Err(PsbtValidationError::InsaneAmounts);
     }
 
+    // The outputs, the sigops and the finalized witnesses must also be standard
+    standardness::check_psbt(&psbt)?;
+
     Ok(psbt)
 }
 
@@ -293,3 +316,171 @@ pub fn p2wsh_input_index(psbt: &Psbt) -> Option<usize> {
             == Some(true)
     })
 }
+
+// Get the maximum satisfaction weight and number of stack items (not counting the witness
+// Script itself) of this witness Script. Returns None if it's not a sane Miniscript, and
+// StandardnessError::Unsatisfiable if it can't be satisfied.
+fn analyse_witness_script(
+    witness_script: &Script,
+) -> Option<Result<(usize, usize), StandardnessError>> {
+    if let Some(analysis) =
+        SCRIPT_ANALYSIS_CACHE.with(|cache| cache.borrow().get(witness_script).copied())
+    {
+        return Some(Ok(analysis));
+    }
+
+    // Miniscript's sanity checks would reject a Script that can't be satisfied as exceeding the
+    // resource limits, check it beforehand to report it as such.
+    let ms = miniscript::Miniscript::parse_insane(witness_script).ok()?;
+    let stack_items = match ms.ext.stack_elem_count_sat {
+        Some(stack_items) => stack_items,
+        None => return Some(Err(StandardnessError::Unsatisfiable)),
+    };
+    ms.sanity_check().ok()?;
+    let weight = miniscript::descriptor::Wsh::new(ms)
+        .ok()?
+        .max_satisfaction_weight()
+        .ok()?;
+    SCRIPT_ANALYSIS_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= SCRIPT_ANALYSIS_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(witness_script.clone(), (weight, stack_items));
+    });
+
+    Some(Ok((weight, stack_items)))
+}
+
+/// Get the maximum size, in weight units, a satisfaction for this witness Script would cost.
+/// Returns None if it's not a sane Miniscript, or if it can't be satisfied.
+pub fn max_sat_weight(witness_script: &Script) -> Option<usize> {
+    analyse_witness_script(witness_script)
+        .and_then(Result::ok)
+        .map(|(weight, _)| weight)
+}
+
+/// The checks a BIP174 Signer must perform on an input before adding a signature to it.
+pub fn signer_checks(psbtin: &PsbtIn) -> Result<(), InputSatisfactionError> {
+    // If we were already finalized, our witness script was wiped.
+    if psbtin.final_script_witness.is_some() {
+        return Err(InputSatisfactionError::AlreadyFinalized);
+    }
+
+    // BIP174:
+    // For a Signer to only produce valid signatures for what it expects to sign, it must
+    // check that the following conditions are true:
+    // -- If a witness UTXO is provided, no non-witness signature may be created.
+    let prev_txo = psbtin
+        .witness_utxo
+        .as_ref()
+        .expect("Cannot be reached. We only create transactions with witness_utxo.");
+    assert!(
+        psbtin.non_witness_utxo.is_none(),
+        "We never create transactions with non_witness_utxo."
+    );
+
+    // -- If a witnessScript is provided, the scriptPubKey or the redeemScript must be for
+    // that witnessScript
+    if let Some(witness_script) = &psbtin.witness_script {
+        let expected_script_pubkey = witness_script.to_v0_p2wsh();
+        assert!(
+            expected_script_pubkey == prev_txo.script_pubkey,
+            "We create TxOut scriptPubKey out of this exact witnessScript."
+        );
+    } else {
+        // We only use P2WSH utxos internally. External inputs are only ever added for fee
+        // bumping, for which we require P2WPKH.
+        assert!(prev_txo.script_pubkey.is_v0_p2wpkh());
+    }
+    assert!(
+        psbtin.redeem_script.is_none(),
+        "We never create Psbt input with legacy txos."
+    );
+
+    Ok(())
+}
+
+/// Check this PSBT input can be finalized, without modifying the PSBT.
+///
+/// Performs the same checks as miniscript's PSBT Finalizer for the P2WSH and P2WPKH inputs of
+/// Revault transactions: the signatures must be well-formed, the witness must be satisfiable and
+/// pass the interpreter checks.
+pub fn is_input_finalizable<C: secp256k1::Verification>(
+    psbt: &Psbt,
+    index: usize,
+    secp: &secp256k1::Secp256k1<C>,
+) -> bool {
+    let psbtin = &psbt.inputs[index];
+    let prev_txo = match psbtin.witness_utxo {
+        Some(ref txo) => txo,
+        None => return false,
+    };
+
+    let sighash_type = psbtin.sighash_type.unwrap_or(SigHashType::All);
+    let well_formed_sigs = psbtin
+        .partial_sigs
+        .values()
+        .all(|rawsig| match rawsig.split_last() {
+            Some((flag, sig)) => {
+                // The Finalizer uses the deprecated from_u32, which is an alias of this one.
+                SigHashType::from_u32_consensus(*flag as u32) == sighash_type
+                    && secp256k1::Signature::from_der(sig).is_ok()
+            }
+            None => false,
+        });
+    if !well_formed_sigs || psbtin.redeem_script.is_some() {
+        return false;
+    }
+
+    let descriptor = if prev_txo.script_pubkey.is_v0_p2wsh() {
+        match psbtin.witness_script {
+            Some(ref witness_script) if witness_script.to_v0_p2wsh() == prev_txo.script_pubkey => {
+                match miniscript::Miniscript::parse_insane(witness_script)
+                    .ok()
+                    .and_then(|ms| Descriptor::new_wsh(ms).ok())
+                {
+                    Some(descriptor) => descriptor,
+                    None => return false,
+                }
+            }
+            _ => return false,
+        }
+    } else if prev_txo.script_pubkey.is_v0_p2wpkh() {
+        match psbtin.partial_sigs.keys().find(|pubkey| {
+            pubkey
+                .wpubkey_hash()
+                .map(|wpkh| Script::new_v0_wpkh(&wpkh) == prev_txo.script_pubkey)
+                .unwrap_or(false)
+        }) {
+            Some(pubkey) => match Descriptor::new_wpkh(*pubkey) {
+                Ok(descriptor) => descriptor,
+                Err(_) => return false,
+            },
+            None => return false,
+        }
+    } else {
+        return false;
+    };
+
+    let (witness, script_sig) =
+        match descriptor.get_satisfaction(PsbtInputSatisfier::new(psbt, index)) {
+            Ok(satisfaction) => satisfaction,
+            Err(_) => return false,
+        };
+
+    // Double check the satisfaction with the interpreter, as the Finalizer does
+    let tx = &psbt.global.unsigned_tx;
+    let mut interpreter = match Interpreter::from_txdata(
+        &prev_txo.script_pubkey,
+        &script_sig,
+        &witness,
+        tx.lock_time,
+        tx.input[index].sequence,
+    ) {
+        Ok(interpreter) => interpreter,
+        Err(_) => return false,
+    };
+    let verify_sig = interpreter.sighash_verify(secp, tx, index, prev_txo.value);
+    interpreter.iter(verify_sig).all(|res| res.is_ok())
+}
@@ -3,7 +3,7 @@
 //! Wrappers around bitcoin's OutPoint and previous TxOut to statically check Revault
 //! transaction creation and ease PSBT management.
 
-use crate::txouts::{CpfpTxOut, DepositTxOut, FeeBumpTxOut, UnvaultTxOut};
+use crate::txouts::{CpfpTxOut, DeepVaultTxOut, DepositTxOut, FeeBumpTxOut, UnvaultTxOut};
 
 use miniscript::bitcoin::{OutPoint, TxIn};
 
@@ -130,3 +130,22 @@ impl CpfpTxIn {
         }
     }
 }
+
+implem_revault_txin!(
+    DeepVaultTxIn,
+    DeepVaultTxOut,
+    doc = "An Emergency Deep Vault txo spent by the \
+            [DeepVaultRecovery](crate::transactions::DeepVaultRecoveryTransaction) transaction."
+);
+impl DeepVaultTxIn {
+    /// Instanciate a TxIn referencing a Deep Vault txout. The sequence must be explicitly
+    /// specified as it spends a CSV-encumbered path, use the
+    /// [Emergency descriptor's timelock](crate::scripts::EmergencyDescriptor::csv_value).
+    pub fn new(outpoint: OutPoint, prev_txout: DeepVaultTxOut, sequence: u32) -> DeepVaultTxIn {
+        DeepVaultTxIn {
+            outpoint,
+            prev_txout,
+            sequence,
+        }
+    }
+}
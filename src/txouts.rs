@@ -6,7 +6,8 @@
 use crate::{
     error::TxoutCreationError,
     scripts::{
-        DerivedCpfpDescriptor, DerivedDepositDescriptor, DerivedUnvaultDescriptor, EmergencyAddress,
+        DerivedCpfpDescriptor, DerivedDepositDescriptor, DerivedUnvaultDescriptor,
+        EmergencyAddress, EmergencyDescriptor,
     },
 };
 
@@ -36,15 +37,8 @@ pub trait RevaultInternalTxOut: fmt::Debug + Clone + PartialEq + RevaultTxOut {
     fn into_witness_script(self) -> Script;
 
     /// Get the maximum size, in weight units, a satisfaction for this scriptPubKey would cost.
-    fn max_sat_weight(&self) -> usize {
-        miniscript::descriptor::Wsh::new(
-            miniscript::Miniscript::parse(self.witness_script())
-                .expect("The witness_script is always created from a Miniscript"),
-        )
-        .expect("The witness_script is always a P2WSH")
-        .max_satisfaction_weight()
-        .expect("It's a sane Script, derived from a Miniscript")
-    }
+    /// It is computed once from the descriptor at creation, or from the witness Script otherwise.
+    fn max_sat_weight(&self) -> usize;
 }
 
 macro_rules! implem_revault_txout {
@@ -54,6 +48,8 @@ macro_rules! implem_revault_txout {
         pub struct $struct_name {
             txout: TxOut,
             witness_script: Script,
+            // Not set for a default txout
+            max_sat_weight: Option<usize>,
         }
 
         impl RevaultTxOut for $struct_name {
@@ -74,6 +70,18 @@ macro_rules! implem_revault_txout {
             fn into_witness_script(self) -> Script {
                 self.witness_script
             }
+
+            fn max_sat_weight(&self) -> usize {
+                self.max_sat_weight.unwrap_or_else(|| {
+                    miniscript::descriptor::Wsh::new(
+                        miniscript::Miniscript::parse(&self.witness_script)
+                            .expect("The witness_script is always created from a Miniscript"),
+                    )
+                    .expect("The witness_script is always a P2WSH")
+                    .max_satisfaction_weight()
+                    .expect("It's a sane Script, derived from a Miniscript")
+                })
+            }
         }
     };
 }
@@ -93,6 +101,12 @@ impl DepositTxOut {
                 script_pubkey: script_descriptor.inner().script_pubkey(),
             },
             witness_script: script_descriptor.inner().explicit_script(),
+            max_sat_weight: Some(
+                script_descriptor
+                    .inner()
+                    .max_satisfaction_weight()
+                    .expect("It's a sane Script, derived from a Miniscript"),
+            ),
         }
     }
 }
@@ -107,6 +121,12 @@ impl UnvaultTxOut {
                 script_pubkey: script_descriptor.inner().script_pubkey(),
             },
             witness_script: script_descriptor.inner().explicit_script(),
+            max_sat_weight: Some(
+                script_descriptor
+                    .inner()
+                    .max_satisfaction_weight()
+                    .expect("It's a sane Script, derived from a Miniscript"),
+            ),
         }
     }
 }
@@ -134,6 +154,31 @@ impl RevaultTxOut for EmergencyTxOut {
     }
 }
 
+implem_revault_txout!(
+    DeepVaultTxOut,
+    doc = "An [Emergency](crate::transactions::EmergencyTransaction) or \
+            [UnvaultEmergency](crate::transactions::UnvaultEmergencyTransaction) output, for which \
+            we know the [Emergency descriptor](crate::scripts::EmergencyDescriptor)."
+);
+impl DeepVaultTxOut {
+    /// Create a new DeepVaultTxOut out of the given Emergency descriptor
+    pub fn new(value: Amount, script_descriptor: &EmergencyDescriptor) -> DeepVaultTxOut {
+        DeepVaultTxOut {
+            txout: TxOut {
+                value: value.as_sat(),
+                script_pubkey: script_descriptor.inner().script_pubkey(),
+            },
+            witness_script: script_descriptor.inner().explicit_script(),
+            max_sat_weight: Some(
+                script_descriptor
+                    .inner()
+                    .max_satisfaction_weight()
+                    .expect("It's a sane Script, derived from a Miniscript"),
+            ),
+        }
+    }
+}
+
 implem_revault_txout!(
     CpfpTxOut,
     doc = "The output attached to the [Unvault](crate::transactions::UnvaultTransaction) \
@@ -148,6 +193,12 @@ impl CpfpTxOut {
                 script_pubkey: script_descriptor.inner().script_pubkey(),
             },
             witness_script: script_descriptor.inner().explicit_script(),
+            max_sat_weight: Some(
+                script_descriptor
+                    .inner()
+                    .max_satisfaction_weight()
+                    .expect("It's a sane Script, derived from a Miniscript"),
+            ),
         }
     }
 }
@@ -0,0 +1,630 @@
+//! # Revault vaults lifecycle
+//!
+//! A [Vault] tracks a deposit along with its chain of pre-signed transactions, and moves through
+//! explicit [states](VaultStatus) as the pre-signed transactions get signed and as transactions
+//! are observed on the network. See [practical-revault](https://github.com/revault/practical-revault)
+//! for the lifecycle of a vault.
+
+use crate::{
+    error::{Error, VaultError},
+    scripts::RevaultDescriptors,
+    transactions::{
+        transaction_chain, CancelTransaction, EmergencyTransaction, RevaultTransaction,
+        UnvaultEmergencyTransaction, UnvaultTransaction,
+    },
+};
+
+use miniscript::bitcoin::{secp256k1, util::bip32, Amount, OutPoint, Txid};
+
+use std::fmt;
+
+/// The state of a vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VaultStatus {
+    /// The deposit transaction is not confirmed yet
+    Unconfirmed,
+    /// The deposit transaction is confirmed
+    Funded,
+    /// The revocation transactions (Cancel, Emergency, UnvaultEmergency) are fully signed
+    Secured,
+    /// The Unvault transaction is fully signed, the funds can be spent by the managers
+    Active,
+    /// The Unvault transaction was broadcast
+    Unvaulting,
+    /// The Unvault transaction is confirmed
+    Unvaulted,
+    /// A Spend transaction was broadcast
+    Spending,
+    /// A Spend transaction is confirmed
+    Spent,
+    /// The Cancel transaction was broadcast
+    Canceling,
+    /// The Cancel transaction is confirmed
+    Canceled,
+    /// The Emergency transaction was broadcast
+    EmergencyVaulting,
+    /// The Emergency transaction is confirmed
+    EmergencyVaulted,
+    /// The Unvault Emergency transaction was broadcast
+    UnvaultEmergencyVaulting,
+    /// The Unvault Emergency transaction is confirmed
+    UnvaultEmergencyVaulted,
+}
+
+impl fmt::Display for VaultStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Self::Unconfirmed => "unconfirmed",
+            Self::Funded => "funded",
+            Self::Secured => "secured",
+            Self::Active => "active",
+            Self::Unvaulting => "unvaulting",
+            Self::Unvaulted => "unvaulted",
+            Self::Spending => "spending",
+            Self::Spent => "spent",
+            Self::Canceling => "canceling",
+            Self::Canceled => "canceled",
+            Self::EmergencyVaulting => "emergencyvaulting",
+            Self::EmergencyVaulted => "emergencyvaulted",
+            Self::UnvaultEmergencyVaulting => "unvaultemergencyvaulting",
+            Self::UnvaultEmergencyVaulted => "unvaultemergencyvaulted",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// Something that happened to a vault on the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VaultEvent {
+    /// The deposit transaction got confirmed
+    DepositConfirmed,
+    /// The Unvault transaction was broadcast
+    UnvaultBroadcast,
+    /// The Unvault transaction got confirmed
+    UnvaultConfirmed,
+    /// A Spend transaction with this txid was broadcast
+    SpendBroadcast(Txid),
+    /// A Spend transaction with this txid got confirmed
+    SpendConfirmed(Txid),
+    /// The Cancel transaction was broadcast
+    CancelBroadcast,
+    /// The Cancel transaction got confirmed
+    CancelConfirmed,
+    /// The Emergency transaction was broadcast
+    EmergencyBroadcast,
+    /// The Emergency transaction got confirmed
+    EmergencyConfirmed,
+    /// The Unvault Emergency transaction was broadcast
+    UnvaultEmergencyBroadcast,
+    /// The Unvault Emergency transaction got confirmed
+    UnvaultEmergencyConfirmed,
+}
+
+impl fmt::Display for VaultEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DepositConfirmed => write!(f, "deposit confirmed"),
+            Self::UnvaultBroadcast => write!(f, "unvault broadcast"),
+            Self::UnvaultConfirmed => write!(f, "unvault confirmed"),
+            Self::SpendBroadcast(txid) => write!(f, "spend '{}' broadcast", txid),
+            Self::SpendConfirmed(txid) => write!(f, "spend '{}' confirmed", txid),
+            Self::CancelBroadcast => write!(f, "cancel broadcast"),
+            Self::CancelConfirmed => write!(f, "cancel confirmed"),
+            Self::EmergencyBroadcast => write!(f, "emergency broadcast"),
+            Self::EmergencyConfirmed => write!(f, "emergency confirmed"),
+            Self::UnvaultEmergencyBroadcast => write!(f, "unvault emergency broadcast"),
+            Self::UnvaultEmergencyConfirmed => write!(f, "unvault emergency confirmed"),
+        }
+    }
+}
+
+/// One of the transactions pre-signed for a vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PresignedTransaction {
+    Unvault,
+    Cancel,
+    Emergency,
+    UnvaultEmergency,
+}
+
+impl fmt::Display for PresignedTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let transaction = match self {
+            Self::Unvault => "unvault",
+            Self::Cancel => "cancel",
+            Self::Emergency => "emergency",
+            Self::UnvaultEmergency => "unvaultemergency",
+        };
+        write!(f, "{}", transaction)
+    }
+}
+
+/// A deposit and its chain of pre-signed transactions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vault {
+    deposit_outpoint: OutPoint,
+    deposit_amount: Amount,
+    derivation_index: bip32::ChildNumber,
+    status: VaultStatus,
+    unvault_tx: UnvaultTransaction,
+    cancel_tx: CancelTransaction,
+    emergency_tx: EmergencyTransaction,
+    unvault_emergency_tx: UnvaultEmergencyTransaction,
+    spend_txid: Option<Txid>,
+}
+
+impl Vault {
+    /// Create an [Unconfirmed](VaultStatus::Unconfirmed) vault for this deposit, along with its
+    /// (unsigned) chain of pre-signed transactions.
+    pub fn new<C: secp256k1::Verification>(
+        deposit_outpoint: OutPoint,
+        deposit_amount: Amount,
+        derivation_index: bip32::ChildNumber,
+        descriptors: &RevaultDescriptors,
+        lock_time: u32,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<Vault, Error> {
+        let (unvault_tx, cancel_tx, emergency_tx, unvault_emergency_tx) = transaction_chain(
+            deposit_outpoint,
+            deposit_amount,
+            descriptors.deposit_descriptor(),
+            descriptors.unvault_descriptor(),
+            descriptors.cpfp_descriptor(),
+            derivation_index,
+            descriptors.emergency_address().clone(),
+            lock_time,
+            secp,
+        )?;
+
+        Ok(Vault {
+            deposit_outpoint,
+            deposit_amount,
+            derivation_index,
+            status: VaultStatus::Unconfirmed,
+            unvault_tx,
+            cancel_tx,
+            emergency_tx,
+            unvault_emergency_tx,
+            spend_txid: None,
+        })
+    }
+
+    pub fn deposit_outpoint(&self) -> OutPoint {
+        self.deposit_outpoint
+    }
+
+    pub fn deposit_amount(&self) -> Amount {
+        self.deposit_amount
+    }
+
+    pub fn derivation_index(&self) -> bip32::ChildNumber {
+        self.derivation_index
+    }
+
+    pub fn status(&self) -> VaultStatus {
+        self.status
+    }
+
+    pub fn unvault_tx(&self) -> &UnvaultTransaction {
+        &self.unvault_tx
+    }
+
+    pub fn cancel_tx(&self) -> &CancelTransaction {
+        &self.cancel_tx
+    }
+
+    pub fn emergency_tx(&self) -> &EmergencyTransaction {
+        &self.emergency_tx
+    }
+
+    pub fn unvault_emergency_tx(&self) -> &UnvaultEmergencyTransaction {
+        &self.unvault_emergency_tx
+    }
+
+    /// The Unvault output, spent by either the Spend or a revocation transaction.
+    pub fn unvault_outpoint(&self) -> OutPoint {
+        // The Cancel transaction is created without fee-bumping input
+        self.cancel_tx.tx().input[0].previous_output
+    }
+
+    /// The txid of the Spend transaction, if one was observed.
+    pub fn spend_txid(&self) -> Option<Txid> {
+        self.spend_txid
+    }
+
+    /// Add a stakeholder's signature to one of the pre-signed transactions.
+    ///
+    /// The vault becomes [Secured](VaultStatus::Secured) once the deposit is confirmed and all
+    /// the revocation transactions are fully signed, then [Active](VaultStatus::Active) once the
+    /// Unvault transaction is too.
+    ///
+    /// # Errors
+    /// - If signing the Unvault transaction before the vault is secured, or signing a
+    ///   transaction once the vault was unvaulted or revaulted.
+    /// - If the signature is invalid.
+    pub fn add_signature<C: secp256k1::Verification>(
+        &mut self,
+        transaction: PresignedTransaction,
+        pubkey: secp256k1::PublicKey,
+        signature: secp256k1::Signature,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), VaultError> {
+        // Never sign the Unvault transaction before the revocation transactions are signed.
+        let can_sign = match transaction {
+            PresignedTransaction::Unvault => matches!(self.status, VaultStatus::Secured),
+            _ => matches!(self.status, VaultStatus::Unconfirmed | VaultStatus::Funded),
+        };
+        if !can_sign {
+            return Err(VaultError::UnexpectedSignature(self.status, transaction));
+        }
+
+        match transaction {
+            PresignedTransaction::Unvault => self.unvault_tx.add_sig(pubkey, signature, secp),
+            PresignedTransaction::Cancel => self.cancel_tx.add_cancel_sig(pubkey, signature, secp),
+            PresignedTransaction::Emergency => {
+                self.emergency_tx.add_emer_sig(pubkey, signature, secp)
+            }
+            PresignedTransaction::UnvaultEmergency => self
+                .unvault_emergency_tx
+                .add_emer_sig(pubkey, signature, secp),
+        }?;
+
+        if self.status == VaultStatus::Funded && self.is_secured(secp) {
+            self.status = VaultStatus::Secured;
+        } else if self.status == VaultStatus::Secured && self.unvault_tx.is_finalizable(secp) {
+            self.status = VaultStatus::Active;
+        }
+
+        Ok(())
+    }
+
+    // Whether all the revocation transactions are fully signed
+    fn is_secured<C: secp256k1::Verification>(&self, secp: &secp256k1::Secp256k1<C>) -> bool {
+        self.cancel_tx.is_finalizable(secp)
+            && self.emergency_tx.is_finalizable(secp)
+            && self.unvault_emergency_tx.is_finalizable(secp)
+    }
+
+    /// Move the vault to its next state after this event happened. Conflicting transactions
+    /// may race, in which case a vault waiting for a transaction to confirm moves to the state of
+    /// whichever conflicting transaction confirms.
+    ///
+    /// # Errors
+    /// - If the event can't happen to a vault in the current state, in which case the state
+    ///   is left untouched.
+    pub fn process_event<C: secp256k1::Verification>(
+        &mut self,
+        event: VaultEvent,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<VaultStatus, VaultError> {
+        use VaultStatus::*;
+
+        let status = match (self.status, event) {
+            (Unconfirmed, VaultEvent::DepositConfirmed) => {
+                // The revocation transactions may have been signed before confirmation.
+                if !self.is_secured(secp) {
+                    Funded
+                } else if !self.unvault_tx.is_finalizable(secp) {
+                    Secured
+                } else {
+                    Active
+                }
+            }
+
+            // The Emergency transaction may race with the Unvault, they spend the same deposit.
+            (Active, VaultEvent::UnvaultBroadcast)
+            | (EmergencyVaulting, VaultEvent::UnvaultBroadcast) => Unvaulting,
+            (Active, VaultEvent::UnvaultConfirmed)
+            | (Unvaulting, VaultEvent::UnvaultConfirmed)
+            | (EmergencyVaulting, VaultEvent::UnvaultConfirmed) => Unvaulted,
+
+            (Unvaulted, VaultEvent::SpendBroadcast(txid))
+            | (Spending, VaultEvent::SpendBroadcast(txid)) => {
+                self.spend_txid = Some(txid);
+                Spending
+            }
+            // The Spend and the revocation transactions spend the same Unvault output.
+            (Unvaulted, VaultEvent::SpendConfirmed(txid))
+            | (Spending, VaultEvent::SpendConfirmed(txid))
+            | (Canceling, VaultEvent::SpendConfirmed(txid))
+            | (UnvaultEmergencyVaulting, VaultEvent::SpendConfirmed(txid)) => {
+                self.spend_txid = Some(txid);
+                Spent
+            }
+
+            (Unvaulting, VaultEvent::CancelBroadcast)
+            | (Unvaulted, VaultEvent::CancelBroadcast)
+            | (Spending, VaultEvent::CancelBroadcast) => Canceling,
+            (Unvaulting, VaultEvent::CancelConfirmed)
+            | (Unvaulted, VaultEvent::CancelConfirmed)
+            | (Spending, VaultEvent::CancelConfirmed)
+            | (Canceling, VaultEvent::CancelConfirmed)
+            | (UnvaultEmergencyVaulting, VaultEvent::CancelConfirmed) => Canceled,
+
+            (Funded, VaultEvent::EmergencyBroadcast)
+            | (Secured, VaultEvent::EmergencyBroadcast)
+            | (Active, VaultEvent::EmergencyBroadcast) => EmergencyVaulting,
+            (Funded, VaultEvent::EmergencyConfirmed)
+            | (Secured, VaultEvent::EmergencyConfirmed)
+            | (Active, VaultEvent::EmergencyConfirmed)
+            | (Unvaulting, VaultEvent::EmergencyConfirmed)
+            | (EmergencyVaulting, VaultEvent::EmergencyConfirmed) => EmergencyVaulted,
+
+            (Unvaulting, VaultEvent::UnvaultEmergencyBroadcast)
+            | (Unvaulted, VaultEvent::UnvaultEmergencyBroadcast)
+            | (Spending, VaultEvent::UnvaultEmergencyBroadcast)
+            | (Canceling, VaultEvent::UnvaultEmergencyBroadcast) => UnvaultEmergencyVaulting,
+            (Unvaulting, VaultEvent::UnvaultEmergencyConfirmed)
+            | (Unvaulted, VaultEvent::UnvaultEmergencyConfirmed)
+            | (Spending, VaultEvent::UnvaultEmergencyConfirmed)
+            | (Canceling, VaultEvent::UnvaultEmergencyConfirmed)
+            | (UnvaultEmergencyVaulting, VaultEvent::UnvaultEmergencyConfirmed) => {
+                UnvaultEmergencyVaulted
+            }
+
+            (status, event) => return Err(VaultError::InvalidTransition(status, event)),
+        };
+        self.status = status;
+
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PresignedTransaction, Vault, VaultEvent, VaultStatus};
+    use crate::{
+        error::VaultError,
+        test_utils::{descriptors, get_random_privkey, sign_vault, xpubs},
+    };
+
+    use miniscript::bitcoin::{secp256k1, util::bip32, Amount, OutPoint, Txid};
+
+    use std::str::FromStr;
+
+    #[test]
+    fn vault_lifecycle() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let stakeholders: Vec<_> = (0..3).map(|_| get_random_privkey(&mut rng)).collect();
+        let managers: Vec<_> = (0..2).map(|_| get_random_privkey(&mut rng)).collect();
+        let descriptors = descriptors(xpubs(&stakeholders, &secp), xpubs(&managers, &secp), 2, 144);
+
+        let new_vault = || {
+            Vault::new(
+                OutPoint::from_str(
+                    "39a8212c6a9b467680d43e47b61b8363fe1febb761f9f548eb4a432b2bc9bbec:0",
+                )
+                .unwrap(),
+                Amount::from_sat(10_000_000),
+                bip32::ChildNumber::from(12),
+                &descriptors,
+                0,
+                &secp,
+            )
+            .unwrap()
+        };
+        let mut vault = new_vault();
+        assert_eq!(vault.status(), VaultStatus::Unconfirmed);
+        assert_eq!(vault.derivation_index(), bip32::ChildNumber::from(12));
+
+        // Can't sign the Unvault before the revocation transactions
+        assert_eq!(
+            sign_vault(
+                &mut vault,
+                PresignedTransaction::Unvault,
+                &stakeholders,
+                &secp
+            ),
+            Err(VaultError::UnexpectedSignature(
+                VaultStatus::Unconfirmed,
+                PresignedTransaction::Unvault
+            ))
+        );
+        // Nor can we unvault
+        assert_eq!(
+            vault.process_event(VaultEvent::UnvaultBroadcast, &secp),
+            Err(VaultError::InvalidTransition(
+                VaultStatus::Unconfirmed,
+                VaultEvent::UnvaultBroadcast
+            ))
+        );
+        assert_eq!(vault.status(), VaultStatus::Unconfirmed);
+
+        assert_eq!(
+            vault.process_event(VaultEvent::DepositConfirmed, &secp),
+            Ok(VaultStatus::Funded)
+        );
+        sign_vault(
+            &mut vault,
+            PresignedTransaction::Cancel,
+            &stakeholders,
+            &secp,
+        )
+        .unwrap();
+        sign_vault(
+            &mut vault,
+            PresignedTransaction::Emergency,
+            &stakeholders,
+            &secp,
+        )
+        .unwrap();
+        assert_eq!(vault.status(), VaultStatus::Funded);
+        sign_vault(
+            &mut vault,
+            PresignedTransaction::UnvaultEmergency,
+            &stakeholders,
+            &secp,
+        )
+        .unwrap();
+        assert_eq!(vault.status(), VaultStatus::Secured);
+        // Can't sign revocation transactions anymore
+        sign_vault(
+            &mut vault,
+            PresignedTransaction::Cancel,
+            &stakeholders,
+            &secp,
+        )
+        .unwrap_err();
+        sign_vault(
+            &mut vault,
+            PresignedTransaction::Unvault,
+            &stakeholders,
+            &secp,
+        )
+        .unwrap();
+        assert_eq!(vault.status(), VaultStatus::Active);
+
+        // Unvault, then a Spend attempt that gets canceled
+        let spend_txid =
+            Txid::from_str("4bb4545bb4bc8853cb03e42984d677fbe880c81e7d95609360eed0d8f45b52f8")
+                .unwrap();
+        for (event, status) in &[
+            (VaultEvent::UnvaultBroadcast, VaultStatus::Unvaulting),
+            (VaultEvent::UnvaultConfirmed, VaultStatus::Unvaulted),
+            (
+                VaultEvent::SpendBroadcast(spend_txid),
+                VaultStatus::Spending,
+            ),
+            (VaultEvent::CancelBroadcast, VaultStatus::Canceling),
+            (VaultEvent::CancelConfirmed, VaultStatus::Canceled),
+        ] {
+            assert_eq!(vault.process_event(*event, &secp), Ok(*status));
+        }
+        assert_eq!(vault.spend_txid(), Some(spend_txid));
+        // It's final
+        vault
+            .process_event(VaultEvent::SpendConfirmed(spend_txid), &secp)
+            .unwrap_err();
+        vault
+            .process_event(VaultEvent::EmergencyConfirmed, &secp)
+            .unwrap_err();
+        assert_eq!(vault.status(), VaultStatus::Canceled);
+
+        // All signatures may be gathered before the deposit confirms
+        let mut vault = new_vault();
+        for tx in &[
+            PresignedTransaction::Cancel,
+            PresignedTransaction::Emergency,
+            PresignedTransaction::UnvaultEmergency,
+        ] {
+            sign_vault(&mut vault, *tx, &stakeholders, &secp).unwrap();
+        }
+        assert_eq!(vault.status(), VaultStatus::Unconfirmed);
+        assert_eq!(
+            vault.process_event(VaultEvent::DepositConfirmed, &secp),
+            Ok(VaultStatus::Secured)
+        );
+        // Emergency from a secured vault
+        assert_eq!(
+            vault.process_event(VaultEvent::EmergencyBroadcast, &secp),
+            Ok(VaultStatus::EmergencyVaulting)
+        );
+        vault
+            .process_event(VaultEvent::CancelConfirmed, &secp)
+            .unwrap_err();
+        assert_eq!(
+            vault.process_event(VaultEvent::EmergencyConfirmed, &secp),
+            Ok(VaultStatus::EmergencyVaulted)
+        );
+    }
+
+    #[test]
+    fn vault_races() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = fastrand::Rng::new();
+        let stakeholders: Vec<_> = (0..2).map(|_| get_random_privkey(&mut rng)).collect();
+        let managers: Vec<_> = (0..2).map(|_| get_random_privkey(&mut rng)).collect();
+        let descriptors = descriptors(xpubs(&stakeholders, &secp), xpubs(&managers, &secp), 2, 144);
+        let spend_txid =
+            Txid::from_str("4bb4545bb4bc8853cb03e42984d677fbe880c81e7d95609360eed0d8f45b52f8")
+                .unwrap();
+
+        // Get an active vault through these events, then check the conflicting transaction
+        // that confirms wins.
+        let race = |events: &[VaultEvent], confirmed: VaultEvent, status: VaultStatus| {
+            let mut vault = Vault::new(
+                OutPoint::from_str(
+                    "39a8212c6a9b467680d43e47b61b8363fe1febb761f9f548eb4a432b2bc9bbec:1",
+                )
+                .unwrap(),
+                Amount::from_sat(10_000_000),
+                bip32::ChildNumber::from(3),
+                &descriptors,
+                0,
+                &secp,
+            )
+            .unwrap();
+            vault
+                .process_event(VaultEvent::DepositConfirmed, &secp)
+                .unwrap();
+            for tx in &[
+                PresignedTransaction::Cancel,
+                PresignedTransaction::Emergency,
+                PresignedTransaction::UnvaultEmergency,
+                PresignedTransaction::Unvault,
+            ] {
+                sign_vault(&mut vault, *tx, &stakeholders, &secp).unwrap();
+            }
+            assert_eq!(vault.status(), VaultStatus::Active);
+
+            for event in events {
+                vault.process_event(*event, &secp).unwrap();
+            }
+            assert_eq!(vault.process_event(confirmed, &secp), Ok(status));
+            vault
+        };
+
+        // The Spend confirms before the Cancel
+        let vault = race(
+            &[
+                VaultEvent::UnvaultBroadcast,
+                VaultEvent::UnvaultConfirmed,
+                VaultEvent::CancelBroadcast,
+            ],
+            VaultEvent::SpendConfirmed(spend_txid),
+            VaultStatus::Spent,
+        );
+        assert_eq!(vault.spend_txid(), Some(spend_txid));
+
+        // The Unvault is broadcast, or confirms, before the Emergency
+        race(
+            &[VaultEvent::EmergencyBroadcast],
+            VaultEvent::UnvaultBroadcast,
+            VaultStatus::Unvaulting,
+        );
+        race(
+            &[VaultEvent::EmergencyBroadcast],
+            VaultEvent::UnvaultConfirmed,
+            VaultStatus::Unvaulted,
+        );
+        // Or the Emergency confirms before the broadcast Unvault
+        race(
+            &[VaultEvent::EmergencyBroadcast, VaultEvent::UnvaultBroadcast],
+            VaultEvent::EmergencyConfirmed,
+            VaultStatus::EmergencyVaulted,
+        );
+
+        // The Cancel or the Spend confirm before the Unvault Emergency
+        race(
+            &[
+                VaultEvent::UnvaultBroadcast,
+                VaultEvent::UnvaultConfirmed,
+                VaultEvent::CancelBroadcast,
+                VaultEvent::UnvaultEmergencyBroadcast,
+            ],
+            VaultEvent::CancelConfirmed,
+            VaultStatus::Canceled,
+        );
+        let vault = race(
+            &[
+                VaultEvent::UnvaultBroadcast,
+                VaultEvent::UnvaultConfirmed,
+                VaultEvent::UnvaultEmergencyBroadcast,
+            ],
+            VaultEvent::SpendConfirmed(spend_txid),
+            VaultStatus::Spent,
+        );
+        assert_eq!(vault.spend_txid(), Some(spend_txid));
+    }
+}